@@ -0,0 +1,309 @@
+use crate::time;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+
+pub static FILENAME: &str = "breaker.yaml";
+
+pub static DEFAULT_MAX_FAILURES: u32 = 3;
+pub static DEFAULT_LOSS_WINDOW_HOURS: u64 = 24;
+pub static DEFAULT_MAX_EXCHANGE_ERRORS: u32 = 5;
+
+// trip points from the config.yaml breaker section. unset usd/fraction
+// limits are not checked.
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+pub struct Settings {
+    pub max_failures: Option<u32>, // consecutive aborted orders that submitted
+    pub max_loss_usd: Option<f64>, // net loss over the window
+    pub loss_window_hours: Option<u64>,
+    pub max_balance_drop: Option<f64>, // fraction lost between orders
+    pub max_exchange_errors: Option<u32>, // consecutive failed sheets on one exchange
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Halt {
+    pub reason: String,
+    pub time: String,
+}
+
+impl fmt::Display for Halt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({})", self.reason, self.time)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Pnl {
+    pub time: u64, // epoch seconds
+    pub usd: f64,
+}
+
+// one exchange leg of a finished order
+pub struct LegOutcome {
+    pub exchange: String,
+    pub filled: usize,
+    pub failures: usize,
+}
+
+// kept in breaker.yaml so a halt survives restarts and `yith halt` from
+// another process reaches a running daemon. the halt has a file of its own
+// (breaker.halt.yaml) so saving the counters never overwrites one.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Breaker {
+    #[serde(skip)]
+    pub halted: Option<Halt>,
+    #[serde(default)]
+    pub failures: u32,
+    #[serde(default)]
+    pub pnl: Vec<Pnl>,
+    #[serde(default)]
+    pub exchange_errors: BTreeMap<String, u32>,
+    pub last_total_usd: Option<f64>,
+}
+
+impl Breaker {
+    pub fn load(filename: &str) -> Breaker {
+        let mut breaker: Breaker = match fs::read_to_string(filename) {
+            Ok(yaml) => {
+                serde_yaml::from_str(&yaml).unwrap_or_else(|e| panic!("{} {}", filename, e))
+            }
+            Err(_) => Breaker::default(),
+        };
+        breaker.halted = halted(filename);
+        breaker
+    }
+
+    // the counters only. see save_halt
+    pub fn save(&self, filename: &str) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(filename, serde_yaml::to_string(self)?)?;
+        Ok(())
+    }
+
+    // written by halt, resume and a trip, never by an order's bookkeeping
+    pub fn save_halt(&self, filename: &str) -> Result<(), Box<dyn std::error::Error>> {
+        let halt_file = halt_filename(filename);
+        match &self.halted {
+            Some(halt) => fs::write(halt_file, serde_yaml::to_string(halt)?)?,
+            None => {
+                if let Err(e) = fs::remove_file(halt_file)
+                    && e.kind() != std::io::ErrorKind::NotFound
+                {
+                    return Err(Box::new(e));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn halt(&mut self, reason: &str) {
+        self.halted = Some(Halt {
+            reason: reason.to_string(),
+            time: time::now_string(),
+        })
+    }
+
+    // clear the halt and the counters that led to it
+    pub fn resume(&mut self) {
+        self.halted = None;
+        self.failures = 0;
+        self.pnl.clear();
+        self.exchange_errors.clear();
+        self.last_total_usd = None;
+    }
+
+    // compare the wallet with the snapshot taken after the last order
+    pub fn check_balance(&mut self, settings: &Settings, total_usd: f64) -> Option<String> {
+        let last = self.last_total_usd?;
+        let max_drop = settings.max_balance_drop?;
+        if last > 0.0 && total_usd < last * (1.0 - max_drop) {
+            self.trip(format!(
+                "balance dropped from {:0.2} to {:0.2} usd between orders",
+                last, total_usd
+            ))
+        } else {
+            None
+        }
+    }
+
+    pub fn record_order(
+        &mut self,
+        settings: &Settings,
+        now: u64,
+        failed: bool,
+        pnl_usd: f64,
+        legs: &[LegOutcome],
+        total_usd: f64,
+    ) -> Option<String> {
+        self.last_total_usd = Some(total_usd);
+        self.failures = if failed { self.failures + 1 } else { 0 };
+        let window = settings
+            .loss_window_hours
+            .unwrap_or(DEFAULT_LOSS_WINDOW_HOURS)
+            * 3600;
+        self.pnl.push(Pnl {
+            time: now,
+            usd: pnl_usd,
+        });
+        self.pnl.retain(|p| p.time + window >= now);
+        for leg in legs {
+            let errors = self
+                .exchange_errors
+                .entry(leg.exchange.clone())
+                .or_insert(0);
+            if leg.filled > 0 {
+                *errors = 0
+            }
+            *errors += leg.failures as u32;
+        }
+
+        let max_failures = settings.max_failures.unwrap_or(DEFAULT_MAX_FAILURES);
+        if self.failures >= max_failures {
+            return self.trip(format!("{} consecutive failed orders", self.failures));
+        }
+        let loss = -self.pnl.iter().map(|p| p.usd).sum::<f64>();
+        if let Some(max_loss) = settings.max_loss_usd
+            && loss > max_loss
+        {
+            return self.trip(format!(
+                "loss {:0.2} usd over {}h above {:0.2} usd",
+                loss,
+                window / 3600,
+                max_loss
+            ));
+        }
+        let max_errors = settings
+            .max_exchange_errors
+            .unwrap_or(DEFAULT_MAX_EXCHANGE_ERRORS);
+        if let Some((exchange, errors)) = self
+            .exchange_errors
+            .iter()
+            .find(|(_e, n)| **n >= max_errors)
+        {
+            return self.trip(format!("{} failed {} sheets in a row", exchange, errors));
+        }
+        None
+    }
+
+    fn trip(&mut self, reason: String) -> Option<String> {
+        self.halt(&reason);
+        Some(reason)
+    }
+}
+
+// re-read from disk so a halt written by another process is seen mid-order
+pub fn halted(filename: &str) -> Option<Halt> {
+    let halt_file = halt_filename(filename);
+    let yaml = fs::read_to_string(&halt_file).ok()?;
+    Some(serde_yaml::from_str(&yaml).unwrap_or_else(|e| panic!("{} {}", halt_file, e)))
+}
+
+// breaker.yaml -> breaker.halt.yaml
+fn halt_filename(filename: &str) -> String {
+    match filename.strip_suffix(".yaml") {
+        Some(stem) => format!("{}.halt.yaml", stem),
+        None => format!("{}.halt", filename),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failures_trip() {
+        let settings = Settings::default();
+        let mut breaker = Breaker::default();
+        assert_eq!(
+            breaker.record_order(&settings, 100, true, 0.0, &[], 1000.0),
+            None
+        );
+        assert_eq!(
+            breaker.record_order(&settings, 200, false, 0.0, &[], 1000.0),
+            None
+        );
+        assert_eq!(breaker.failures, 0);
+        breaker.record_order(&settings, 300, true, 0.0, &[], 1000.0);
+        breaker.record_order(&settings, 400, true, 0.0, &[], 1000.0);
+        let reason = breaker.record_order(&settings, 500, true, 0.0, &[], 1000.0);
+        assert_eq!(reason, Some("3 consecutive failed orders".to_string()));
+        assert!(breaker.halted.is_some());
+        breaker.resume();
+        assert_eq!(breaker.halted, None);
+        assert_eq!(breaker.failures, 0);
+    }
+
+    #[test]
+    fn test_loss_window_trip() {
+        let settings = Settings {
+            max_loss_usd: Some(50.0),
+            loss_window_hours: Some(1),
+            ..Settings::default()
+        };
+        let mut breaker = Breaker::default();
+        assert_eq!(
+            breaker.record_order(&settings, 0, false, -40.0, &[], 1000.0),
+            None
+        );
+        // the first loss has left the window
+        assert_eq!(
+            breaker.record_order(&settings, 4000, false, -40.0, &[], 960.0),
+            None
+        );
+        assert!(
+            breaker
+                .record_order(&settings, 5000, false, -20.0, &[], 940.0)
+                .is_some()
+        );
+    }
+
+    #[test]
+    fn test_balance_and_exchange_trips() {
+        let settings = Settings {
+            max_balance_drop: Some(0.1),
+            max_exchange_errors: Some(2),
+            ..Settings::default()
+        };
+        let mut breaker = Breaker::default();
+        assert_eq!(breaker.check_balance(&settings, 1000.0), None); // no snapshot yet
+        let failing = LegOutcome {
+            exchange: "idex".to_string(),
+            filled: 0,
+            failures: 1,
+        };
+        assert_eq!(
+            breaker.record_order(&settings, 0, false, 0.0, &[failing], 1000.0),
+            None
+        );
+        assert_eq!(breaker.check_balance(&settings, 950.0), None);
+        assert!(breaker.check_balance(&settings, 850.0).is_some());
+        breaker.resume();
+        let failing = LegOutcome {
+            exchange: "idex".to_string(),
+            filled: 0,
+            failures: 2,
+        };
+        let reason = breaker.record_order(&settings, 0, false, 0.0, &[failing], 1000.0);
+        assert_eq!(reason, Some("idex failed 2 sheets in a row".to_string()));
+    }
+
+    #[test]
+    fn test_halt_survives_counter_save() {
+        let path = std::env::temp_dir().join(format!("yith-breaker-{}.yaml", std::process::id()));
+        let filename = path.to_str().unwrap();
+        let mut running = Breaker::load(filename); // an order starts
+        let mut halter = Breaker::load(filename);
+        halter.halt("deploy");
+        halter.save_halt(filename).unwrap(); // `yith halt` mid-order
+        running.failures = 1;
+        running.save(filename).unwrap(); // the order's bookkeeping
+        let loaded = Breaker::load(filename);
+        assert_eq!(loaded.failures, 1);
+        assert_eq!(loaded.halted.unwrap().reason, "deploy");
+        halter.resume();
+        halter.save_halt(filename).unwrap();
+        assert_eq!(halted(filename), None);
+        halter.save_halt(filename).unwrap(); // nothing left to remove
+        let _ = fs::remove_file(filename);
+    }
+}
@@ -1,7 +1,16 @@
+use crate::breaker;
+use crate::control;
 use crate::etherscan;
 use crate::exchange;
 use crate::exchanges;
+use crate::fees;
 use crate::geth;
+use crate::http;
+use crate::notify;
+use crate::recheck;
+use crate::tokens;
+use crate::types;
+use crate::unwind;
 use once_cell::sync::OnceCell;
 use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
@@ -11,19 +20,32 @@ use std::fs;
 pub static FILENAME: &'static str = "config.yaml";
 pub static CONFIG: OnceCell<Config> = OnceCell::new();
 pub static ETHERSCAN: OnceCell<etherscan::Etherscan> = OnceCell::new();
+pub static TOKENS: OnceCell<tokens::Registry> = OnceCell::new();
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Config {
     pub trade_live: bool,
     pub redis_url: String,
+    pub lease_ms: Option<u64>, // inplay lock ttl, renewed every third. default 30000
+    pub max_order_age_secs: Option<u64>, // from the order date to each leg submit. default 60
     pub geth_url: String,
     pub wallet_private_key: String,
     pub proxy: Option<String>,
     pub etherscan_key: String,
     pub idex_key: String,
-    pub email: Option<String>,
+    pub email: Option<String>, // every event, through the localhost smtp relay
+    pub notify: Option<notify::Settings>, // smtp, webhook, chat and file sinks
     pub spread_premium: Option<f64>,
     pub eth_dust: f64,
+    pub min_profit_usd: Option<f64>, // after fees and gas. default 0
+    pub max_slippage: Option<f64>,   // fraction of the ask cost. default 0.01
+    pub slippage_policy: Option<recheck::Policy>, // hold or sell. default hold
+    pub unwind_policies: Option<Vec<unwind::Policy>>, // sell_back, next_best, hold
+    pub max_unwind_loss: Option<f64>, // fraction below the ask price. default 0.02
+    #[serde(default)]
+    pub breaker: breaker::Settings,
+    pub control: Option<control::Settings>, // local http api for the daemon
+    pub cassette: Option<http::CassetteSettings>, // record or replay exchange http traffic
 }
 
 pub fn read_type<T>(filename: &str) -> T
@@ -38,6 +60,7 @@ where
 pub struct Exchange {
     pub settings: ExchangeSettings,
     pub api: Box<dyn exchange::Api>,
+    pub rate_limit: exchange::RateLimit,
 }
 
 impl fmt::Display for Exchange {
@@ -55,8 +78,12 @@ pub struct ExchangeSettings {
     pub contract_address: Option<String>,
     pub fee_recipient_address: Option<String>,
     pub api_url: String,
-    pub maker_fee: f64,
+    pub maker_fee: f64, // fraction, 0.002 = 0.2%
     pub taker_fee: f64,
+    #[serde(default)]
+    pub sheets: types::SheetStrategy, // rollup, levels or vwap
+    pub rate_limit_ms: Option<u64>, // minimum ms between submit and status calls
+    pub max_order_age_secs: Option<u64>, // overrides the config.yaml max_order_age_secs
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -102,6 +129,10 @@ pub fn hydrate_exchanges(
     config: &Config,
 ) -> Result<ExchangeList, Box<dyn std::error::Error>> {
     let exchange_settings: Vec<ExchangeSettings> = read_type(filename);
+    for settings in &exchange_settings {
+        fees::validate(&format!("{} maker_fee", settings.name), settings.maker_fee)?;
+        fees::validate(&format!("{} taker_fee", settings.name), settings.taker_fee)?;
+    }
     let exchanges = exchange_settings
         .into_iter()
         .map(|settings| {
@@ -111,7 +142,7 @@ pub fn hydrate_exchanges(
                     geth::Client::build(&config.geth_url),
                 )),
                 ExchangeProtocol::Ddex3 => Box::new(exchanges::ddex3::Ddex3::new(settings.clone())),
-                ExchangeProtocol::Ddex4 => Box::new(exchanges::ddex4::Ddex4 {}),
+                ExchangeProtocol::Ddex4 => Box::new(exchanges::ddex4::Ddex4::new()),
                 ExchangeProtocol::Switcheo => Box::new(exchanges::switcheo::Switcheo::new(
                     settings.clone(),
                     geth::Client::build(&config.geth_url),
@@ -127,6 +158,7 @@ pub fn hydrate_exchanges(
             };
             Exchange {
                 api: api,
+                rate_limit: exchange::RateLimit::new(settings.rate_limit_ms.unwrap_or(0)),
                 settings: settings,
             }
         })
@@ -0,0 +1,359 @@
+use crate::breaker;
+use crate::lifecycle;
+use crate::metrics;
+use crate::types;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::VecDeque;
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Mutex;
+use std::time::Duration;
+
+pub static DEFAULT_LISTEN: &str = "127.0.0.1:8735";
+
+// the config.yaml control section. the api runs with `yith run --daemon`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Settings {
+    pub listen: Option<String>, // loopback only. default 127.0.0.1:8735
+    pub token: String,          // sent as Authorization: Bearer <token>, /metrics included
+}
+
+#[derive(Debug)]
+pub struct ControlError {
+    pub msg: String,
+}
+
+impl std::error::Error for ControlError {}
+
+impl fmt::Display for ControlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+// orders posted to the api, run by the daemon ahead of redis
+static QUEUE: Mutex<VecDeque<types::Order>> = Mutex::new(VecDeque::new());
+static CURRENT: Mutex<Option<lifecycle::Event>> = Mutex::new(None);
+
+pub fn enqueue(order: types::Order) {
+    QUEUE.lock().unwrap().push_back(order)
+}
+
+pub fn next_order() -> Option<types::Order> {
+    QUEUE.lock().unwrap().pop_front()
+}
+
+pub fn queued() -> usize {
+    QUEUE.lock().unwrap().len()
+}
+
+// keeps the latest transition for GET /status
+pub struct Tracker {}
+
+impl lifecycle::Subscriber for Tracker {
+    fn notify(&mut self, event: &lifecycle::Event) {
+        *CURRENT.lock().unwrap() = Some(event.clone())
+    }
+}
+
+// the parts of the daemon the api reads from
+pub trait Ops: Sync {
+    fn balances(&self) -> Result<serde_json::Value, String>;
+    fn orders(&self) -> Result<serde_json::Value, String>;
+    fn validate(&self, order: &types::Order) -> Result<(), String>;
+    fn standby(&self) -> Option<String>; // why posted orders would not run here
+}
+
+pub struct Api<'a> {
+    pub token: String,
+    pub breaker_file: String,
+    pub ops: &'a dyn Ops,
+}
+
+impl Api<'_> {
+    // status, body and content type. /metrics is prometheus text, the rest json
+    pub fn respond(
+        &self,
+        method: &str,
+        url: &str,
+        auth: Option<&str>,
+        body: &str,
+    ) -> (u16, String, &'static str) {
+        if method == "GET" && url.split('?').next() == Some("/metrics") && self.authorized(auth) {
+            return (200, metrics::render(), "text/plain; version=0.0.4");
+        }
+        let (code, json) = self.handle(method, url, auth, body);
+        (code, json.to_string(), "application/json")
+    }
+
+    pub fn handle(
+        &self,
+        method: &str,
+        url: &str,
+        auth: Option<&str>,
+        body: &str,
+    ) -> (u16, serde_json::Value) {
+        if !self.authorized(auth) {
+            return (401, json!({"error": "bad or missing bearer token"}));
+        }
+        let path = url.split('?').next().unwrap_or(url);
+        let result = match (method, path) {
+            ("GET", "/balances") => self.ops.balances().map(|json| (200, json)),
+            ("GET", "/orders") => self.ops.orders().map(|json| (200, json)),
+            ("GET", "/status") => Ok((200, self.status())),
+            ("POST", "/orders") => Ok(self.submit(body)),
+            ("POST", "/halt") => self.halt(body).map(|json| (200, json)),
+            ("POST", "/resume") => self.resume().map(|json| (200, json)),
+            _ => Ok((
+                404,
+                json!({"error": format!("no route {} {}", method, path)}),
+            )),
+        };
+        result.unwrap_or_else(|e| (500, json!({ "error": e })))
+    }
+
+    fn authorized(&self, auth: Option<&str>) -> bool {
+        match auth.and_then(|value| value.strip_prefix("Bearer ")) {
+            Some(token) => same(token.as_bytes(), self.token.as_bytes()),
+            None => false,
+        }
+    }
+
+    fn status(&self) -> serde_json::Value {
+        json!({
+            "order": *CURRENT.lock().unwrap(),
+            "queued": queued(),
+            "halted": breaker::Breaker::load(&self.breaker_file).halted,
+            "standby": self.ops.standby(),
+        })
+    }
+
+    // a standby instance would only hold the order in memory, so it refuses
+    fn submit(&self, body: &str) -> (u16, serde_json::Value) {
+        if let Some(reason) = self.ops.standby() {
+            return (503, json!({ "error": reason, "standby": true }));
+        }
+        let order = match types::Order::parse(body, true) {
+            Ok(order) => order,
+            Err(e) => return (400, json!({ "error": e.msg })),
+        };
+        if let Err(e) = self.ops.validate(&order) {
+            return (422, json!({ "error": e }));
+        }
+        let id = order.id.clone();
+        enqueue(order);
+        (202, json!({"queued": id, "position": queued()}))
+    }
+
+    fn halt(&self, body: &str) -> Result<serde_json::Value, String> {
+        let reason = serde_json::from_str::<serde_json::Value>(body)
+            .ok()
+            .and_then(|json| json["reason"].as_str().map(|reason| reason.to_string()))
+            .unwrap_or_else(|| "halted from the control api".to_string());
+        let mut breaker = breaker::Breaker::load(&self.breaker_file);
+        breaker.halt(&reason);
+        breaker
+            .save_halt(&self.breaker_file)
+            .map_err(|e| e.to_string())?;
+        log::info!("halted: {}", reason);
+        Ok(json!({ "halted": breaker.halted }))
+    }
+
+    fn resume(&self) -> Result<serde_json::Value, String> {
+        let mut breaker = breaker::Breaker::load(&self.breaker_file);
+        let was = breaker.halted.take();
+        breaker.resume();
+        breaker
+            .save(&self.breaker_file)
+            .and_then(|_| breaker.save_halt(&self.breaker_file))
+            .map_err(|e| e.to_string())?;
+        if let Some(halt) = &was {
+            log::info!("resuming from halt: {}", halt)
+        }
+        Ok(json!({ "resumed": was }))
+    }
+}
+
+// compare without stopping at the first difference
+fn same(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+pub fn bind(settings: &Settings) -> Result<tiny_http::Server, ControlError> {
+    let listen = settings.listen.as_deref().unwrap_or(DEFAULT_LISTEN);
+    let addr: SocketAddr = listen.parse().map_err(|e| ControlError {
+        msg: format!("control listen {}: {}", listen, e),
+    })?;
+    if !addr.ip().is_loopback() {
+        return Err(ControlError {
+            msg: format!("control listen {} is not a loopback address", listen),
+        });
+    }
+    if settings.token.is_empty() {
+        return Err(ControlError {
+            msg: "control token is empty".to_string(),
+        });
+    }
+    tiny_http::Server::http(addr).map_err(|e| ControlError {
+        msg: format!("control listen {}: {}", listen, e),
+    })
+}
+
+// answer requests one at a time until stop() is true
+pub fn serve(server: tiny_http::Server, api: &Api, stop: impl Fn() -> bool) {
+    log::info!("control api on {}", server.server_addr());
+    while !stop() {
+        match server.recv_timeout(Duration::from_secs(1)) {
+            Ok(Some(mut request)) => {
+                let mut body = String::new();
+                let _ = request.as_reader().read_to_string(&mut body);
+                let auth = request
+                    .headers()
+                    .iter()
+                    .find(|header| header.field.equiv("Authorization"))
+                    .map(|header| header.value.to_string());
+                let method = request.method().to_string();
+                let (code, text, content_type) =
+                    api.respond(&method, request.url(), auth.as_deref(), &body);
+                log::info!("control {} {} {}", method, request.url(), code);
+                let header = format!("Content-Type: {}", content_type);
+                let response = tiny_http::Response::from_string(text)
+                    .with_status_code(code)
+                    .with_header(header.parse::<tiny_http::Header>().unwrap());
+                if let Err(e) = request.respond(response) {
+                    log::warn!("control api response: {}", e)
+                }
+            }
+            Ok(None) => (),
+            Err(e) => log::warn!("control api: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Fake {
+        standby: bool,
+    }
+
+    impl Ops for Fake {
+        fn balances(&self) -> Result<serde_json::Value, String> {
+            Ok(json!({"total": 1.5}))
+        }
+
+        fn orders(&self) -> Result<serde_json::Value, String> {
+            Err("exchange down".to_string())
+        }
+
+        fn validate(&self, order: &types::Order) -> Result<(), String> {
+            if order.cost > 0.0 {
+                Ok(())
+            } else {
+                Err("cost is zero".to_string())
+            }
+        }
+
+        fn standby(&self) -> Option<String> {
+            self.standby
+                .then(|| "inplay:lock held by another instance".to_string())
+        }
+    }
+
+    fn order_json(cost: f64) -> String {
+        let books = |askbid| types::Books {
+            askbid,
+            books: vec![],
+        };
+        let order = types::Order {
+            id: "abc".to_string(),
+            date: "2020-01-01T00:00:00Z".to_string(),
+            pair: types::Pair {
+                base: "DAI".to_string(),
+                quote: "ETH".to_string(),
+            },
+            ask_books: books(types::AskBid::Ask),
+            bid_books: books(types::AskBid::Bid),
+            cost,
+            trade_profit: 0.0,
+            profit: 0.0,
+            fee_network: 0.0,
+            quote_usd: 0.0,
+            network_usd: 0.0,
+            ttl_secs: None,
+        };
+        serde_json::to_string(&order).unwrap()
+    }
+
+    #[test]
+    fn test_handle() {
+        let breaker_file =
+            std::env::temp_dir().join(format!("yith-control-{}.yaml", std::process::id()));
+        let api = Api {
+            token: "s3cret".to_string(),
+            breaker_file: breaker_file.to_str().unwrap().to_string(),
+            ops: &Fake { standby: false },
+        };
+        let auth = Some("Bearer s3cret");
+        assert_eq!(api.handle("GET", "/balances", None, "").0, 401);
+        assert_eq!(api.respond("GET", "/metrics", None, "").0, 401);
+        let (code, _text, content_type) = api.respond("GET", "/metrics", auth, "");
+        assert_eq!((code, content_type), (200, "text/plain; version=0.0.4"));
+        assert_eq!(
+            api.handle("GET", "/balances", Some("Bearer nope"), "").0,
+            401
+        );
+        assert_eq!(
+            api.handle("GET", "/balances?fresh=1", auth, ""),
+            (200, json!({"total": 1.5}))
+        );
+        assert_eq!(
+            api.handle("GET", "/orders", auth, ""),
+            (500, json!({"error": "exchange down"}))
+        );
+        assert_eq!(api.handle("DELETE", "/orders", auth, "").0, 404);
+
+        assert_eq!(api.handle("POST", "/orders", auth, "{").0, 400);
+        assert_eq!(api.handle("POST", "/orders", auth, &order_json(0.0)).0, 422);
+        let (code, json) = api.handle("POST", "/orders", auth, &order_json(1.0));
+        assert_eq!((code, &json["queued"]), (202, &json!("abc")));
+        assert_eq!(api.handle("GET", "/status", auth, "").1["queued"], json!(1));
+        assert_eq!(next_order().unwrap().id, "abc");
+        let standby = Api {
+            ops: &Fake { standby: true },
+            ..api
+        };
+        let (code, json) = standby.handle("POST", "/orders", auth, &order_json(1.0));
+        assert_eq!((code, &json["standby"]), (503, &json!(true)));
+        assert_eq!(queued(), 0);
+        let api = Api {
+            ops: &Fake { standby: false },
+            ..standby
+        };
+
+        let (code, json) = api.handle("POST", "/halt", auth, r#"{"reason": "deploy"}"#);
+        assert_eq!((code, &json["halted"]["reason"]), (200, &json!("deploy")));
+        assert_eq!(
+            api.handle("GET", "/status", auth, "").1["halted"]["reason"],
+            json!("deploy")
+        );
+        let (code, json) = api.handle("POST", "/resume", auth, "");
+        assert_eq!((code, &json["resumed"]["reason"]), (200, &json!("deploy")));
+        assert_eq!(
+            api.handle("GET", "/status", auth, "").1["halted"],
+            json!(null)
+        );
+        let _ = std::fs::remove_file(breaker_file);
+    }
+
+    #[test]
+    fn test_bind_loopback_only() {
+        let settings = Settings {
+            listen: Some("0.0.0.0:8735".to_string()),
+            token: "s3cret".to_string(),
+        };
+        assert!(bind(&settings).is_err());
+    }
+}
@@ -1,16 +1,121 @@
-use lettre::{SmtpClient, Transport};
+use crate::notify;
+use lettre::smtp::authentication::Credentials;
+use lettre::{ClientSecurity, ClientTlsParameters, SmtpClient, Transport};
 use lettre_email::Email;
+use serde::{Deserialize, Serialize};
 
-pub fn send(to_addr: &str, subject: &str, body: &str) {
-    let email = Email::builder() // to be used
-        .to(to_addr)
-        .from("yith@donp.org")
-        .subject(subject)
-        .body(body)
-        .build()
-        .unwrap();
-    let mut mailer = SmtpClient::new_unencrypted_localhost().unwrap().transport();
-    let result = mailer.send(email.into());
-    let word = if result.is_ok() { "sent" } else { "FAILED" };
-    println!("email {} {} {}", to_addr, subject, word);
+pub static DEFAULT_FROM: &str = "yith@donp.org";
+pub static DEFAULT_HOST: &str = "localhost";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Tls {
+    None,     // plain smtp, port 25
+    Starttls, // upgrade after connecting, port 587
+    Wrapper,  // tls from the start, port 465
+}
+
+// an smtp notify sink. only `to` is required.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Smtp {
+    pub to: String,
+    pub from: Option<String>, // default yith@donp.org
+    pub host: Option<String>, // default localhost
+    pub port: Option<u16>,    // default by tls mode
+    pub tls: Option<Tls>,     // default none
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+impl Smtp {
+    // the unencrypted localhost relay used by the plain config email setting
+    pub fn localhost(to: &str) -> Smtp {
+        Smtp {
+            to: to.to_string(),
+            from: None,
+            host: None,
+            port: None,
+            tls: None,
+            username: None,
+            password: None,
+        }
+    }
+
+    fn client(&self) -> Result<SmtpClient, notify::NotifyError> {
+        let host = self.host.as_deref().unwrap_or(DEFAULT_HOST);
+        let tls = self.tls.unwrap_or(Tls::None);
+        let port = self.port.unwrap_or(match tls {
+            Tls::None => 25,
+            Tls::Starttls => 587,
+            Tls::Wrapper => 465,
+        });
+        let security = match tls {
+            Tls::None => ClientSecurity::None,
+            Tls::Starttls | Tls::Wrapper => {
+                let connector =
+                    native_tls::TlsConnector::new().map_err(notify::NotifyError::wrap)?;
+                let params = ClientTlsParameters::new(host.to_string(), connector);
+                if tls == Tls::Starttls {
+                    ClientSecurity::Required(params)
+                } else {
+                    ClientSecurity::Wrapper(params)
+                }
+            }
+        };
+        let mut client =
+            SmtpClient::new((host, port), security).map_err(notify::NotifyError::wrap)?;
+        if let (Some(username), Some(password)) = (&self.username, &self.password) {
+            client = client.credentials(Credentials::new(username.clone(), password.clone()))
+        }
+        Ok(client)
+    }
+}
+
+impl notify::Notifier for Smtp {
+    fn send(&self, message: &notify::Message) -> Result<(), notify::NotifyError> {
+        let email = self
+            .builder(&message.subject)
+            .body(message.body.as_str())
+            .build()
+            .map_err(notify::NotifyError::wrap)?;
+        self.deliver(email)
+    }
+}
+
+impl Smtp {
+    // a plain text and html message, for reports
+    pub fn send_alternative(
+        &self,
+        subject: &str,
+        text: &str,
+        html: &str,
+    ) -> Result<(), notify::NotifyError> {
+        let email = self
+            .builder(subject)
+            .alternative(html, text)
+            .build()
+            .map_err(notify::NotifyError::wrap)?;
+        self.deliver(email)
+    }
+
+    fn builder(&self, subject: &str) -> lettre_email::EmailBuilder {
+        Email::builder()
+            .to(self.to.as_str())
+            .from(self.from.as_deref().unwrap_or(DEFAULT_FROM))
+            .subject(subject)
+    }
+
+    fn deliver(&self, email: Email) -> Result<(), notify::NotifyError> {
+        let mut mailer = self.client()?.transport();
+        mailer
+            .send(email.into())
+            .map(|_response| ())
+            .map_err(notify::NotifyError::wrap)
+    }
+}
+
+impl std::fmt::Display for Smtp {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "smtp {}", self.to)
+    }
 }
@@ -1,5 +1,6 @@
 use crate::errors;
 use crate::eth;
+use crate::gas;
 use crate::geth;
 
 pub struct Erc20 {}
@@ -19,7 +20,7 @@ impl Erc20 {
         let params = (tx.clone(), Some("latest".to_string()));
         match client.rpc_str("eth_call", geth::ParamTypes::Infura(params)) {
             Ok(tx) => {
-                println!("{:?}", tx);
+                log::info!("{:?}", tx);
                 Ok(u128::from_str_radix(&tx[2..], 16).unwrap())
             }
             Err(e) => Err(errors::MainError::build_box(e.to_string())),
@@ -34,26 +35,26 @@ impl Erc20 {
     ) -> Result<bool, Box<dyn std::error::Error>> {
         let pub_addr = format!("0x{}", eth::privkey_to_addr(private_key));
         let data = approve_data(trusted_contract_addr, std::u128::MAX);
-        let gas_price_fast = geth::ethgasstation_fast();
-        let nonce = client.nonce(&pub_addr).unwrap();
+        let gas_price_fast = geth::ethgasstation_fast()?;
+        let nonce = client.next_nonce(&pub_addr).unwrap();
         let mut token_addr_bytes = [0u8; 20];
         token_addr_bytes.copy_from_slice(&eth::dehex(token_addr)[..]);
         let tx = ethereum_tx_sign::RawTransaction {
-            nonce: ethereum_types::U256::from(nonce),
+            nonce: ethereum_types::U256::from(nonce.value),
             to: Some(ethereum_types::H160::from(token_addr_bytes)),
             value: ethereum_types::U256::zero(),
             gas_price: ethereum_types::U256::from(gas_price_fast),
-            gas: ethereum_types::U256::from(310240),
+            gas: ethereum_types::U256::from(gas::Kind::Approve.gas_limit()),
             data: data,
         };
         let private_key = ethereum_types::H256::from_slice(&eth::dehex(private_key));
         let rlp_bytes = tx.sign(&private_key, &eth::ETH_CHAIN_MAINNET);
         let params = (eth::hex(&rlp_bytes),);
-        let result = client.rpc_str("eth_sendRawTransaction", geth::ParamTypes::Single(params));
+        let result = client.send_raw(nonce, params);
         match result {
             Err(e) => Err(e),
             Ok(tx) => {
-                println!("GOOD TX {}", tx);
+                crate::log::sent_tx("GOOD TX", &tx);
                 Ok(true)
             }
         }
@@ -8,7 +8,7 @@ pub struct MainError {
 
 impl MainError {
     pub fn build_box(msg: String) -> Box<dyn error::Error> {
-        println!("{}", msg);
+        log::warn!("{}", msg);
         Box::new(MainError { msg: msg })
     }
 }
@@ -1,8 +1,7 @@
-use crate::exchanges;
+use crate::http;
 use reqwest::header;
 use serde::{Deserialize, Serialize};
 use std::fmt;
-use std::fs;
 use std::time::Duration;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -30,28 +29,17 @@ pub struct Erc20Transaction {
 
 #[derive(Debug)]
 pub struct Etherscan {
-    pub tokens: TokenList,
     pub api_key: String,
 }
 
 impl Etherscan {
     pub fn new(api_key: &str) -> Etherscan {
-        let tokens = read_tokens("./notes/etherscan-tokens.json");
         Etherscan {
-            tokens: tokens,
             api_key: api_key.to_string(),
         }
     }
 }
 
-type TokenList = exchanges::switcheo::TokenList;
-
-pub fn read_tokens(filename: &str) -> TokenList {
-    let yaml = fs::read_to_string(filename).unwrap();
-    let tokens = serde_yaml::from_str(&yaml).unwrap();
-    TokenList { tokens: tokens }
-}
-
 pub struct Balances<'a> {
     coins: Vec<Balance<'a>>,
 }
@@ -93,7 +81,7 @@ pub struct BalanceResponse {
 static ETHERSCAN_API_URL: &'static str = "https://api.etherscan.io/api";
 
 pub fn balance<'a>(public_addr: &str, contract: &str, api_key: &'a str) -> f64 {
-    let client = build_client(api_key).unwrap();
+    let client = http::LoggingClient::new(build_client(api_key).unwrap());
     let url_params = match contract {
         "0x0000000000000000000000000000000000000000" => {
             format!("module=account&action=balance&address=0x{}", public_addr)
@@ -113,7 +101,7 @@ pub fn balance<'a>(public_addr: &str, contract: &str, api_key: &'a str) -> f64 {
         if balance_response.status == "1" {
             balance_response.result.parse::<f64>().unwrap()
         } else {
-            println!("{:?}", balance_response);
+            log::warn!("etherscan balance {:?}", balance_response);
             0.0
         }
     } else {
@@ -127,13 +115,13 @@ pub fn last_token_transaction(
     token: &str,
     api_key: &str,
 ) -> Result<Erc20Transaction, String> {
-    let client = build_client(api_key).unwrap();
+    let client = http::LoggingClient::new(build_client(api_key).unwrap());
     let url = format!(
         "{}?module=account&action=tokentx&address=0x{}&startblock={}&sort=desc&apikey={}",
         ETHERSCAN_API_URL, public_addr, start_block, api_key
     );
     let resp = client.get(&url).send().unwrap();
-    println!(
+    log::info!(
         "etherscan tokentx looking for {} since block {} => {}",
         token,
         start_block,
@@ -162,13 +150,13 @@ pub fn last_internal_transaction_from(
     from_addr: &str,
     api_key: &str,
 ) -> Result<InternalTransaction, String> {
-    let client = build_client(api_key).unwrap();
+    let client = http::LoggingClient::new(build_client(api_key).unwrap());
     let url = format!(
         "{}?module=account&action=txlistinternal&address=0x{}&startblock={}&sort=desc&apikey={}",
         ETHERSCAN_API_URL, public_addr, start_block, api_key
     );
     let resp = client.get(&url).send().unwrap();
-    println!("{} {}", url, resp.status());
+    log::info!("{} {}", url, resp.status());
     if resp.status().is_success() {
         let response = resp.json::<ApiResponse<InternalTransaction>>().unwrap();
         let good: Vec<&InternalTransaction> = response
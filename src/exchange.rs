@@ -1,11 +1,14 @@
 use crate::config;
 use crate::exchanges;
+use crate::gas;
 use crate::types;
 use num_bigint::BigInt;
 use serde::{Deserialize, Serialize};
 use std::collections;
 use std::error;
 use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 #[derive(Debug)]
 pub enum TransferDirection {
@@ -61,7 +64,7 @@ pub struct ExchangeError {
 
 impl ExchangeError {
     pub fn build_box(msg: String) -> Box<dyn error::Error> {
-        println!("{}", msg);
+        log::warn!("{}", msg);
         Box::new(ExchangeError { msg: msg })
     }
 }
@@ -83,9 +86,11 @@ impl fmt::Display for ExchangeError {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Serialize)]
 pub enum BuySell {
+    #[serde(rename = "buy")]
     Buy,
+    #[serde(rename = "sell")]
     Sell,
 }
 
@@ -105,7 +110,7 @@ pub enum BalanceStatus {
     Error,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Serialize)]
 pub enum OrderState {
     Pending,
     Open,
@@ -171,8 +176,26 @@ impl fmt::Display for Market {
     }
 }
 
+// exchange metadata fetched by `yith refresh`, in the format the adapter loads
+#[derive(Debug)]
+pub struct MetadataFile {
+    pub filename: String,
+    pub json: String,
+}
+
+impl MetadataFile {
+    // normalized: keys sorted, pretty printed
+    pub fn build<T: Serialize>(filename: &str, data: &T) -> MetadataFile {
+        let value = serde_json::to_value(data).unwrap();
+        MetadataFile {
+            filename: filename.to_string(),
+            json: serde_json::to_string_pretty(&value).unwrap(),
+        }
+    }
+}
+
 #[allow(unused_variables)]
-pub trait Api {
+pub trait Api: Send + Sync {
     fn setup(&mut self) {}
 
     fn build(
@@ -196,15 +219,50 @@ pub trait Api {
         market: &Market,
         exchange: &config::ExchangeSettings,
     ) -> Option<(Option<f64>, Option<f64>)> {
-        println!(
-            "WARNING: {} has no market_minimum call ({})",
-            exchange.name, market
-        );
+        log::warn!("{} has no market_minimum call ({})", exchange.name, market);
         None
     }
 
+    fn markets(
+        &self,
+        exchange: &config::ExchangeSettings,
+    ) -> Result<Vec<MetadataFile>, Box<dyn error::Error>> {
+        Err(ExchangeError::build_box(format!(
+            "{} markets refresh not supported",
+            exchange.name
+        )))
+    }
+
+    // the current offers on one side of a market, best price first
+    fn order_book(
+        &self,
+        exchange: &config::ExchangeSettings,
+        market: &Market,
+        askbid: &types::AskBid,
+    ) -> Result<Vec<types::Offer>, Box<dyn error::Error>> {
+        Err(ExchangeError::build_box(format!(
+            "{} order book not supported",
+            exchange.name
+        )))
+    }
+
+    // whether the exchange's pair list has base/quote in this order.
+    // None for exchanges without a pair list.
+    fn has_market(&self, market: &Market) -> Option<bool> {
+        None
+    }
+
+    // on-chain transactions sent by submit, not counting deposits and withdrawals
+    fn gas_steps(
+        &self,
+        exchange: &config::ExchangeSettings,
+        sell_token: &types::Ticker,
+    ) -> Vec<gas::Step> {
+        vec![]
+    }
+
     fn balances<'a>(&self, privkey: &str, exchange: &config::ExchangeSettings) -> BalanceList {
-        println!("WARNING: {} has no balances call", exchange.name);
+        log::warn!("{} has no balances call", exchange.name);
         collections::HashMap::new()
     }
 
@@ -214,17 +272,17 @@ pub trait Api {
         privkey: &str,
         exchange: &config::ExchangeSettings,
     ) -> BalanceStatus {
-        println!("WARNING: {} has no transfer_status call", exchange.name);
+        log::warn!("{} has no transfer_status call", exchange.name);
         BalanceStatus::InProgress
     }
 
     fn open_orders(&self, private_key: &str, exchange: &config::ExchangeSettings) -> Vec<Order> {
-        println!("WARNING: {} has no open_orders call", exchange.name);
+        log::warn!("{} has no open_orders call", exchange.name);
         vec![]
     }
 
     fn order_status(&self, order_id: &str, exchange: &config::ExchangeSettings) -> OrderState {
-        println!("WARNING: no order_status call");
+        log::warn!("{} has no order_status call", exchange.name);
         OrderState::Open
     }
 
@@ -236,7 +294,7 @@ pub trait Api {
         token: &types::Ticker,
     ) -> Result<Option<String>, Box<dyn error::Error>> {
         if exchange.has_balances {
-            println!("WARNING: withdraw not implemented for {}", exchange.name);
+            log::warn!("withdraw not implemented for {}", exchange.name);
             Ok(None)
         } else {
             Err(ExchangeError::build_box(
@@ -253,7 +311,7 @@ pub trait Api {
         token: &types::Ticker,
     ) -> Result<Option<String>, Box<dyn error::Error>> {
         if exchange.has_balances {
-            println!("WARNING: deposit not implemented for {}", exchange.name);
+            log::warn!("deposit not implemented for {}", exchange.name);
             Ok(None)
         } else {
             Err(ExchangeError::build_box(
@@ -263,6 +321,33 @@ pub trait Api {
     }
 }
 
+// minimum time between api calls to one exchange, shared across threads
+pub struct RateLimit {
+    interval: Duration,
+    last: Mutex<Option<Instant>>,
+}
+
+impl RateLimit {
+    pub fn new(interval_ms: u64) -> RateLimit {
+        RateLimit {
+            interval: Duration::from_millis(interval_ms),
+            last: Mutex::new(None),
+        }
+    }
+
+    // block until the interval since the previous call has passed
+    pub fn wait(&self) {
+        let mut last = self.last.lock().unwrap();
+        if let Some(at) = *last {
+            let elapsed = at.elapsed();
+            if elapsed < self.interval {
+                std::thread::sleep(self.interval - elapsed);
+            }
+        }
+        *last = Some(Instant::now());
+    }
+}
+
 pub fn quantity_in_base_units(qty: f64, prec: i32, scale: i32) -> BigInt {
     let f64_str = qty.to_string();
     let parts = f64_str.split(".").collect::<Vec<_>>();
@@ -301,6 +386,16 @@ pub fn chopped_f64(number: f64) -> f64 {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_rate_limit() {
+        let limit = RateLimit::new(20);
+        let start = Instant::now();
+        limit.wait();
+        limit.wait();
+        limit.wait();
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+
     #[test]
     fn test_quantity_in_base_units() {
         let unit_q = quantity_in_base_units(1.1234, 2, 18);
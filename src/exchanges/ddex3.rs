@@ -2,6 +2,7 @@ use crate::config;
 use crate::eth;
 use crate::exchange;
 use crate::exchanges::ddex::Ddex;
+use crate::http;
 use crate::log;
 use crate::types;
 use reqwest::header;
@@ -206,19 +207,59 @@ pub struct Pair {
     min_order_size: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MarketsResponse {
+    status: i32,
+    desc: String,
+    data: MarketsData,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MarketsData {
+    markets: Vec<Pair>,
+}
+
+pub fn fetch_markets(
+    client: &http::LoggingClient,
+    api_url: &str,
+) -> Result<Vec<exchange::MetadataFile>, Box<dyn std::error::Error>> {
+    let url = format!("{}/markets", api_url);
+    let resp = client.get(url.as_str()).send()?;
+    if !resp.status().is_success() {
+        return Err(exchange::ExchangeError::build_box(format!(
+            "ddex3 markets {}",
+            resp.status()
+        )));
+    }
+    let markets = resp.json::<MarketsResponse>()?;
+    if markets.status != 0 {
+        return Err(exchange::ExchangeError::build_box(format!(
+            "ddex3 markets {}",
+            markets.desc
+        )));
+    }
+    Ok(vec![exchange::MetadataFile::build(
+        "notes/ddex3-pairs.json",
+        &markets.data.markets,
+    )])
+}
+
 #[allow(dead_code)]
 pub struct Ddex3 {
     pairs: PairList,
     settings: config::ExchangeSettings,
+    client: http::LoggingClient,
 }
 
 impl Ddex3 {
     pub fn new(settings: config::ExchangeSettings) -> Ddex3 {
         let pairs = PairList::from_file("notes/ddex3-pairs.json");
         log::debug!("ddex3 loaded {} pairs", pairs.pairs.len());
+        let client = http::LoggingClient::new(build_http_client().unwrap());
         Ddex3 {
-            pairs: pairs,
-            settings: settings,
+            pairs,
+            settings,
+            client,
         }
     }
 }
@@ -236,7 +277,7 @@ impl exchange::Api for Ddex3 {
         market: &exchange::Market,
         offer: &types::Offer,
     ) -> Result<exchange::OrderSheet, Box<dyn std::error::Error>> {
-        println!(
+        log::info!(
             "ddex3(hydro) build {:#?} {} {}@{}",
             askbid, market, offer.base_qty, offer.quote
         );
@@ -259,26 +300,29 @@ impl exchange::Api for Ddex3 {
             amount: format!("{:.width$}", qty, width = pair.amount_decimals as usize),
         };
 
-        let client = build_http_client()?;
-
         let url = format!("{}{}", exchange.api_url.as_str(), "/orders/build");
-        println!("Ddex3 {}", url);
+        log::info!("Ddex3 {}", url);
 
         let headers = auth_header(privkey);
-        println!("{}", serde_json::to_string(&sheet).unwrap());
-        let resp = client.post(&url).headers(headers).json(&sheet).send()?;
+        log::info!("{}", serde_json::to_string(&sheet).unwrap());
+        let resp = self
+            .client
+            .post(&url)
+            .headers(headers)
+            .json(&sheet)
+            .send()?;
         let status = resp.status();
-        println!("{:#?} {}", resp.status(), resp.url());
+        log::info!("{:#?} {}", resp.status(), resp.url());
         let json = resp.text().unwrap();
-        println!("{}", json);
-        let body = serde_json::from_str::<BuildResponse>(&json).unwrap();
+        log::info!("{}", json);
+        let body = serde_json::from_str::<BuildResponse>(json).unwrap();
         if status.is_success() {
             if body.status > 0 {
                 let order_error = exchange::OrderError {
                     msg: body.desc,
                     code: body.status as i32,
                 };
-                println!("ERR: {}", order_error);
+                log::error!("{}", order_error);
                 Err(Box::new(order_error))
             } else {
                 if let Some(order_build) = body.data {
@@ -288,7 +332,7 @@ impl exchange::Api for Ddex3 {
                         msg: body.desc,
                         code: body.status as i32,
                     };
-                    println!("ERR: {}", order_error);
+                    log::error!("{}", order_error);
                     Err(Box::new(order_error))
                 }
             }
@@ -297,7 +341,7 @@ impl exchange::Api for Ddex3 {
                 msg: body.desc,
                 code: body.status as i32,
             };
-            println!("ERR: {}", order_error);
+            log::error!("{}", order_error);
             Err(Box::new(order_error))
         }
     }
@@ -324,19 +368,19 @@ impl exchange::Api for Ddex3 {
                 signature: signature,
                 method: 0, // web ddex uses method 1
             };
-            let client = build_http_client()?;
             let url = format!("{}/orders/sync", exchange.api_url.as_str());
-            println!("{} {}", url, serde_json::to_string(&order_place).unwrap());
+            log::info!("{} {}", url, serde_json::to_string(&order_place).unwrap());
             let headers = auth_header(private_key);
-            let resp = client
+            let resp = self
+                .client
                 .post(&url)
                 .headers(headers)
                 .json(&order_place)
                 .send()?;
             let status = resp.status();
             let json = resp.text().unwrap();
-            let response = serde_json::from_str::<BuildResponse>(&json).unwrap();
-            println!("{:#?} {} {}", status, url, json);
+            let response = serde_json::from_str::<BuildResponse>(json).unwrap();
+            log::info!("{:#?} {} {}", status, url, json);
             if response.status == 0 {
                 Ok(sheet.id.clone())
             } else {
@@ -354,16 +398,20 @@ impl exchange::Api for Ddex3 {
         private_key: &str,
         exchange: &config::ExchangeSettings,
     ) -> Vec<exchange::Order> {
-        let client = build_http_client().unwrap();
         let url = format!("{}/orders", exchange.api_url.as_str());
-        println!("{}", url);
+        log::info!("{}", url);
         let headers = auth_header(private_key);
-        let resp = client.get(url.as_str()).headers(headers).send().unwrap();
+        let resp = self
+            .client
+            .get(url.as_str())
+            .headers(headers)
+            .send()
+            .unwrap();
         //println!("{:#?} {}", resp.status(), resp.url());
         //println!("{:#?}", resp.text());
         let order_resp = resp.json::<OrderResponse>().unwrap();
         if order_resp.status < 0 {
-            println!("ddex3 order list error {}", order_resp.desc);
+            log::info!("ddex3 order list error {}", order_resp.desc);
             vec![]
         } else {
             order_resp
@@ -375,6 +423,17 @@ impl exchange::Api for Ddex3 {
                 .collect()
         }
     }
+    fn markets(
+        &self,
+        exchange: &config::ExchangeSettings,
+    ) -> Result<Vec<exchange::MetadataFile>, Box<dyn std::error::Error>> {
+        fetch_markets(&self.client, &exchange.api_url)
+    }
+
+    fn has_market(&self, market: &exchange::Market) -> Option<bool> {
+        Some(self.pairs.get(&self.make_market_id(market)).is_ok())
+    }
+
     fn market_minimums(
         &self,
         market: &exchange::Market,
@@ -446,6 +505,23 @@ mod tests {
     static MSG_V3: &str = "HYDRO-AUTHENTICATION@1524088776656";
     static GOOD_SIG_V3: &str = "603efd7241bfb6c61f4330facee0f7027d98e030ef241ad03a372638c317859a50620dacee177b771ce05812770a637c4c7395da0042c94250f86fb52472f9351b"; // was ..00"
 
+    #[test]
+    fn test_fetch_markets() {
+        let api_url = "https://api.ddex.io/v3";
+        let mock = http::Mock::new().respond(
+            &format!("{}/markets", api_url),
+            200,
+            r#"{"status":0,"desc":"success","data":{"markets":[{"id":"HOT-WETH","baseToken":"HOT","quoteToken":"WETH","priceDecimals":8,"amountDecimals":0,"minOrderSize":"1000.000000000000000000"}]}}"#,
+        );
+        let client = http::LoggingClient::mocked(mock);
+        let files = fetch_markets(&client, api_url).unwrap();
+        assert_eq!(files[0].filename, "notes/ddex3-pairs.json");
+        let pairs = serde_yaml::from_str::<Vec<Pair>>(&files[0].json).unwrap();
+        let pair_list = PairList { pairs };
+        assert_eq!(pair_list.get("HOT-WETH").unwrap().price_decimals, 8);
+        assert!(!files[0].json.contains("baseToken"));
+    }
+
     #[test]
     fn test_build_token() {
         let token = build_token(PRIVKEY, MSG_V3);
@@ -2,6 +2,7 @@ use crate::config;
 use crate::eth;
 use crate::exchange;
 use crate::exchanges::ddex::Ddex;
+use crate::http;
 use crate::types;
 use reqwest::header;
 use secp256k1::{PublicKey, Secp256k1, SecretKey};
@@ -99,7 +100,22 @@ pub struct OrderResponse {
     data: Option<OrderData>,
 }
 
-pub struct Ddex4 {}
+pub struct Ddex4 {
+    client: http::LoggingClient,
+}
+
+impl Ddex4 {
+    pub fn new() -> Ddex4 {
+        let client = http::LoggingClient::new(build_auth_client().unwrap());
+        Ddex4 { client }
+    }
+}
+
+impl Default for Ddex4 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Ddex for Ddex4 {}
 
@@ -114,7 +130,7 @@ impl exchange::Api for Ddex4 {
         market: &exchange::Market,
         offer: &types::Offer,
     ) -> Result<exchange::OrderSheet, Box<dyn std::error::Error>> {
-        println!(
+        log::info!(
             "ddex4(hydro) build {:#?} {} {}@{}",
             askbid, market, offer.base_qty, offer.quote
         );
@@ -135,17 +151,20 @@ impl exchange::Api for Ddex4 {
             amount: format!("{:.width$}", qty, width = market.quantity_decimals as usize),
         };
 
-        let client = build_auth_client()?;
-
         let url = format!("{}{}", exchange.api_url.as_str(), "/orders/build");
-        println!("Ddex4 {}", url);
-        println!("{:#?}", &sheet);
+        log::info!("Ddex4 {}", url);
+        log::debug!("{:#?}", &sheet);
 
         let headers = auth_header(privkey);
-        println!("{}", serde_json::to_string(&sheet).unwrap());
-        let resp = client.post(&url).headers(headers).json(&sheet).send()?;
+        log::info!("{}", serde_json::to_string(&sheet).unwrap());
+        let resp = self
+            .client
+            .post(&url)
+            .headers(headers)
+            .json(&sheet)
+            .send()?;
         let status = resp.status();
-        println!("{:#?} {}", resp.status(), resp.url());
+        log::info!("{:#?} {}", resp.status(), resp.url());
         let body = resp.json::<BuildResponse>().unwrap();
         if status.is_success() {
             if body.status > 0 {
@@ -153,7 +172,7 @@ impl exchange::Api for Ddex4 {
                     msg: body.desc,
                     code: body.status as i32,
                 };
-                println!("ERR: {}", order_error);
+                log::error!("{}", order_error);
                 Err(Box::new(order_error))
             } else {
                 Ok(exchange::OrderSheet::Ddex4(sheet))
@@ -163,7 +182,7 @@ impl exchange::Api for Ddex4 {
                 msg: body.desc,
                 code: body.status as i32,
             };
-            println!("ERR: {}", order_error);
+            log::error!("{}", order_error);
             Err(Box::new(order_error))
         }
     }
@@ -174,7 +193,7 @@ impl exchange::Api for Ddex4 {
         _exchange: &config::ExchangeSettings,
         sheet: exchange::OrderSheet,
     ) -> Result<String, Box<dyn std::error::Error>> {
-        println!("HYDRO order! {:#?}", sheet);
+        log::info!("HYDRO order! {:#?}", sheet);
         Ok(format!("ddex4-order-id"))
     }
 
@@ -183,16 +202,20 @@ impl exchange::Api for Ddex4 {
         private_key: &str,
         exchange: &config::ExchangeSettings,
     ) -> Vec<exchange::Order> {
-        let client = reqwest::blocking::Client::new();
         let url = format!("{}/orders?marketId=all", exchange.api_url.as_str());
-        println!("{}", url);
+        log::info!("{}", url);
         let headers = auth_header(private_key);
-        let resp = client.get(url.as_str()).headers(headers).send().unwrap();
+        let resp = self
+            .client
+            .get(url.as_str())
+            .headers(headers)
+            .send()
+            .unwrap();
         //println!("{:#?} {}", resp.status(), resp.url());
         //println!("{:#?}", resp.text());
         let order_resp = resp.json::<OrderResponse>().unwrap();
         if order_resp.status < 0 {
-            println!("ddex3 order list error {}", order_resp.desc);
+            log::info!("ddex3 order list error {}", order_resp.desc);
             vec![]
         } else {
             order_resp
@@ -3,15 +3,16 @@ use crate::eth;
 use crate::etherscan;
 use crate::exchange;
 use crate::exchange::Api;
+use crate::gas;
 use crate::geth;
 use crate::http;
 use crate::time;
+use crate::tokens;
 use crate::types;
 use reqwest::header;
 use secp256k1::SecretKey;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::fs;
 use std::time::Duration;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -197,52 +198,32 @@ pub struct OrderBookEntry {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
-pub struct TokenDetail {
-    pub name: String,
-    pub address: String,
-    pub decimals: i32,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct TokenList {
-    #[serde(flatten)]
-    tokens: HashMap<String, TokenDetail>,
-}
-
-impl TokenList {
-    pub fn read_tokens(filename: &str) -> TokenList {
-        let file_ok = fs::read_to_string(filename);
-        let yaml = file_ok.unwrap();
-        let tokens = serde_yaml::from_str(&yaml).unwrap();
-        TokenList { tokens: tokens }
-    }
-
-    pub fn get(&self, symbol2: &str) -> &TokenDetail {
-        let detail = self
-            .tokens
-            .iter()
-            .find(|(symbol, _detail)| *symbol == symbol2)
-            .unwrap()
-            .1;
-        // println!(
-        //     "idex lookup {} ^{} {}",
-        //     symbol2, detail.decimals, detail.address
-        // );
-        detail
-    }
-
-    pub fn by_addr(&self, addr: &str) -> (&String, &TokenDetail) {
-        let detail = self
-            .tokens
-            .iter()
-            .find(|(_symbol, detail)| detail.address == addr)
-            .unwrap();
-        // println!(
-        //     "idex by_addr {} ^{} {}",
-        //     detail.0, detail.1.decimals, detail.1.address
-        // );
-        detail
+pub struct Currency {
+    name: String,
+    address: String,
+    decimals: i32,
+}
+
+pub fn fetch_markets(
+    client: &http::LoggingClient,
+    api_url: &str,
+) -> Result<Vec<exchange::MetadataFile>, Box<dyn std::error::Error>> {
+    let url = format!("{}/returnCurrencies", api_url);
+    let resp = client
+        .post(url.as_str())
+        .json(&HashMap::<String, String>::new())
+        .send()?;
+    if !resp.status().is_success() {
+        return Err(exchange::ExchangeError::build_box(format!(
+            "idex currencies {}",
+            resp.status()
+        )));
     }
+    let currencies = resp.json::<HashMap<String, Currency>>()?;
+    Ok(vec![exchange::MetadataFile::build(
+        "notes/idex-tokens.json",
+        &currencies,
+    )])
 }
 
 #[allow(dead_code)]
@@ -250,23 +231,29 @@ pub struct Idex {
     geth: geth::Client,
     settings: config::ExchangeSettings,
     client: http::LoggingClient,
-    tokens: TokenList,
 }
 
 impl Idex {
     pub fn new(settings: config::ExchangeSettings, api_key: &str, geth: geth::Client) -> Idex {
         let client = Idex::build_http_client(api_key).unwrap();
         let logging_client = http::LoggingClient::new(client);
-        let tokens = TokenList::read_tokens("notes/idex-tokens.json");
-        log::debug!("idex loaded {} tokens", tokens.tokens.len());
         Idex {
             geth: geth,
             settings: settings,
             client: logging_client,
-            tokens: tokens,
         }
     }
 
+    pub fn token(
+        &self,
+        symbol: &str,
+    ) -> Result<&'static tokens::Token, Box<dyn std::error::Error>> {
+        let registry = config::TOKENS.get().unwrap();
+        registry.get_on(&self.settings.name, symbol).ok_or_else(|| {
+            exchange::ExchangeError::build_box(format!("idex token {} not in registry", symbol))
+        })
+    }
+
     pub fn build_http_client(api_key: &str) -> reqwest::Result<reqwest::blocking::Client> {
         let mut headers = header::HeaderMap::new();
         headers.insert(
@@ -288,18 +275,38 @@ impl Idex {
         );
         let resp = self.client.get(url.as_str()).send().unwrap();
         let status = resp.status();
-        println!("{} {}", url, status);
+        log::info!("{} {}", url, status);
         let nonce = resp.json::<NonceResponse>().unwrap().nonce;
         nonce as usize
     }
 
+    fn order_book_snapshot(
+        &self,
+        exchange: &config::ExchangeSettings,
+        market_name: &str,
+        count: u32,
+    ) -> Result<OrderBookResponse, Box<dyn std::error::Error>> {
+        let url = format!("{}/returnOrderBook", exchange.api_url.as_str(),);
+        let order_book_request = OrderBookRequest {
+            market: market_name.to_string(),
+            count,
+        };
+        let resp = self
+            .client
+            .post(url.as_str())
+            .json(&order_book_request)
+            .send()?;
+        let json = resp.text()?;
+        Ok(serde_json::from_str::<OrderBookResponse>(json)?)
+    }
+
     pub fn balance_wait(
         &self,
         public_addr: &str,
         exchange: &config::ExchangeSettings,
         token: &str,
     ) {
-        println!("idex transfer stage 2 balance watch {}", token);
+        log::info!("idex transfer stage 2 balance watch {}", token);
         let old_balance: f64 = *self
             .balances(public_addr, exchange)
             .get(token)
@@ -311,7 +318,7 @@ impl Idex {
             same = match balances.get(token) {
                 Some(balance) => {
                     let waited = start.elapsed();
-                    println!(
+                    log::info!(
                         "idex balance {} => {} {} {}",
                         old_balance,
                         balance,
@@ -340,29 +347,17 @@ impl exchange::Api for Idex {
         market: &exchange::Market,
         offer: &types::Offer,
     ) -> Result<exchange::OrderSheet, Box<dyn std::error::Error>> {
-        let base_token = self.tokens.get(&market.base.symbol);
-        let quote_token = self.tokens.get(&market.quote.symbol);
+        let base_token = self.token(&market.base.symbol)?;
+        let quote_token = self.token(&market.quote.symbol)?;
         let nonce = self.nonce(privkey); // call before OrderBook #speed
 
-        let url = format!("{}/returnOrderBook", exchange.api_url.as_str(),);
         let market_name = format!("{}_{}", &market.quote.symbol, &market.base.symbol);
-        let order_book_request = OrderBookRequest {
-            market: market_name.clone(),
-            count: 2,
-        };
-        let resp = self
-            .client
-            .post(url.as_str())
-            .json(&order_book_request)
-            .send()
-            .unwrap();
-        let json = resp.text().unwrap();
-        let book = serde_json::from_str::<OrderBookResponse>(&json).unwrap();
+        let book = self.order_book_snapshot(exchange, &market_name, 2)?;
         let side = match askbid {
             types::AskBid::Ask => book.asks,
             types::AskBid::Bid => book.bids,
         };
-        println!("{} {:?} orderbook snapshot {:?}", market_name, askbid, side);
+        log::info!("{} {:?} orderbook snapshot {:?}", market_name, askbid, side);
 
         let buy_token = match askbid {
             types::AskBid::Ask => quote_token,
@@ -401,7 +396,7 @@ impl exchange::Api for Idex {
                     orders.push(order);
                 }
                 remaining_buy -= min_buy;
-                println!(
+                log::info!(
                     "+ {:0.5}@{:0.5}={:0.5} {:0.5}@{:0.5}={:0.5} spending {:0.5}{} remaining {:0.5}",
                     offer.base_qty,
                     offer.quote,
@@ -410,7 +405,7 @@ impl exchange::Api for Idex {
                     price,
                     cost,
                     min_buy,
-                    buy_token.name,
+                    buy_token.symbol,
                     remaining_buy,
                 );
             }
@@ -428,6 +423,28 @@ impl exchange::Api for Idex {
         }
     }
 
+    fn order_book(
+        &self,
+        exchange: &config::ExchangeSettings,
+        market: &exchange::Market,
+        askbid: &types::AskBid,
+    ) -> Result<Vec<types::Offer>, Box<dyn std::error::Error>> {
+        let market_name = format!("{}_{}", &market.quote.symbol, &market.base.symbol);
+        let book = self.order_book_snapshot(exchange, &market_name, 20)?;
+        let side = match askbid {
+            types::AskBid::Ask => book.asks,
+            types::AskBid::Bid => book.bids,
+        };
+        side.iter()
+            .map(|o| {
+                Ok(types::Offer {
+                    base_qty: o.amount.parse::<f64>()?,
+                    quote: o.price.parse::<f64>()?,
+                })
+            })
+            .collect()
+    }
+
     fn submit(
         &self,
         privkey: &str,
@@ -460,17 +477,17 @@ impl exchange::Api for Idex {
                     };
                     orders.push(so);
                 });
-            println!("{}", serde_json::to_string(&orders).unwrap());
+            log::info!("{}", serde_json::to_string(&orders).unwrap());
             let url = format!("{}/trade", exchange.api_url.as_str());
             let resp = self.client.post(url.as_str()).json(&orders).send().unwrap();
-            println!("{} {}", url, resp.status());
+            log::info!("{} {}", url, resp.status());
             if resp.status().is_success() {
                 let json = resp.text().unwrap();
-                println!("{}", json);
+                log::info!("{}", json);
                 let orders = serde_json::from_str::<Vec<OrderResponse>>(&json).unwrap();
                 // TODO handle multiple orders
                 if orders.len() > 1 {
-                    println!(
+                    log::info!(
                         "warning: {} orders in play {}",
                         orders.len(),
                         orders
@@ -493,6 +510,13 @@ impl exchange::Api for Idex {
         }
     }
 
+    fn markets(
+        &self,
+        exchange: &config::ExchangeSettings,
+    ) -> Result<Vec<exchange::MetadataFile>, Box<dyn std::error::Error>> {
+        fetch_markets(&self.client, &exchange.api_url)
+    }
+
     fn balances(
         &self,
         public_addr: &str,
@@ -525,7 +549,7 @@ impl exchange::Api for Idex {
         let url = format!("{}/withdraw", exchange.api_url.as_str());
         let pub_addr = format!("0x{}", eth::privkey_to_addr(private_key));
         let nonce = self.nonce(private_key);
-        let token = &self.tokens.get(&ticker.symbol);
+        let token = self.token(&ticker.symbol)?;
         let bigint = exchange::quantity_in_base_units(amount, token.decimals, 18);
         let withdraw = WithdrawRequest {
             address: pub_addr,
@@ -564,8 +588,8 @@ impl exchange::Api for Idex {
         amount: f64,
         ticker: &types::Ticker,
     ) -> Result<Option<String>, Box<dyn std::error::Error>> {
-        println!("idex deposit {} {}", amount, ticker.symbol);
-        let token = &self.tokens.get(&ticker.symbol);
+        log::info!("idex deposit {} {}", amount, ticker.symbol);
+        let token = self.token(&ticker.symbol)?;
         let (data, value) = if ticker.symbol == "ETH" {
             let bigint = exchange::quantity_in_base_units(amount, token.decimals, 18);
             (
@@ -581,11 +605,11 @@ impl exchange::Api for Idex {
         };
 
         let pub_addr = format!("0x{}", eth::privkey_to_addr(private_key));
-        let nonce = self.geth.nonce(&pub_addr).unwrap();
-        let gas_tx = 70000; // eth dep 35,717. token dep 60,920
-        let gas_price_fast = geth::ethgasstation_fast();
+        let nonce = self.geth.next_nonce(&pub_addr).unwrap();
+        let gas_tx = gas::Kind::Deposit.gas_limit(); // eth dep 35,717. token dep 60,920
+        let gas_price_fast = geth::ethgasstation_fast()?;
         let gas_price_gwei = gas_price_fast / 1_000_000_000u64;
-        println!(
+        log::info!(
             "deposit tx {} gas @{}gwei (ethgasstation_fast) = {}eth",
             gas_tx,
             gas_price_gwei,
@@ -596,7 +620,7 @@ impl exchange::Api for Idex {
         let contract_addr = exchange.contract_address.as_ref().unwrap().clone();
         contract_addra.copy_from_slice(&eth::dehex(&contract_addr)[..]);
         let tx = ethereum_tx_sign::RawTransaction {
-            nonce: ethereum_types::U256::from(nonce),
+            nonce: ethereum_types::U256::from(nonce.value),
             to: Some(ethereum_types::H160::from(contract_addra)),
             value: value,
             gas_price: ethereum_types::U256::from(gas_price_fast),
@@ -606,10 +630,8 @@ impl exchange::Api for Idex {
         let private_key = ethereum_types::H256::from_slice(&eth::dehex(private_key));
         let rlp_bytes = tx.sign(&private_key, &eth::ETH_CHAIN_MAINNET);
         let params = (eth::hex(&rlp_bytes),);
-        let tx = self
-            .geth
-            .rpc_str("eth_sendRawTransaction", geth::ParamTypes::Single(params))?;
-        println!("GOOD TX {}", tx);
+        let tx = self.geth.send_raw(nonce, params)?;
+        crate::log::sent_tx("GOOD TX", &tx);
         Ok(Some(format!("{}.{}", ticker.symbol, tx)))
     }
 
@@ -648,7 +670,7 @@ impl exchange::Api for Idex {
     ) -> exchange::OrderState {
         if order_id.len() == 36 {
             // uuid from TradeHistory
-            println!("uuid order status assumed filled!");
+            log::info!("uuid order status assumed filled!");
             exchange::OrderState::Filled
         } else {
             // order hash, len 67
@@ -664,7 +686,7 @@ impl exchange::Api for Idex {
                 .unwrap();
             let status = resp.status();
             let json = resp.text().unwrap();
-            println!("{} {} {:?}", url, status, json);
+            log::info!("{} {} {:?}", url, status, json);
             let response = serde_json::from_str::<OrderStatusResponse>(&json).unwrap();
             response.into()
         }
@@ -691,7 +713,7 @@ impl exchange::Api for Idex {
                 .unwrap();
             match result.part {
                 geth::RpcResultTypes::Error(e) => {
-                    println!("{}", e.error.message);
+                    log::error!("{}", e.error.message);
                     exchange::BalanceStatus::Complete
                 }
                 geth::RpcResultTypes::Result(r) => match r.result {
@@ -702,7 +724,7 @@ impl exchange::Api for Idex {
                                 exchange::BalanceStatus::Complete
                             }
                             _ => {
-                                println!("deposit tx failed. erc20 allowance problem?");
+                                log::error!("deposit tx failed. erc20 allowance problem?");
                                 exchange::BalanceStatus::Error
                             }
                         }
@@ -848,4 +870,63 @@ mod tests {
         assert_eq!(hex::encode(r), good_r[2..]);
         assert_eq!(hex::encode(s), good_s[2..]);
     }
+
+    #[test]
+    fn test_build_cassette() {
+        tokens::test_registry();
+        let settings = config::ExchangeSettings {
+            name: "idex".to_string(),
+            enabled: true,
+            has_balances: true,
+            protocol: config::ExchangeProtocol::Idex,
+            contract_address: Some("0x2a0c0dbecc7e4d658f48e01e3fa353f44050c208".to_string()),
+            fee_recipient_address: None,
+            api_url: "https://api.idex.market".to_string(),
+            maker_fee: 0.001,
+            taker_fee: 0.002,
+            sheets: types::SheetStrategy::Rollup,
+            rate_limit_ms: None,
+            max_order_age_secs: None,
+        };
+        // replays hand-written v1 api responses, not captured from the live api
+        let cassette = http::Cassette::open(&http::CassetteSettings {
+            mode: http::CassetteMode::Replay,
+            path: "notes/cassettes/idex.synthetic.json".to_string(),
+        })
+        .unwrap();
+        let idex = Idex {
+            geth: geth::Client::build("http://localhost:8545"),
+            settings: settings.clone(),
+            client: http::LoggingClient::cassette(cassette),
+        };
+        let market = exchange::Market {
+            base: types::Ticker::from("DAI"),
+            base_contract: "0x6b175474e89094c44da98b954eedeac495271d0f".to_string(),
+            quote: types::Ticker::from("ETH"),
+            quote_contract: "0x0000000000000000000000000000000000000000".to_string(),
+            quantity_decimals: 18.0,
+            price_decimals: 18.0,
+            source_name: "idex".to_string(),
+        };
+        let offer = types::Offer {
+            base_qty: 100.0,
+            quote: 0.005,
+        };
+        let askbid = types::AskBid::Ask;
+        let sheet = idex
+            .build(PRIVKEY_DDEX3, &askbid, &settings, &market, &offer)
+            .unwrap();
+        match sheet {
+            exchange::OrderSheet::Idex(sheet) => {
+                assert_eq!(sheet.starting_nonce, 4817);
+                assert_eq!(sheet.orders.len(), 2);
+                assert_eq!(sheet.orders[0].amount, "294000000000000000");
+                assert_eq!(
+                    sheet.orders[1].order_hash,
+                    "0x8b1d6e0f3a2c4e5d7f9a1b3c5d7e9f0a2b4c6d8e0f1a3b5c7d9e1f2a4b6c8d0e"
+                );
+            }
+            _ => panic!("not an idex order"),
+        }
+    }
 }
@@ -2,9 +2,11 @@ use crate::config;
 use crate::errors;
 use crate::eth;
 use crate::exchange;
-use crate::exchanges;
+use crate::gas;
 use crate::geth;
+use crate::http;
 use crate::time;
+use crate::tokens;
 use crate::types;
 use ethereum_types;
 use serde::{Deserialize, Serialize};
@@ -13,6 +15,8 @@ use std::collections::HashMap;
 use std::error;
 use std::fs;
 
+pub static OASIS_API: &str = "https://api.oasisdex.com/v2"; // pair listing. offers go on-chain
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct OrderSheet {
     pub address: String,
@@ -32,6 +36,11 @@ pub struct PairDetail {
     pub active: bool,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PairsResponse {
+    data: HashMap<String, PairDetail>,
+}
+
 pub struct PairList {
     pub pairs: HashMap<String, PairDetail>,
 }
@@ -49,22 +58,33 @@ pub struct Oasis {
     geth: geth::Client,
     pairs: PairList,
     contract: Contract,
-    tokens: exchanges::idex::TokenList, // borrow from Idex
+    client: http::LoggingClient,
 }
 
 impl Oasis {
     pub fn new(geth: geth::Client) -> Oasis {
         let pairs = read_pairs("notes/oasis-pairs.json");
         let abi = read_abi("notes/oasis-abi.json");
-        let tokens = exchanges::idex::TokenList::read_tokens("notes/oasis-idex-tokens.json");
+        let client = http::LoggingClient::new(reqwest::blocking::Client::new());
         Oasis {
             geth: geth,
             pairs: pairs,
             contract: abi,
-            tokens: tokens,
+            client,
         }
     }
 
+    fn token(
+        &self,
+        exchange: &config::ExchangeSettings,
+        symbol: &str,
+    ) -> Result<&'static tokens::Token, Box<dyn std::error::Error>> {
+        let registry = config::TOKENS.get().unwrap();
+        registry.get_on(&exchange.name, symbol).ok_or_else(|| {
+            exchange::ExchangeError::build_box(format!("oasis token {} not in registry", symbol))
+        })
+    }
+
     fn min_sell(
         &self,
         token: &str,
@@ -100,7 +120,7 @@ impl Oasis {
         match self.balance(token_addr, addr, exchange) {
             Some(balance) => {
                 let first_balance = balance;
-                println!("oasis first balance {}", first_balance);
+                log::info!("oasis first balance {}", first_balance);
                 let mut next_balance = first_balance;
                 while next_balance == first_balance {
                     match self.balance(token_addr, addr, exchange) {
@@ -123,7 +143,8 @@ impl Oasis {
         addr: &str,
         _exchange: &config::ExchangeSettings,
     ) -> Option<f64> {
-        let (_symbol, token) = &self.tokens.by_addr(token_addr);
+        let registry = config::TOKENS.get().unwrap();
+        let token = registry.by_addr(token_addr).unwrap();
         let mut tx = geth::JsonRpcParam::new();
         tx.insert("to".to_string(), token_addr.to_string());
         tx.insert("data".to_string(), get_balance_data(addr));
@@ -135,11 +156,11 @@ impl Oasis {
             Ok(units_str) => {
                 let units = u128::from_str_radix(&units_str[2..], 16).unwrap();
                 let qty = exchange::units_to_quantity(units, token.decimals);
-                println!("{} oasis balance {}{}", time::now_string(), qty, token.name,);
+                log::info!("{} oasis balance {}{}", time::now_string(), qty, token.symbol,);
                 Some(qty)
             }
             Err(e) => {
-                println!("{}", e);
+                log::error!("{}", e);
                 None
             }
         }
@@ -153,6 +174,25 @@ pub fn read_pairs(filename: &str) -> PairList {
     PairList { pairs: pairs }
 }
 
+pub fn fetch_markets(
+    client: &http::LoggingClient,
+    api_url: &str,
+) -> Result<Vec<exchange::MetadataFile>, Box<dyn std::error::Error>> {
+    let url = format!("{}/pairs", api_url);
+    let resp = client.get(url.as_str()).send()?;
+    if !resp.status().is_success() {
+        return Err(exchange::ExchangeError::build_box(format!(
+            "oasis pairs {}",
+            resp.status()
+        )));
+    }
+    let pairs = resp.json::<PairsResponse>()?;
+    Ok(vec![exchange::MetadataFile::build(
+        "notes/oasis-pairs.json",
+        &pairs.data,
+    )])
+}
+
 pub fn read_abi(filename: &str) -> Contract {
     let file_ok = fs::read_to_string(filename);
     let yaml = file_ok.unwrap();
@@ -202,6 +242,26 @@ impl Contract {
 }
 
 impl exchange::Api for Oasis {
+    fn markets(
+        &self,
+        _exchange: &config::ExchangeSettings,
+    ) -> Result<Vec<exchange::MetadataFile>, Box<dyn std::error::Error>> {
+        fetch_markets(&self.client, OASIS_API)
+    }
+
+    // offers are placed on-chain. eth is sold as weth.
+    fn gas_steps(
+        &self,
+        exchange: &config::ExchangeSettings,
+        sell_token: &types::Ticker,
+    ) -> Vec<gas::Step> {
+        let mut steps = vec![gas::Step::build(&exchange.name, gas::Kind::Offer)];
+        if sell_token.symbol == "ETH" {
+            steps.push(gas::Step::build(&exchange.name, gas::Kind::Wrap))
+        }
+        steps
+    }
+
     fn build(
         &self,
         privkey: &str,
@@ -225,13 +285,13 @@ impl exchange::Api for Oasis {
             pair.quote_precision,
         );
         let cost_str = cost_int.to_str_radix(10);
-        println!(
+        log::info!(
             "qty {} {} cost {} {}",
             offer.base_qty, qty_int, offer_cost, cost_int
         );
 
-        let base_token = &self.tokens.get(&pair.base);
-        let quote_token = &self.tokens.get(&pair.quote);
+        let base_token = self.token(exchange, &pair.base)?;
+        let quote_token = self.token(exchange, &pair.quote)?;
         let sell_token = match askbid {
             types::AskBid::Ask => quote_token,
             types::AskBid::Bid => base_token,
@@ -239,14 +299,14 @@ impl exchange::Api for Oasis {
         let min_sell = match self.min_sell(&sell_token.address, exchange) {
             Ok(units) => {
                 let qty = exchange::units_to_quantity(units as u128, pair.quote_precision);
-                println!(
+                log::info!(
                     "Min-Sell {} ^{} {} = {}",
-                    &sell_token.name, pair.quote_precision, units, qty
+                    &sell_token.symbol, pair.quote_precision, units, qty
                 );
                 qty
             }
             Err(e) => {
-                println!("Err {:?}", e);
+                log::error!("Err {:?}", e);
                 0.0
             }
         };
@@ -258,10 +318,10 @@ impl exchange::Api for Oasis {
                 ),
                 code: 14 as i32,
             };
-            println!("ERR: {}", order_error);
+            log::error!("{}", order_error);
             return Err(Box::new(order_error));
         } else {
-            println!(
+            log::info!(
                 "min-cost of {} met with {}{}",
                 min_sell, offer_cost, &pair.quote
             );
@@ -283,7 +343,7 @@ impl exchange::Api for Oasis {
                 amount_sell: qty_str,
             },
         };
-        println!("{:?}", order_sheet);
+        log::info!("{:?}", order_sheet);
         Ok(exchange::OrderSheet::Oasis(order_sheet))
     }
 
@@ -295,23 +355,23 @@ impl exchange::Api for Oasis {
     ) -> Result<String, Box<dyn error::Error>> {
         if let exchange::OrderSheet::Oasis(sheet) = sheet_opt {
             let pub_addr = format!("0x{}", eth::privkey_to_addr(private_key));
-            let nonce = self.geth.nonce(&sheet.address)?;
-            let gas_price_fast = geth::ethgasstation_fast();
+            let nonce = self.geth.next_nonce(&sheet.address)?;
+            let gas_price_fast = geth::ethgasstation_fast()?;
             let gas_price_gwei = gas_price_fast / 1_000_000_000u64;
-            println!("TX Count/next nonce {} gas {}gwei", nonce, gas_price_gwei);
+            log::info!("TX Count/next nonce {} gas {}gwei", nonce.value, gas_price_gwei);
 
             let mut contract_addra = [0u8; 20];
             let contract_addr = exchange.contract_address.as_ref().unwrap().clone();
             contract_addra.copy_from_slice(&eth::dehex(&contract_addr)[..]);
             let tx = ethereum_tx_sign::RawTransaction {
-                nonce: ethereum_types::U256::from(nonce),
+                nonce: ethereum_types::U256::from(nonce.value),
                 to: Some(ethereum_types::H160::from(contract_addra)),
                 value: ethereum_types::U256::zero(),
                 gas_price: ethereum_types::U256::from(gas_price_fast),
-                gas: ethereum_types::U256::from(310240),
+                gas: ethereum_types::U256::from(gas::Kind::Offer.gas_limit()),
                 data: eth_data(&self.contract, &sheet),
             };
-            println!(
+            log::info!(
                 "nonce {} gas_price {} gas_limit {}",
                 tx.nonce, tx.gas_price, tx.gas
             );
@@ -319,10 +379,8 @@ impl exchange::Api for Oasis {
             let rlp_bytes = tx.sign(&private_key, &eth::ETH_CHAIN_MAINNET);
             let params = (eth::hex(&rlp_bytes),);
 
-            let tx = self
-                .geth
-                .rpc_str("eth_sendRawTransaction", geth::ParamTypes::Single(params))?;
-            println!("GOOD TX {}", tx);
+            let tx = self.geth.send_raw(nonce, params)?;
+            crate::log::sent_tx("GOOD TX", &tx);
             self.wait_for_balance_change(&sheet.token_buy, &pub_addr, exchange);
             Ok(tx)
         } else {
@@ -394,4 +452,19 @@ mod tests {
         };
         let _abi_hex = eth_data(&contract, &sheet);
     }
+
+    #[test]
+    fn test_fetch_markets() {
+        let mock = http::Mock::new().respond(
+            &format!("{}/pairs", OASIS_API),
+            200,
+            r#"{"data":{"WETH/DAI":{"base":"WETH","quote":"DAI","basePrecision":4,"quotePrecision":2,"active":true}},"message":"Success"}"#,
+        );
+        let client = http::LoggingClient::mocked(mock);
+        let files = fetch_markets(&client, OASIS_API).unwrap();
+        assert_eq!(files[0].filename, "notes/oasis-pairs.json");
+        let pairs = serde_yaml::from_str::<HashMap<String, PairDetail>>(&files[0].json).unwrap();
+        let pair_list = PairList { pairs };
+        assert_eq!(pair_list.get("WETH", "DAI").quote_precision, 2);
+    }
 }
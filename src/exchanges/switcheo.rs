@@ -5,6 +5,7 @@ use crate::geth;
 use crate::http;
 use crate::log;
 use crate::time;
+use crate::tokens;
 use crate::types;
 use secp256k1::SecretKey;
 use serde::{Deserialize, Serialize};
@@ -61,6 +62,8 @@ pub struct TokenDetail {
     stablecoin_type: Option<String>,
 }
 
+// switcheo's listing details (precision, minimums) for each token.
+// symbols and addresses resolve through the token registry.
 #[derive(Debug)]
 pub struct TokenList {
     pub tokens: HashMap<String, TokenDetail>,
@@ -73,9 +76,40 @@ pub fn read_tokens(filename: &str) -> TokenList {
     TokenList { tokens: tokens }
 }
 
+pub fn fetch_markets(
+    client: &http::LoggingClient,
+    api_url: &str,
+) -> Result<Vec<exchange::MetadataFile>, Box<dyn std::error::Error>> {
+    let url = format!("{}/exchange/pairs?show_details=1", api_url);
+    let resp = client.get(url.as_str()).send()?;
+    if !resp.status().is_success() {
+        return Err(exchange::ExchangeError::build_box(format!(
+            "switcheo pairs {}",
+            resp.status()
+        )));
+    }
+    let pairs = resp.json::<Vec<Pair>>()?;
+    let url = format!("{}/exchange/tokens?show_listing_details=1", api_url);
+    let resp = client.get(url.as_str()).send()?;
+    if !resp.status().is_success() {
+        return Err(exchange::ExchangeError::build_box(format!(
+            "switcheo tokens {}",
+            resp.status()
+        )));
+    }
+    let tokens = resp.json::<HashMap<String, TokenDetail>>()?;
+    Ok(vec![
+        exchange::MetadataFile::build("notes/switcheo-pairs.json", &pairs),
+        exchange::MetadataFile::build("notes/switcheo-tokens.json", &tokens),
+    ])
+}
+
 impl TokenList {
-    pub fn get(&self, ticker: &types::Ticker) -> Option<&TokenDetail> {
-        self.tokens.get(&ticker.symbol)
+    pub fn by_addr(&self, address: &str) -> Option<&TokenDetail> {
+        let address = tokens::normalize_address(address);
+        self.tokens
+            .values()
+            .find(|detail| tokens::normalize_address(&detail.hash) == address)
     }
 
     pub fn len(&self) -> usize {
@@ -536,6 +570,25 @@ impl Switcheo {
         }
     }
 
+    // a registry token, as listed by switcheo
+    pub fn token(&self, symbol: &str) -> Result<&TokenDetail, Box<dyn std::error::Error>> {
+        let registry = config::TOKENS.get().unwrap();
+        let token = registry
+            .get_on(&self.settings.name, symbol)
+            .ok_or_else(|| {
+                exchange::ExchangeError::build_box(format!(
+                    "switcheo token {} not in registry",
+                    symbol
+                ))
+            })?;
+        self.tokens.by_addr(&token.address).ok_or_else(|| {
+            exchange::ExchangeError::build_box(format!(
+                "switcheo does not list {} {}",
+                symbol, token.address
+            ))
+        })
+    }
+
     pub fn transfer(
         &self,
         privkey: &str,
@@ -546,7 +599,7 @@ impl Switcheo {
     ) -> Result<String, Box<dyn std::error::Error>> {
         let privbytes = &hex::decode(privkey).unwrap();
         let secret_key = SecretKey::from_slice(privbytes).unwrap();
-        let token_detail = self.tokens.get(&token).unwrap();
+        let token_detail = self.token(&token.symbol)?;
         let units = amount_to_units(
             amount,
             token_detail.transfer_decimals,
@@ -579,7 +632,7 @@ impl Switcheo {
             .send()
             .unwrap();
         let status = resp.status();
-        println!("{} {}", resp.url(), status);
+        log::info!("{} {}", resp.url(), status);
         let json = resp.text().unwrap();
         if status.is_success() {
             Ok(json.to_string())
@@ -589,7 +642,7 @@ impl Switcheo {
                 msg: resp_err.error,
                 code: resp_err.error_code as i32,
             };
-            println!("ERR: {}", order_error);
+            log::error!("{}", order_error);
             Err(Box::new(order_error))
         }
     }
@@ -635,7 +688,7 @@ impl Switcheo {
                 .join(", ");
             let balances_confirming = balances.confirming.len();
             repeat = if balances_confirming > 0 {
-                println!("switcheo confirming balances: {}", report);
+                log::info!("switcheo confirming balances: {}", report);
                 time::sleep(5000);
                 true
             } else {
@@ -656,7 +709,7 @@ impl exchange::Api for Switcheo {
         market: &exchange::Market,
         offer: &types::Offer,
     ) -> Result<exchange::OrderSheet, Box<dyn std::error::Error>> {
-        println!(
+        log::info!(
             "={:#?} {} {}@{}",
             askbid, market, offer.base_qty, offer.quote
         );
@@ -665,8 +718,8 @@ impl exchange::Api for Switcheo {
         let secret_key = SecretKey::from_slice(privbytes).unwrap();
         let market_pair = make_market_pair(market);
         let now_millis = time::now_millis();
-        let base_token_detail = self.tokens.get(&market.base).unwrap();
-        let quote_token_detail = self.tokens.get(&market.quote).unwrap();
+        let base_token_detail = self.token(&market.base.symbol)?;
+        let quote_token_detail = self.token(&market.quote.symbol)?;
         let pair = self.pairs.get(&market_pair).unwrap();
 
         let price = if quote_token_detail.decimals == base_token_detail.decimals {
@@ -696,7 +749,7 @@ impl exchange::Api for Switcheo {
         let sign_json = serde_json::to_string(&sheet).unwrap();
         let signature = eth::ethsign(&sign_json, &secret_key);
         let address = format!("0x{}", eth::privkey_to_addr(privkey));
-        println!("{:#?}", sheet);
+        log::debug!("{:#?}", sheet);
         let sheet_sign = OrderSheetSign {
             address: address,
             sheet: sheet,
@@ -704,8 +757,8 @@ impl exchange::Api for Switcheo {
         };
 
         let url = format!("{}/orders", exchange.api_url.as_str());
-        println!("switcheo build {}", url);
-        println!("{}", serde_json::to_string(&sheet_sign.sheet).unwrap());
+        log::info!("switcheo build {}", url);
+        log::info!("{}", serde_json::to_string(&sheet_sign.sheet).unwrap());
         let resp = self
             .client
             .post(url.as_str())
@@ -713,21 +766,21 @@ impl exchange::Api for Switcheo {
             .send()
             .unwrap();
         let status = resp.status();
-        println!("switcheo build result {:#?} {}", status, resp.url());
+        log::info!("switcheo build result {:#?} {}", status, resp.url());
         if status.is_success() {
             let json = resp.text().unwrap();
             //println!("{}", json);
             let order = serde_json::from_str::<Order>(&json).unwrap();
-            println!("{} fills", &order.fills.len());
+            log::info!("{} fills", &order.fills.len());
             for fill in &order.fills {
-                println!(
+                log::info!(
                     "{}",
                     fill_display(fill, base_token_detail, quote_token_detail)
                 );
             }
-            println!("{} makegroups", &order.makes.len());
+            log::info!("{} makegroups", &order.makes.len());
             for make in &order.makes {
-                println!(
+                log::info!(
                     "{}",
                     makegroup_display(make, base_token_detail, quote_token_detail)
                 );
@@ -739,7 +792,7 @@ impl exchange::Api for Switcheo {
                 msg: build_err.error,
                 code: build_err.error_code as i32,
             };
-            println!("ERR: {}", order_error);
+            log::error!("{}", order_error);
             Err(Box::new(order_error))
         }
     }
@@ -758,7 +811,7 @@ impl exchange::Api for Switcheo {
                 exchange.api_url.as_str(),
                 order.id
             );
-            println!("{}", url);
+            log::info!("{}", url);
             let makes = makes_sigs(&order.makes, &secret_key);
             let fill_groups = fillgroup_sigs(&order.fill_groups, &secret_key);
             let sig_sheet = SignatureBody {
@@ -769,7 +822,7 @@ impl exchange::Api for Switcheo {
                 },
             };
             let json = serde_json::to_string(&sig_sheet).unwrap();
-            println!("switcheo submit {}", json);
+            log::info!("switcheo submit {}", json);
             let resp = self
                 .client
                 .post(url.as_str())
@@ -777,7 +830,7 @@ impl exchange::Api for Switcheo {
                 .send()
                 .unwrap();
             let status = resp.status();
-            println!("{} {:?}", status, resp.text());
+            log::info!("{} {:?}", status, resp.text());
             if status.is_success() {
                 Ok(order.id.clone())
             } else {
@@ -793,20 +846,31 @@ impl exchange::Api for Switcheo {
         }
     }
 
+    fn markets(
+        &self,
+        exchange: &config::ExchangeSettings,
+    ) -> Result<Vec<exchange::MetadataFile>, Box<dyn std::error::Error>> {
+        fetch_markets(&self.client, &exchange.api_url)
+    }
+
+    fn has_market(&self, market: &exchange::Market) -> Option<bool> {
+        Some(self.pairs.get(&make_market_pair(market)).is_some())
+    }
+
     fn market_minimums(
         &self,
         market: &exchange::Market,
         _exchange: &config::ExchangeSettings,
     ) -> Option<(Option<f64>, Option<f64>)> {
-        match self.tokens.get(&market.quote) {
-            Some(base_token_detail) => {
+        match self.token(&market.quote.symbol) {
+            Ok(base_token_detail) => {
                 let min_cost = units_to_amount(
                     &base_token_detail.minimum_quantity,
                     base_token_detail.decimals,
                 );
                 Some((None, Some(min_cost)))
             }
-            None => None,
+            Err(_e) => None,
         }
     }
 
@@ -817,7 +881,7 @@ impl exchange::Api for Switcheo {
     ) -> HashMap<String, f64> {
         let balances = self.balances(public_addr, exchange);
         if balances.confirming.len() > 0 {
-            println!(
+            log::info!(
                 "WARNING: switcheo confirming balances {:?}",
                 balances.confirming
             )
@@ -825,16 +889,12 @@ impl exchange::Api for Switcheo {
         balances
             .confirmed
             .iter()
-            .map(|(symbol, units)| {
-                match self.tokens.get(&types::Ticker {
-                    symbol: symbol.to_string(),
-                }) {
-                    Some(token) => {
-                        let f_bal = units_to_amount(units, token.decimals);
-                        (symbol.clone(), f_bal)
-                    }
-                    None => (format!("conversion-err {} {}", symbol, units), 0.0),
+            .map(|(symbol, units)| match self.token(symbol) {
+                Ok(token) => {
+                    let f_bal = units_to_amount(units, token.decimals);
+                    (symbol.clone(), f_bal)
                 }
+                Err(_e) => (format!("conversion-err {} {}", symbol, units), 0.0),
             })
             .collect()
     }
@@ -904,7 +964,7 @@ impl exchange::Api for Switcheo {
                     let response = serde_json::from_str::<WithdrawalResponse>(&json).unwrap();
                     Ok(Some(response.id))
                 } else {
-                    println!("http err");
+                    log::error!("http err");
                     let err = serde_json::from_str::<TransferResponseErr>(&json).unwrap();
                     Err(exchange::ExchangeError::build_box(err.error_message))
                 }
@@ -932,7 +992,7 @@ impl exchange::Api for Switcheo {
                 let tx = self
                     .geth
                     .rpc_str("eth_sendRawTransaction", geth::ParamTypes::Single(params))?;
-                println!("deposit approval {}", tx);
+                crate::log::sent_tx("deposit approval", &tx);
                 let deposit_execute = DepositExecute {
                     transaction_hash: tx.clone(),
                 };
@@ -952,7 +1012,7 @@ impl exchange::Api for Switcheo {
                 if status.is_success() {
                     let response = serde_json::from_str::<DepositResponseOk>(&json).unwrap();
                     let tx = eth::hex(&eth::hash_msg(&eth::dehex(&response.transaction_hash)));
-                    println!("deposit tx {}", tx);
+                    crate::log::sent_tx("deposit tx", &tx);
                     Ok(Some(build_response.id))
                 } else {
                     let err = serde_json::from_str::<TransferResponseErr>(&json).unwrap();
@@ -969,7 +1029,7 @@ impl exchange::Api for Switcheo {
         exchange: &config::ExchangeSettings,
     ) -> exchange::OrderState {
         let url = format!("{}/orders/{}", self.settings.api_url.as_str(), order_id);
-        println!("{}", url);
+        log::info!("{}", url);
         let resp = self.client.get(url.as_str()).send().unwrap();
         let status = resp.status();
         if status.is_success() {
@@ -977,7 +1037,7 @@ impl exchange::Api for Switcheo {
             match order.order_status.finto() {
                 exchange::OrderState::Filled => {
                     // wait for confirming balances
-                    println!("switcheo order_status shows Filled, waiting on confirming balances");
+                    log::info!("switcheo order_status shows Filled, waiting on confirming balances");
                     let config = config::CONFIG.get().unwrap();
                     let my_addr = eth::privkey_to_addr(&config.wallet_private_key);
                     self.wait_confirming_balances(&my_addr, exchange);
@@ -1002,7 +1062,7 @@ impl exchange::Api for Switcheo {
             my_addr,
             exchange.contract_address.as_ref().unwrap()
         );
-        println!("{}", url);
+        log::info!("{}", url);
         let resp = self.client.get(url.as_str()).send().unwrap();
         let status = resp.status();
         if status.is_success() {
@@ -1010,24 +1070,16 @@ impl exchange::Api for Switcheo {
             let shortlist = Vec::<exchange::Order>::new();
             orders.into_iter().fold(shortlist, |mut m, o| {
                 let (base_name, quote_name) = split_market_pair(&o.pair);
-                match self.tokens.get(&types::Ticker {
-                    symbol: base_name.to_string(),
-                }) {
-                    Some(base_token) => {
-                        match self.tokens.get(&types::Ticker {
-                            symbol: quote_name.to_string(),
-                        }) {
-                            Some(quote_token) => m.push(o.into_exg(base_token, quote_token)),
-                            None => (),
-                        }
-                    }
-                    None => (),
+                if let (Ok(base_token), Ok(quote_token)) =
+                    (self.token(&base_name), self.token(&quote_name))
+                {
+                    m.push(o.into_exg(base_token, quote_token))
                 }
                 m
             })
         } else {
             let build_err = resp.json::<ResponseError>().unwrap();
-            println!("{:?}", build_err);
+            log::error!("{:?}", build_err);
             vec![] // bad
         }
     }
@@ -1164,6 +1216,148 @@ mod tests {
         assert_eq!(amt, 1.23456789)
     }
 
+    #[test]
+    fn test_fetch_markets() {
+        let api_url = "https://api.switcheo.network/v2";
+        let mock = http::Mock::new()
+            .respond(
+                &format!("{}/exchange/pairs?show_details=1", api_url),
+                200,
+                r#"[{"name":"SWTH_ETH","precision":8},{"name":"DAI_ETH","precision":4}]"#,
+            )
+            .respond(
+                &format!("{}/exchange/tokens?show_listing_details=1", api_url),
+                200,
+                r#"{"DAI":{"symbol":"DAI","name":"Dai","type":"ERC-20","hash":"6b175474e89094c44da98b954eedeac495271d0f","decimals":18,"transfer_decimals":18,"precision":2,"minimum_quantity":"1000000000000000000","trading_active":true,"is_stablecoin":true,"stablecoin_type":"dai","listing_info":{}}}"#,
+            );
+        let client = http::LoggingClient::mocked(mock);
+        let files = fetch_markets(&client, api_url).unwrap();
+        assert_eq!(files.len(), 2);
+        assert_eq!(files[0].filename, "notes/switcheo-pairs.json");
+        let pairs = serde_json::from_str::<Vec<Pair>>(&files[0].json).unwrap();
+        assert_eq!(pairs[1].name, "DAI_ETH");
+        let tokens = serde_json::from_str::<HashMap<String, TokenDetail>>(&files[1].json).unwrap();
+        assert_eq!(tokens["DAI"].decimals, 18);
+        assert!(!files[1].json.contains("listing_info"));
+    }
+
+    #[test]
+    fn test_fetch_markets_error() {
+        let client = http::LoggingClient::mocked(http::Mock::new());
+        assert!(fetch_markets(&client, "https://api.switcheo.network/v2").is_err());
+    }
+
+    // an eth token as listed by /exchange/tokens
+    fn token_detail(symbol: &str, hash: &str) -> TokenDetail {
+        TokenDetail {
+            symbol: symbol.to_string(),
+            name: symbol.to_string(),
+            r#type: "ERC-20".to_string(),
+            hash: hash.to_string(),
+            decimals: 18,
+            transfer_decimals: 18,
+            precision: 2,
+            minimum_quantity: "0".to_string(),
+            trading_active: true,
+            is_stablecoin: false,
+            stablecoin_type: None,
+        }
+    }
+
+    fn settings() -> config::ExchangeSettings {
+        config::ExchangeSettings {
+            name: "switcheo".to_string(),
+            enabled: true,
+            has_balances: true,
+            protocol: config::ExchangeProtocol::Switcheo,
+            contract_address: Some("0x7ee7ca6e75de79e618e88bdf80d0b1db136b22d0".to_string()),
+            fee_recipient_address: None,
+            api_url: "https://api.switcheo.network/v2".to_string(),
+            maker_fee: 0.0,
+            taker_fee: 0.0025,
+            sheets: types::SheetStrategy::Rollup,
+            rate_limit_ms: None,
+            max_order_age_secs: None,
+        }
+    }
+
+    // replays hand-written v2 api responses, not captured from the live api
+    fn replayed() -> Switcheo {
+        tokens::test_registry();
+        let cassette = http::Cassette::open(&http::CassetteSettings {
+            mode: http::CassetteMode::Replay,
+            path: "notes/cassettes/switcheo.synthetic.json".to_string(),
+        })
+        .unwrap();
+        let mut tokens = HashMap::new();
+        tokens.insert(
+            "DAI".to_string(),
+            token_detail("DAI", "0x6b175474e89094c44da98b954eedeac495271d0f"),
+        );
+        tokens.insert(
+            "ETH".to_string(),
+            token_detail("ETH", "0x0000000000000000000000000000000000000000"),
+        );
+        Switcheo {
+            geth: geth::Client::build("http://localhost:8545"),
+            tokens: TokenList { tokens },
+            pairs: PairList {
+                pairs: vec![Pair {
+                    name: "DAI_ETH".to_string(),
+                    precision: 8,
+                }],
+            },
+            settings: settings(),
+            client: http::LoggingClient::cassette(cassette),
+        }
+    }
+
+    fn build_order(
+        switcheo: &Switcheo,
+    ) -> Result<exchange::OrderSheet, Box<dyn std::error::Error>> {
+        let market = exchange::Market {
+            base: types::Ticker::from("DAI"),
+            base_contract: "0x6b175474e89094c44da98b954eedeac495271d0f".to_string(),
+            quote: types::Ticker::from("ETH"),
+            quote_contract: "0x0000000000000000000000000000000000000000".to_string(),
+            quantity_decimals: 2.0,
+            price_decimals: 8.0,
+            source_name: "switcheo".to_string(),
+        };
+        let offer = types::Offer {
+            base_qty: 100.0,
+            quote: 0.005,
+        };
+        let askbid = types::AskBid::Ask;
+        exchange::Api::build(switcheo, PRIVKEY, &askbid, &settings(), &market, &offer)
+    }
+
+    #[test]
+    fn test_build_cassette() {
+        let switcheo = replayed();
+        match build_order(&switcheo).unwrap() {
+            exchange::OrderSheet::Switcheo(order) => {
+                assert_eq!(order.id, "c3ea8f6d-6a3e-4b1e-9a0c-2d4f0b8e1a55");
+                assert_eq!(order.pair, "DAI_ETH");
+                assert_eq!(order.fills.len(), 1);
+                assert_eq!(
+                    order.fill_groups[0].fill_ids,
+                    vec![order.fills[0].id.clone()]
+                );
+                assert_eq!(order.makes.len(), 0);
+            }
+            _ => panic!("not a switcheo order"),
+        }
+    }
+
+    #[test]
+    fn test_submit_cassette() {
+        let switcheo = replayed();
+        let sheet = build_order(&switcheo).unwrap();
+        let id = exchange::Api::submit(&switcheo, PRIVKEY, &settings(), sheet).unwrap();
+        assert_eq!(id, "c3ea8f6d-6a3e-4b1e-9a0c-2d4f0b8e1a55");
+    }
+
     #[test]
     fn test_fillgroup_sigs() {
         let sha256 = "b64c9ca323f29f9de97212bc108361aa9d28bc2feccafd9bd6caf5e40a4cc7e7";
@@ -1,6 +1,7 @@
 use crate::config;
 use crate::eth;
 use crate::exchange;
+use crate::gas;
 use crate::geth;
 use crate::http;
 use crate::time;
@@ -8,7 +9,6 @@ use crate::types;
 use chrono;
 use secp256k1::SecretKey;
 use serde::{Deserialize, Serialize};
-use std::fs;
 use std::str::FromStr;
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -166,52 +166,46 @@ pub enum SignatureType {
     EIP1271Wallet = 0x07,
 }
 
-pub fn read_tokens(filename: &str) -> TokenList {
-    let file_ok = fs::read_to_string(filename);
-    let yaml = file_ok.unwrap();
-    let tokens = serde_yaml::from_str(&yaml).unwrap();
-    TokenList { tokens: tokens }
-}
-
 #[derive(Debug, Serialize, Deserialize)]
-pub struct TokenList {
-    tokens: Vec<Token>,
+pub struct TokenListing {
+    symbol: String,
+    address: String,
+    decimals: i32,
 }
 
-impl TokenList {
-    pub fn by_addr(&self, addr: &str) -> &Token {
-        self.tokens
-            .iter()
-            .find(|detail| detail.address == addr)
-            .unwrap()
+pub fn fetch_markets(
+    client: &http::LoggingClient,
+    exchange: &config::ExchangeSettings,
+) -> Result<Vec<exchange::MetadataFile>, Box<dyn std::error::Error>> {
+    let url = format!("{}/tokens", exchange.api_url);
+    let resp = client.get(url.as_str()).send()?;
+    if !resp.status().is_success() {
+        return Err(exchange::ExchangeError::build_box(format!(
+            "{} tokens {}",
+            exchange.name,
+            resp.status()
+        )));
     }
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-pub struct Token {
-    pub address: String,
-    pub symbol: String,
-    pub name: String,
-    pub decimals: u32,
-    pub active: u32,
+    let tokens = resp.json::<Vec<TokenListing>>()?;
+    Ok(vec![exchange::MetadataFile::build(
+        &format!("notes/{}-tokens.json", exchange.name),
+        &tokens,
+    )])
 }
 
 pub struct Zeroex {
     pub settings: config::ExchangeSettings,
     client: http::LoggingClient,
-    tokens: TokenList,
     geth: geth::Client,
 }
 
 impl Zeroex {
     pub fn new(settings: config::ExchangeSettings, geth: geth::Client) -> Zeroex {
-        let tokens = read_tokens("notes/radarrelay-tokens.json");
         let client = reqwest::blocking::Client::new();
         let logging_client = http::LoggingClient::new(client);
         Zeroex {
             settings: settings,
             client: logging_client,
-            tokens: tokens,
             geth: geth,
         }
     }
@@ -220,6 +214,19 @@ impl Zeroex {
 impl exchange::Api for Zeroex {
     fn setup(&mut self) {}
 
+    // the taker fill is an ethereum tx. eth is sold as weth.
+    fn gas_steps(
+        &self,
+        exchange: &config::ExchangeSettings,
+        sell_token: &types::Ticker,
+    ) -> Vec<gas::Step> {
+        let mut steps = vec![gas::Step::build(&exchange.name, gas::Kind::Fill)];
+        if sell_token.symbol == "ETH" {
+            steps.push(gas::Step::build(&exchange.name, gas::Kind::Wrap))
+        }
+        steps
+    }
+
     fn build(
         &self,
         privkey: &str,
@@ -228,7 +235,7 @@ impl exchange::Api for Zeroex {
         market: &exchange::Market,
         offer: &types::Offer,
     ) -> Result<exchange::OrderSheet, Box<dyn std::error::Error>> {
-        println!(
+        log::info!(
             "=0x build {:#?} {} {}@{}",
             askbid, market, offer.base_qty, offer.quote
         );
@@ -255,53 +262,65 @@ impl exchange::Api for Zeroex {
             exchange.api_url.as_str(),
             market.id("-")
         );
-        println!("{}", url);
-        println!("{}", serde_json::to_string(&sheet).unwrap());
+        log::info!("{}", url);
+        log::info!("{}", serde_json::to_string(&sheet).unwrap());
         let resp = self.client.post(url.as_str()).json(&sheet).send()?;
-        println!("{:#?} {}", resp.status(), resp.url());
+        log::info!("{:#?} {}", resp.status(), resp.url());
         if resp.status().is_success() {
             let mkt_orders = resp.json::<MarketOrders>().unwrap();
+            let registry = config::TOKENS.get().unwrap();
             let mut forms = mkt_orders
                 .orders
                 .into_iter()
                 .fold(vec![], |mut memo, form| {
-                    println!("considering {:#?}", form);
+                    log::debug!("considering {:#?}", form);
                     let taker_asset_addr = format!("0x{}", &form.taker_asset_data[34..74]);
-                    let taker_token = self.tokens.by_addr(&taker_asset_addr);
                     let maker_asset_addr = format!("0x{}", &form.maker_asset_data[34..74]);
-                    let maker_token = self.tokens.by_addr(&maker_asset_addr);
-                    let taker_qty = form.taker_qty(taker_token.decimals);
-                    let maker_qty = form.maker_qty(maker_token.decimals);
+                    let (taker_token, maker_token) = match (
+                        registry.by_addr(&taker_asset_addr),
+                        registry.by_addr(&maker_asset_addr),
+                    ) {
+                        (Some(taker_token), Some(maker_token)) => (taker_token, maker_token),
+                        _ => {
+                            log::info!(
+                                "skipping form with unknown token {} {}",
+                                taker_asset_addr, maker_asset_addr
+                            );
+                            return memo;
+                        }
+                    };
+                    let taker_qty = form.taker_qty(taker_token.decimals as u32);
+                    let maker_qty = form.maker_qty(maker_token.decimals as u32);
                     let (mkt_qty, mkt_price) = match side {
                         BuySell::Buy => (maker_qty, taker_qty / maker_qty),
                         BuySell::Sell => (taker_qty, maker_qty / taker_qty),
                     };
-                    println!("offer {:?} {}@{}", side, mkt_qty, mkt_price);
+                    log::info!("offer {:?} {}@{}", side, mkt_qty, mkt_price);
                     let better = match side {
                         BuySell::Buy => mkt_price <= offer.quote,
                         BuySell::Sell => mkt_price >= offer.quote,
                     };
-                    println!(
+                    log::info!(
                         "better {} for price {} (offer quote {})",
                         better, mkt_price, offer.quote
                     );
                     let good_qty = eth::minimum(&vec![qty, mkt_qty]);
-                    println!("good qty {}", good_qty);
+                    log::info!("good qty {}", good_qty);
                     if good_qty > 0.0 && better {
                         let (maker_qty, taker_qty) = match side {
                             BuySell::Buy => (good_qty, (good_qty / maker_qty) * taker_qty),
                             BuySell::Sell => ((good_qty / taker_qty) * maker_qty, good_qty),
                         };
-                        println!("maker_qty {} taker_qty {}", maker_qty, taker_qty);
+                        log::info!("maker_qty {} taker_qty {}", maker_qty, taker_qty);
                         let taker_asset_amount = format!(
                             "{}",
                             exchange::quantity_in_base_units(
                                 taker_qty,
-                                taker_token.decimals as i32,
-                                taker_token.decimals as i32
+                                taker_token.decimals,
+                                taker_token.decimals
                             )
                         );
-                        println!("taking units {:#?}", taker_asset_amount);
+                        log::info!("taking units {:#?}", taker_asset_amount);
                         memo.push((form, taker_asset_amount));
                     }
                     memo
@@ -320,7 +339,7 @@ impl exchange::Api for Zeroex {
                 msg: bodyerr.error,
                 code: -1,
             };
-            println!("ERR: {}", order_error);
+            log::error!("{}", order_error);
             Err(Box::new(order_error))
         }
     }
@@ -333,12 +352,12 @@ impl exchange::Api for Zeroex {
     ) -> Result<String, Box<dyn std::error::Error>> {
         if let exchange::OrderSheet::Zeroex((order, amount)) = sheet {
             let pub_addr = format!("0x{}", eth::privkey_to_addr(private_key));
-            let nonce = self.geth.nonce(&pub_addr).unwrap();
-            let gas_limit = 250000;
-            let gas_price_fast = geth::ethgasstation_fast();
+            let nonce = self.geth.next_nonce(&pub_addr).unwrap();
+            let gas_limit = gas::Kind::Fill.gas_limit();
+            let gas_price_fast = geth::ethgasstation_fast()?;
             let gas_price_gwei = gas_price_fast / 1_000_000_000u64;
             let gas_cost = gas_limit * gas_price_fast;
-            println!(
+            log::info!(
                 "deposit tx {} gas limit @{}gwei (ethgasstation_fast) = {} eth",
                 gas_limit,
                 gas_price_gwei,
@@ -349,9 +368,9 @@ impl exchange::Api for Zeroex {
             let contract_addr = exchange.contract_address.as_ref().unwrap().clone();
             contract_addra.copy_from_slice(&eth::dehex(&contract_addr)[..]);
             let data = order_fill_data(&order, &amount, eth::dehex(&order.signature));
-            println!("filling order with amount {}", amount);
+            log::info!("filling order with amount {}", amount);
             let tx = ethereum_tx_sign::RawTransaction {
-                nonce: ethereum_types::U256::from(nonce),
+                nonce: ethereum_types::U256::from(nonce.value),
                 to: Some(ethereum_types::H160::from(contract_addra)),
                 value: ethereum_types::U256::zero(),
                 gas_price: ethereum_types::U256::from(gas_price_fast),
@@ -361,10 +380,8 @@ impl exchange::Api for Zeroex {
             let private_key = ethereum_types::H256::from_slice(&eth::dehex(private_key));
             let rlp_bytes = tx.sign(&private_key, &eth::ETH_CHAIN_MAINNET);
             let params = (eth::hex(&rlp_bytes),);
-            let tx = self
-                .geth
-                .rpc_str("eth_sendRawTransaction", geth::ParamTypes::Single(params))?;
-            println!("GOOD TX {}", tx);
+            let tx = self.geth.send_raw(nonce, params)?;
+            crate::log::sent_tx("GOOD TX", &tx);
             Ok(tx)
         } else {
             Err(exchange::ExchangeError::build_box(format!(
@@ -373,6 +390,13 @@ impl exchange::Api for Zeroex {
         }
     }
 
+    fn markets(
+        &self,
+        exchange: &config::ExchangeSettings,
+    ) -> Result<Vec<exchange::MetadataFile>, Box<dyn std::error::Error>> {
+        fetch_markets(&self.client, exchange)
+    }
+
     fn open_orders(
         &self,
         private_key: &str,
@@ -384,7 +408,7 @@ impl exchange::Api for Zeroex {
             exchange.api_url.as_str(),
             account
         );
-        println!("{}", url);
+        log::info!("{}", url);
         let resp = self.client.get(url.as_str()).send().unwrap();
         //println!("{:#?} {}", resp.status(), resp.url());
         let orders = resp.json::<Vec<Order>>().unwrap();
@@ -0,0 +1,161 @@
+use crate::types;
+use std::fmt;
+
+// exchanges.yaml maker_fee/taker_fee are fractions of the traded amount:
+// 0.002 is a 0.2% fee. the fee is taken from the token received.
+pub static MAX_FEE: f64 = 0.05;
+
+#[derive(Debug)]
+pub struct FeeError {
+    pub msg: String,
+}
+
+impl std::error::Error for FeeError {}
+
+impl fmt::Display for FeeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+pub fn validate(name: &str, fee: f64) -> Result<f64, FeeError> {
+    if !fee.is_finite() || fee < 0.0 {
+        Err(FeeError {
+            msg: format!("{} {} is not a valid fee fraction", name, fee),
+        })
+    } else if fee > MAX_FEE {
+        Err(FeeError {
+            msg: format!(
+                "{} {} is over the {} maximum. fees are fractions, 0.002 = 0.2%",
+                name, fee, MAX_FEE
+            ),
+        })
+    } else {
+        Ok(fee)
+    }
+}
+
+// a capped offer in order terms (before unswap), with the fee it pays
+#[derive(Debug)]
+pub struct Entry {
+    pub askbid: types::AskBid,
+    pub exchange: String,
+    pub offer: types::Offer,
+    pub fee: f64,
+}
+
+impl Entry {
+    // base token received on an ask, quote token received on a bid
+    pub fn received(&self) -> f64 {
+        let gross = match self.askbid {
+            types::AskBid::Ask => self.offer.base_qty,
+            types::AskBid::Bid => self.offer.base_qty * self.offer.quote,
+        };
+        gross * (1.0 - self.fee)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Ledger {
+    pub entries: Vec<Entry>,
+}
+
+impl Ledger {
+    pub fn new() -> Ledger {
+        Ledger::default()
+    }
+
+    pub fn add(&mut self, entry: Entry) {
+        self.entries.push(entry)
+    }
+
+    pub fn clear(&mut self, askbid: types::AskBid) {
+        self.entries.retain(|e| e.askbid != askbid)
+    }
+
+    fn side(&self, askbid: types::AskBid) -> impl Iterator<Item = &Entry> {
+        self.entries.iter().filter(move |e| e.askbid == askbid)
+    }
+
+    pub fn has_asks(&self) -> bool {
+        self.side(types::AskBid::Ask).next().is_some()
+    }
+
+    // base bought on the ask leg, net of fees, not yet claimed by a bid
+    pub fn unsold(&self) -> f64 {
+        let bought: f64 = self.side(types::AskBid::Ask).map(|e| e.received()).sum();
+        let sold: f64 = self
+            .side(types::AskBid::Bid)
+            .map(|e| e.offer.base_qty)
+            .sum();
+        (bought - sold).max(0.0)
+    }
+
+    // quote spent on the asks
+    pub fn ask_spent(&self) -> f64 {
+        self.side(types::AskBid::Ask)
+            .map(|e| e.offer.base_qty * e.offer.quote)
+            .sum()
+    }
+
+    // quote received from the bids, net of fees
+    pub fn bid_received(&self) -> f64 {
+        self.side(types::AskBid::Bid).map(|e| e.received()).sum()
+    }
+
+    // quote received from the bids minus quote spent on the asks
+    pub fn expected_profit(&self) -> f64 {
+        self.bid_received() - self.ask_spent()
+    }
+
+    pub fn fees_paid(&self) -> f64 {
+        self.entries
+            .iter()
+            .map(|e| {
+                let gross = e.offer.base_qty * e.offer.quote;
+                gross * e.fee
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate() {
+        assert_eq!(validate("taker_fee", 0.002).unwrap(), 0.002);
+        assert!(validate("taker_fee", 0.2).is_err());
+        assert!(validate("taker_fee", -0.1).is_err());
+    }
+
+    #[test]
+    fn test_ledger() {
+        let mut ledger = Ledger::new();
+        ledger.add(Entry {
+            askbid: types::AskBid::Ask,
+            exchange: "idex".to_string(),
+            offer: types::Offer {
+                base_qty: 100.0,
+                quote: 0.01,
+            },
+            fee: 0.01,
+        });
+        assert_eq!(ledger.unsold(), 99.0);
+        ledger.add(Entry {
+            askbid: types::AskBid::Bid,
+            exchange: "switcheo".to_string(),
+            offer: types::Offer {
+                base_qty: 99.0,
+                quote: 0.0102,
+            },
+            fee: 0.0,
+        });
+        assert_eq!(ledger.unsold(), 0.0);
+        let profit = ledger.expected_profit(); // 99 * 0.0102 - 100 * 0.01
+        assert!((profit - 0.0098).abs() < 1e-9);
+        ledger.clear(types::AskBid::Bid);
+        assert_eq!(ledger.unsold(), 99.0);
+    }
+}
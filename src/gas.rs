@@ -0,0 +1,133 @@
+use std::fmt;
+
+// gas limits match the ones the transaction builders send with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Fill,
+    Offer,
+    Deposit,
+    Withdraw,
+    Approve,
+    Wrap,
+}
+
+impl Kind {
+    pub fn gas_limit(&self) -> u64 {
+        match self {
+            Kind::Fill => 250_000,     // zeroex submit
+            Kind::Offer => 310_240,    // oasis offer
+            Kind::Deposit => 70_000,   // idex deposit
+            Kind::Withdraw => 100_000, // charged by the exchange on withdraw
+            Kind::Approve => 310_240,  // erc20 approve
+            Kind::Wrap => 50_000,      // weth deposit
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let word = match self {
+            Kind::Fill => "fill",
+            Kind::Offer => "offer",
+            Kind::Deposit => "deposit",
+            Kind::Withdraw => "withdraw",
+            Kind::Approve => "approve",
+            Kind::Wrap => "wrap",
+        };
+        write!(f, "{}", word)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub exchange: String,
+    pub kind: Kind,
+    pub gas: u64,
+}
+
+impl Step {
+    pub fn build(exchange: &str, kind: Kind) -> Step {
+        Step {
+            exchange: exchange.to_string(),
+            kind,
+            gas: kind.gas_limit(),
+        }
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {} {}", self.exchange, self.kind, self.gas)
+    }
+}
+
+// on-chain steps an order needs, tagged by side so a leg can be rebuilt
+#[derive(Debug, Default)]
+pub struct Plan {
+    pub steps: Vec<(crate::types::AskBid, Step)>,
+}
+
+impl Plan {
+    pub fn new() -> Plan {
+        Plan::default()
+    }
+
+    pub fn add(&mut self, askbid: crate::types::AskBid, step: Step) {
+        self.steps.push((askbid, step))
+    }
+
+    pub fn clear(&mut self, askbid: crate::types::AskBid) {
+        self.steps.retain(|(side, _step)| *side != askbid)
+    }
+
+    pub fn total_gas(&self) -> u64 {
+        self.steps.iter().map(|(_side, step)| step.gas).sum()
+    }
+
+    pub fn cost_eth(&self, gas_price_wei: u64) -> f64 {
+        self.total_gas() as f64 * gas_price_wei as f64 / 1e18
+    }
+
+    // gas cost in quote tokens, using the order's usd prices
+    pub fn cost_quote(&self, gas_price_wei: u64, network_usd: f64, quote_usd: f64) -> Option<f64> {
+        if quote_usd > 0.0 && network_usd > 0.0 {
+            Some(self.cost_eth(gas_price_wei) * network_usd / quote_usd)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Plan {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let parts: Vec<String> = self
+            .steps
+            .iter()
+            .map(|(side, step)| format!("{} {}", side, step))
+            .collect();
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::AskBid;
+
+    #[test]
+    fn test_plan_cost() {
+        let mut plan = Plan::new();
+        plan.add(AskBid::Ask, Step::build("radarrelay", Kind::Fill));
+        plan.add(AskBid::Bid, Step::build("idex", Kind::Deposit));
+        plan.add(AskBid::Bid, Step::build("idex", Kind::Withdraw));
+        assert_eq!(plan.total_gas(), 420_000);
+        let gwei_10 = 10_000_000_000;
+        assert_eq!(plan.cost_eth(gwei_10), 0.0042);
+        // eth at 200usd, quote token at 1usd
+        let quote = plan.cost_quote(gwei_10, 200.0, 1.0).unwrap();
+        assert!((quote - 0.84).abs() < 1e-9);
+        assert_eq!(plan.cost_quote(gwei_10, 200.0, 0.0), None);
+        plan.clear(AskBid::Bid);
+        assert_eq!(plan.total_gas(), 250_000);
+    }
+}
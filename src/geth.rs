@@ -1,10 +1,16 @@
 use crate::errors;
 use crate::http;
+use crate::metrics;
 use bs58;
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::error;
+use std::sync::Mutex;
+
+// nonces handed out per address and not yet counted by the node, shared by
+// concurrent submits
+static NONCES: Mutex<Option<HashMap<String, Vec<u32>>>> = Mutex::new(None);
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -64,13 +70,33 @@ impl Client {
         u32::from_str_radix(&blk_num_str[2..], 16).unwrap()
     }
 
+    // the transaction count including those still in the mempool
     pub fn nonce(&self, addr: &str) -> Result<u32, Box<dyn error::Error>> {
-        let params = (addr.to_string(), "latest".to_string());
+        let params = (addr.to_string(), "pending".to_string());
         let tx_count_str =
             self.rpc_str("eth_getTransactionCount", ParamTypes::InfuraSingle(params))?;
         Ok(u32::from_str_radix(&tx_count_str[2..], 16).unwrap())
     }
 
+    // the next unused nonce for addr. another thread may hold a nonce the
+    // node has not seen yet.
+    pub fn next_nonce(&self, addr: &str) -> Result<Nonce, Box<dyn error::Error>> {
+        let node_nonce = self.nonce(addr)?;
+        Ok(hold_nonce(addr, node_nonce))
+    }
+
+    // broadcast a transaction signed with a nonce from next_nonce. a sent
+    // nonce stays held until the node counts it, a failed one is freed.
+    pub fn send_raw(
+        &self,
+        mut nonce: Nonce,
+        params: SingleParam,
+    ) -> Result<String, Box<dyn error::Error>> {
+        let result = self.rpc_str("eth_sendRawTransaction", ParamTypes::Single(params));
+        nonce.sent = result.is_ok();
+        result
+    }
+
     pub fn call(
         &self,
         method: &str,
@@ -82,8 +108,10 @@ impl Client {
             method: method.to_string(),
             params: params,
         };
-        println!("geth {}", method);
+        log::info!("geth {}", method);
+        let started = std::time::Instant::now();
         let result = self.http.post(&self.url).json(&jrpc).send();
+        metrics::rpc_call(method, started.elapsed());
         match result {
             Ok(res) => {
                 let json = res.text().unwrap();
@@ -156,6 +184,56 @@ pub struct ErrorDetailRpc {
     pub message: String,
 }
 
+// the lowest nonce from the node's pending count up that no other thread
+// holds. reservations the node has counted past are dropped.
+// a nonce reserved for one transaction. dropped unsent, by an error or a
+// panic before send_raw, it is free for the next transaction.
+pub struct Nonce {
+    addr: String,
+    pub value: u32,
+    sent: bool,
+}
+
+impl Drop for Nonce {
+    fn drop(&mut self) {
+        if !self.sent {
+            let mut nonces = NONCES.lock().unwrap_or_else(|e| e.into_inner());
+            release_nonce(
+                nonces.get_or_insert_with(HashMap::new),
+                &self.addr,
+                self.value,
+            );
+        }
+    }
+}
+
+fn hold_nonce(addr: &str, node_nonce: u32) -> Nonce {
+    let mut nonces = NONCES.lock().unwrap();
+    let value = reserve_nonce(nonces.get_or_insert_with(HashMap::new), addr, node_nonce);
+    Nonce {
+        addr: addr.to_string(),
+        value,
+        sent: false,
+    }
+}
+
+fn reserve_nonce(nonces: &mut HashMap<String, Vec<u32>>, addr: &str, node_nonce: u32) -> u32 {
+    let reserved = nonces.entry(addr.to_lowercase()).or_default();
+    reserved.retain(|nonce| *nonce >= node_nonce);
+    let mut nonce = node_nonce;
+    while reserved.contains(&nonce) {
+        nonce += 1;
+    }
+    reserved.push(nonce);
+    nonce
+}
+
+fn release_nonce(nonces: &mut HashMap<String, Vec<u32>>, addr: &str, nonce: u32) {
+    if let Some(reserved) = nonces.get_mut(&addr.to_lowercase()) {
+        reserved.retain(|held| *held != nonce)
+    }
+}
+
 pub fn gen_id() -> String {
     let mut pad = [0u8; 6];
     rand::thread_rng().fill(&mut pad);
@@ -171,13 +249,46 @@ pub struct EthGasStationResult {
     pub average: f32,
 }
 
-pub fn ethgasstation() -> EthGasStationResult {
+pub fn ethgasstation() -> Result<EthGasStationResult, Box<dyn error::Error>> {
     let url = "https://ethgasstation.info/api/ethgasAPI.json";
-    let client = reqwest::blocking::Client::new();
-    let result = client.get(url).send().unwrap();
-    result.json::<EthGasStationResult>().unwrap()
+    let client = http::LoggingClient::new(reqwest::blocking::Client::new());
+    client.get(url).send()?.json::<EthGasStationResult>()
 }
-pub fn ethgasstation_fast() -> u64 {
-    let gas_prices = ethgasstation();
-    (gas_prices.fast as f64 * 100_000_000u64 as f64) as u64
+pub fn ethgasstation_fast() -> Result<u64, Box<dyn error::Error>> {
+    let gas_prices = ethgasstation()?;
+    Ok((gas_prices.fast as f64 * 100_000_000_f64) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserve_nonce() {
+        let mut nonces = HashMap::new();
+        assert_eq!(reserve_nonce(&mut nonces, "0xAB", 7), 7);
+        assert_eq!(reserve_nonce(&mut nonces, "0xab", 7), 8); // node has not seen 7 yet
+        release_nonce(&mut nonces, "0xab", 7); // send failed
+        assert_eq!(reserve_nonce(&mut nonces, "0xab", 7), 7); // no gap left behind
+        release_nonce(&mut nonces, "0xab", 7);
+        release_nonce(&mut nonces, "0xab", 8);
+        assert_eq!(reserve_nonce(&mut nonces, "0xab", 7), 7); // cache resynced to the node
+        assert_eq!(reserve_nonce(&mut nonces, "0xab", 12), 12);
+        assert_eq!(nonces["0xab"], vec![12]);
+        assert_eq!(reserve_nonce(&mut nonces, "0xcd", 3), 3);
+    }
+
+    #[test]
+    fn test_nonce_released_on_drop() {
+        let first = hold_nonce("0xnoncedrop", 5);
+        let second = hold_nonce("0xnoncedrop", 5);
+        assert_eq!((first.value, second.value), (5, 6));
+        drop(first); // an early return before send
+        let mut sent = hold_nonce("0xnoncedrop", 5);
+        assert_eq!(sent.value, 5);
+        sent.sent = true;
+        drop(sent); // held until the node counts it
+        assert_eq!(hold_nonce("0xnoncedrop", 5).value, 7);
+        assert_eq!(hold_nonce("0xnoncedrop", 6).value, 7);
+    }
 }
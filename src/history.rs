@@ -0,0 +1,149 @@
+use crate::log;
+use crate::report;
+use crate::time;
+use crate::types;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+
+pub static FILENAME: &str = "history.jsonl";
+
+// one leg of a finished order
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Leg {
+    pub exchange: String,
+    pub askbid: types::AskBid,
+    pub sheets: usize,
+    pub filled: usize,
+    pub failures: usize,
+}
+
+// one order taken for execution, appended to history.jsonl as it finishes.
+// `yith report` aggregates these.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Record {
+    pub time: u64, // epoch seconds
+    pub order_id: String,
+    pub pair: String,
+    pub aborted: Option<String>,
+    pub predicted_usd: f64,        // net of fees and gas, as planned
+    pub realized_usd: Option<f64>, // wallet value change. none for trades
+    pub fees_usd: f64,
+    pub gas_usd: f64,
+    pub legs: Vec<Leg>,
+    #[serde(default)]
+    pub balance_change: BTreeMap<String, f64>, // usd by source
+}
+
+impl Record {
+    pub fn build(order: &types::Order, run_report: &report::RunReport) -> Record {
+        Record {
+            time: time::since_epoch().as_secs(),
+            order_id: order.id.clone(),
+            pair: order.pair.to_string(),
+            aborted: run_report.aborted.clone(),
+            predicted_usd: run_report.net_profit_usd,
+            realized_usd: None,
+            fees_usd: run_report.fees * order.quote_usd,
+            gas_usd: run_report.gas.unwrap_or(0.0) * order.quote_usd,
+            legs: run_report
+                .legs
+                .iter()
+                .map(|leg| Leg {
+                    exchange: leg.exchange.clone(),
+                    askbid: leg.askbid,
+                    sheets: leg.sheets,
+                    filled: leg.filled.len(),
+                    failures: leg.failures,
+                })
+                .collect(),
+            balance_change: BTreeMap::new(),
+        }
+    }
+
+    // turned away before any leg was built
+    pub fn rejected(order: &types::Order, reason: &str) -> Record {
+        Record {
+            time: time::since_epoch().as_secs(),
+            order_id: order.id.clone(),
+            pair: order.pair.to_string(),
+            aborted: Some(reason.to_string()),
+            predicted_usd: 0.0,
+            realized_usd: None,
+            fees_usd: 0.0,
+            gas_usd: 0.0,
+            legs: vec![],
+            balance_change: BTreeMap::new(),
+        }
+    }
+
+    // realized value and per source change between two wallet reports
+    pub fn balances(&mut self, start: &report::BalanceReport, end: &report::BalanceReport) {
+        self.realized_usd = Some(end.total - start.total);
+        let sources = start.subtotals.keys().chain(end.subtotals.keys());
+        for source in sources {
+            let before = start.subtotals.get(source).unwrap_or(&0.0);
+            let after = end.subtotals.get(source).unwrap_or(&0.0);
+            self.balance_change.insert(source.clone(), after - before);
+        }
+    }
+}
+
+pub fn append(filename: &str, record: &Record) {
+    let written = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(filename)
+        .and_then(|mut file| writeln!(file, "{}", serde_json::to_string(record).unwrap()));
+    if let Err(e) = written {
+        log::warn!("{} not written: {}", filename, e)
+    }
+}
+
+// records at or after since (epoch seconds). unreadable lines are skipped.
+pub fn load(filename: &str, since: u64) -> Vec<Record> {
+    let text = fs::read_to_string(filename).unwrap_or_default();
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| match serde_json::from_str::<Record>(line) {
+            Ok(record) => Some(record),
+            Err(e) => {
+                log::warn!("{} skipped line: {}", filename, e);
+                None
+            }
+        })
+        .filter(|record| record.time >= since)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_append_load() {
+        let filename =
+            std::env::temp_dir().join(format!("yith-history-{}.jsonl", std::process::id()));
+        let filename = filename.to_str().unwrap();
+        let mut old = Record {
+            time: 100,
+            order_id: "old".to_string(),
+            pair: "DAI-ETH".to_string(),
+            aborted: None,
+            predicted_usd: 1.0,
+            realized_usd: Some(0.5),
+            fees_usd: 0.1,
+            gas_usd: 0.2,
+            legs: vec![],
+            balance_change: BTreeMap::new(),
+        };
+        append(filename, &old);
+        old.time = 200;
+        old.order_id = "new".to_string();
+        append(filename, &old);
+        let records = load(filename, 150);
+        assert_eq!(records, vec![old]);
+        let _ = fs::remove_file(filename);
+    }
+}
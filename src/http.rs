@@ -1,13 +1,277 @@
+use crate::eth;
 use crate::geth;
+use crate::log;
+use crate::metrics;
 use crate::{http_error, http_info};
+use once_cell::sync::OnceCell;
 use reqwest::blocking::RequestBuilder;
 use reqwest::{StatusCode, Url};
 use serde::de::DeserializeOwned;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use serde_json;
+use std::collections::HashMap;
+use std::fs;
+use std::sync::{Arc, Mutex};
+
+// set once from the config.yaml cassette section, picked up by every new client
+static CASSETTE: OnceCell<Arc<Cassette>> = OnceCell::new();
 
 pub struct LoggingClient {
     client: reqwest::blocking::Client,
+    mock: Option<Arc<Mock>>,
+    cassette: Option<Arc<Cassette>>,
+}
+
+// canned responses by url, stands in for the network in offline tests
+#[derive(Debug, Default)]
+pub struct Mock {
+    responses: HashMap<String, (u16, String)>,
+}
+
+impl Mock {
+    pub fn new() -> Mock {
+        Mock::default()
+    }
+
+    pub fn respond(mut self, url: &str, status: u16, body: &str) -> Mock {
+        self.responses
+            .insert(url.to_string(), (status, body.to_string()));
+        self
+    }
+
+    fn response(&self, url: &str) -> (u16, String) {
+        match self.responses.get(url) {
+            Some(response) => response.clone(),
+            None => (404, "".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CassetteMode {
+    Record, // save each request and response to the cassette
+    Replay, // answer from the cassette, no network
+}
+
+// the config.yaml cassette section
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CassetteSettings {
+    pub mode: CassetteMode,
+    pub path: String,
+}
+
+// query params and json keys never written to a cassette
+static SECRETS: [&str; 9] = [
+    "apikey",
+    "api_key",
+    "api-key",
+    "secret",
+    "password",
+    "private_key",
+    "privkey",
+    "signature",
+    "signatures",
+];
+// json keys that change every run, left out of the body hash
+static VOLATILE: [&str; 3] = ["timestamp", "nonce", "id"];
+static REDACTED: &str = "REDACTED";
+
+// one request and its response. requests match on method, url and body_hash.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Interaction {
+    pub method: String,
+    pub url: String,
+    pub body_hash: String,
+    pub body: Option<String>,
+    pub status: u16,
+    pub response: String,
+}
+
+struct Tape {
+    interactions: Vec<Interaction>,
+    served: Vec<bool>,
+}
+
+// request/response pairs kept in a json file, replayed in place of the
+// exchange api
+pub struct Cassette {
+    mode: CassetteMode,
+    path: String,
+    tape: Mutex<Tape>,
+}
+
+impl Cassette {
+    // a missing file is an empty cassette
+    pub fn open(settings: &CassetteSettings) -> Result<Cassette, Box<dyn std::error::Error>> {
+        let interactions = match fs::read_to_string(&settings.path) {
+            Ok(json) => serde_json::from_str::<Vec<Interaction>>(&json)?,
+            Err(_e) => vec![],
+        };
+        let served = vec![false; interactions.len()];
+        Ok(Cassette {
+            mode: settings.mode,
+            path: settings.path.clone(),
+            tape: Mutex::new(Tape {
+                interactions,
+                served,
+            }),
+        })
+    }
+
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+
+    pub fn len(&self) -> usize {
+        self.tape.lock().unwrap().interactions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn record(&self, verb: &Verb, url: &str, body: Option<&str>, status: u16, response: &str) {
+        if self.mode != CassetteMode::Record {
+            return;
+        }
+        let interaction = Interaction {
+            method: verb.as_str().to_string(),
+            url: redact_url(url),
+            body_hash: body_hash(body),
+            body: body.map(redact_json),
+            status,
+            response: redact_json(response),
+        };
+        let mut tape = self.tape.lock().unwrap();
+        tape.interactions.push(interaction);
+        tape.served.push(true);
+        let json = serde_json::to_string_pretty(&tape.interactions).unwrap();
+        if let Err(e) = fs::write(&self.path, json) {
+            log::warn!("cassette {} not written: {}", self.path, e)
+        }
+    }
+
+    // the first unserved match, then the last match again for repeat calls
+    pub fn replay(&self, verb: &Verb, url: &str, body: Option<&str>) -> (u16, String) {
+        let method = verb.as_str();
+        let url = redact_url(url);
+        let hash = body_hash(body);
+        let mut tape = self.tape.lock().unwrap();
+        let matches: Vec<usize> = tape
+            .interactions
+            .iter()
+            .enumerate()
+            .filter(|(_idx, i)| i.method == method && i.url == url && i.body_hash == hash)
+            .map(|(idx, _i)| idx)
+            .collect();
+        let found = matches
+            .iter()
+            .find(|idx| !tape.served[**idx])
+            .or(matches.last())
+            .copied();
+        match found {
+            Some(idx) => {
+                tape.served[idx] = true;
+                let interaction = &tape.interactions[idx];
+                (interaction.status, interaction.response.clone())
+            }
+            None => {
+                log::warn!(
+                    "cassette {} has no {} {} body_hash {}",
+                    self.path,
+                    method,
+                    url,
+                    hash
+                );
+                (404, "".to_string())
+            }
+        }
+    }
+}
+
+// every client made after this records or replays through the cassette
+pub fn use_cassette(settings: &CassetteSettings) -> Result<(), Box<dyn std::error::Error>> {
+    let cassette = Cassette::open(settings)?;
+    log::info!(
+        "cassette {:?} {} ({} interactions)",
+        cassette.mode,
+        cassette.path,
+        cassette.len()
+    );
+    CASSETTE
+        .set(Arc::new(cassette))
+        .map_err(|_c| "cassette already set".into())
+}
+
+fn is_secret(name: &str) -> bool {
+    SECRETS.contains(&name.to_lowercase().as_str())
+}
+
+pub fn redact_url(url_str: &str) -> String {
+    match Url::parse(url_str) {
+        Ok(mut url) if url.query().is_some() => {
+            let pairs: Vec<(String, String)> = url
+                .query_pairs()
+                .map(|(name, value)| {
+                    let value = if is_secret(&name) {
+                        REDACTED.to_string()
+                    } else {
+                        value.to_string()
+                    };
+                    (name.to_string(), value)
+                })
+                .collect();
+            url.query_pairs_mut().clear().extend_pairs(pairs);
+            url.to_string()
+        }
+        _ => url_str.to_string(),
+    }
+}
+
+fn redact_value(value: &mut serde_json::Value, drop: &[&str]) {
+    match value {
+        serde_json::Value::Object(map) => {
+            map.retain(|key, _v| !drop.contains(&key.as_str()));
+            for (key, v) in map.iter_mut() {
+                if is_secret(key) {
+                    *v = serde_json::Value::String(REDACTED.to_string())
+                } else {
+                    redact_value(v, drop)
+                }
+            }
+        }
+        serde_json::Value::Array(values) => values.iter_mut().for_each(|v| redact_value(v, drop)),
+        _ => (),
+    }
+}
+
+// secrets replaced. a body that is not json is kept as is.
+pub fn redact_json(json: &str) -> String {
+    match serde_json::from_str::<serde_json::Value>(json) {
+        Ok(mut value) => {
+            redact_value(&mut value, &[]);
+            value.to_string()
+        }
+        Err(_e) => json.to_string(),
+    }
+}
+
+// keccak of the redacted body without its volatile keys
+pub fn body_hash(body: Option<&str>) -> String {
+    match body {
+        Some(json) => {
+            let stable = match serde_json::from_str::<serde_json::Value>(json) {
+                Ok(mut value) => {
+                    redact_value(&mut value, &VOLATILE);
+                    value.to_string()
+                }
+                Err(_e) => json.to_string(),
+            };
+            eth::hex(&eth::hash_msg(&stable.into_bytes())[..8])
+        }
+        None => "".to_string(),
+    }
 }
 
 #[derive(Debug)]
@@ -16,9 +280,39 @@ pub enum Verb {
     Post,
 }
 
+impl Verb {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Verb::Get => "GET",
+            Verb::Post => "POST",
+        }
+    }
+}
+
 impl LoggingClient {
     pub fn new(client: reqwest::blocking::Client) -> LoggingClient {
-        LoggingClient { client: client }
+        LoggingClient {
+            client,
+            mock: None,
+            cassette: CASSETTE.get().cloned(),
+        }
+    }
+
+    pub fn mocked(mock: Mock) -> LoggingClient {
+        LoggingClient {
+            client: reqwest::blocking::Client::new(),
+            mock: Some(Arc::new(mock)),
+            cassette: None,
+        }
+    }
+
+    // replays or records through this cassette instead of the global one
+    pub fn cassette(cassette: Cassette) -> LoggingClient {
+        LoggingClient {
+            client: reqwest::blocking::Client::new(),
+            mock: None,
+            cassette: Some(Arc::new(cassette)),
+        }
     }
 
     pub fn get(&self, url: &str) -> LoggingBuilder {
@@ -32,7 +326,8 @@ impl LoggingClient {
     pub fn method(&self, verb: Verb, url_str: &str) -> LoggingBuilder {
         let id = geth::gen_id();
         let url = Url::parse(url_str).unwrap();
-        println!(
+        let _request_id = log::scoped_field(log::REQUEST_ID, &id);
+        log::info!(
             "[{}] {:?} {} {}",
             id,
             verb,
@@ -49,6 +344,8 @@ impl LoggingClient {
             url: url_str.to_string(),
             json: None,
             builder: builder,
+            mock: self.mock.clone(),
+            cassette: self.cassette.clone(),
         }
     }
 }
@@ -59,6 +356,8 @@ pub struct LoggingBuilder {
     url: String,
     json: Option<String>,
     builder: RequestBuilder,
+    mock: Option<Arc<Mock>>,
+    cassette: Option<Arc<Cassette>>,
 }
 
 impl LoggingBuilder {
@@ -70,6 +369,8 @@ impl LoggingBuilder {
             url: self.url,
             json: self.json,
             builder: builder,
+            mock: self.mock,
+            cassette: self.cassette,
         }
     }
     pub fn json<T: Serialize + ?Sized>(self, object: &T) -> LoggingBuilder {
@@ -81,19 +382,54 @@ impl LoggingBuilder {
             url: self.url,
             json: Some(json),
             builder: builder,
+            mock: self.mock,
+            cassette: self.cassette,
         }
     }
     pub fn send(self) -> reqwest::Result<LoggingResponse> {
+        let _request_id = log::scoped_field(log::REQUEST_ID, &self.id);
         http_info!("[{}] {:?} {}", self.id, self.verb, self.url);
-        if self.json.is_some() {
-            http_info!("[{}] {} ", self.id, self.json.unwrap());
+        if let Some(json) = &self.json {
+            http_info!("[{}] {} ", self.id, json);
+        }
+        if let Some(mock) = self.mock {
+            let (code, text) = mock.response(&self.url);
+            let status = StatusCode::from_u16(code).unwrap();
+            http_info!("[{}] {} {} (mock)", self.id, status, text);
+            return Ok(LoggingResponse {
+                url: self.url,
+                status,
+                text,
+            });
         }
+        if let Some(cassette) = &self.cassette
+            && cassette.mode() == CassetteMode::Replay
+        {
+            let (code, text) = cassette.replay(&self.verb, &self.url, self.json.as_deref());
+            let status = StatusCode::from_u16(code).unwrap();
+            http_info!("[{}] {} {} (cassette)", self.id, status, text);
+            return Ok(LoggingResponse {
+                url: self.url,
+                status,
+                text,
+            });
+        }
+        let host = Url::parse(&self.url)
+            .ok()
+            .and_then(|url| url.host_str().map(|host| host.to_string()))
+            .unwrap_or_default();
+        let started = std::time::Instant::now();
         let resp = self.builder.send();
         match resp {
             Ok(r) => {
                 let status = r.status();
                 let text = r.text().unwrap();
+                metrics::http_request(&host, status.as_str(), started.elapsed());
                 http_info!("[{}] {} {}", self.id, status, text);
+                if let Some(cassette) = &self.cassette {
+                    let body = self.json.as_deref();
+                    cassette.record(&self.verb, &self.url, body, status.as_u16(), &text)
+                }
                 Ok(LoggingResponse {
                     url: self.url,
                     status: status,
@@ -101,6 +437,7 @@ impl LoggingBuilder {
                 })
             }
             Err(e) => {
+                metrics::http_request(&host, "error", started.elapsed());
                 http_error!("[{}] {:?}", self.id, e);
                 Err(e)
             }
@@ -131,3 +468,59 @@ impl LoggingResponse {
         &self.url
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact() {
+        let url = "https://api.etherscan.io/api?module=account&address=0xabc&apikey=K3Y";
+        assert_eq!(
+            redact_url(url),
+            "https://api.etherscan.io/api?module=account&address=0xabc&apikey=REDACTED"
+        );
+        let body = r#"{"pair":"DAI_ETH","signature":"0x5ig","timestamp":1}"#;
+        assert_eq!(
+            redact_json(body),
+            r#"{"pair":"DAI_ETH","signature":"REDACTED","timestamp":1}"#
+        );
+        let later = r#"{"pair":"DAI_ETH","signature":"0xother","timestamp":2}"#;
+        assert_eq!(body_hash(Some(body)), body_hash(Some(later)));
+        assert_ne!(
+            body_hash(Some(body)),
+            body_hash(Some(r#"{"pair":"SWTH_ETH"}"#))
+        );
+    }
+
+    #[test]
+    fn test_cassette_record_replay() {
+        let path = std::env::temp_dir().join(format!("yith-cassette-{}.json", std::process::id()));
+        let settings = CassetteSettings {
+            mode: CassetteMode::Record,
+            path: path.to_str().unwrap().to_string(),
+        };
+        let url = "https://api.example.com/orders?apikey=K3Y";
+        let body = r#"{"pair":"DAI_ETH","timestamp":1}"#;
+        let recorder = Cassette::open(&settings).unwrap();
+        recorder.record(&Verb::Post, url, Some(body), 200, r#"{"id":"first"}"#);
+        recorder.record(&Verb::Post, url, Some(body), 200, r#"{"id":"second"}"#);
+        assert!(!fs::read_to_string(&path).unwrap().contains("K3Y"));
+
+        let replay = CassetteSettings {
+            mode: CassetteMode::Replay,
+            ..settings
+        };
+        let client = LoggingClient::cassette(Cassette::open(&replay).unwrap());
+        let later = serde_json::json!({"pair": "DAI_ETH", "timestamp": 2});
+        let first = client.post(url).json(&later).send().unwrap();
+        assert_eq!(first.text().unwrap(), r#"{"id":"first"}"#);
+        let second = client.post(url).json(&later).send().unwrap();
+        assert_eq!(second.text().unwrap(), r#"{"id":"second"}"#);
+        let again = client.post(url).json(&later).send().unwrap();
+        assert_eq!(again.text().unwrap(), r#"{"id":"second"}"#);
+        let missing = client.get(url).send().unwrap();
+        assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+        let _ = fs::remove_file(path);
+    }
+}
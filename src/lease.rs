@@ -0,0 +1,210 @@
+use crate::redis::Error;
+use crate::time;
+use rand::Rng;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+pub static KEY: &str = "inplay:lock";
+pub static DEFAULT_TTL_MS: u64 = 30000;
+
+// extend or delete the lock only while it still holds our token
+static RENEW: &str = "if redis.call('get', KEYS[1]) == ARGV[1] then return redis.call('pexpire', KEYS[1], ARGV[2]) else return 0 end";
+static RELEASE: &str = "if redis.call('get', KEYS[1]) == ARGV[1] then return redis.call('del', KEYS[1]) else return 0 end";
+
+// what this process knows about its lease. checked before every submit so
+// an instance that lost the lease stops trading even if redis is unreachable.
+#[derive(Debug)]
+pub struct Fence {
+    pub token: String,
+    pub deadline: Instant, // expiry as of the last successful renewal
+    pub lost: bool,        // redis holds another owner's token
+}
+
+impl Fence {
+    pub fn check(&self, now: Instant) -> Option<String> {
+        if self.lost {
+            Some(format!("lease {} taken by another instance", self.token))
+        } else if now >= self.deadline {
+            Some(format!("lease {} expired without renewal", self.token))
+        } else {
+            None
+        }
+    }
+}
+
+static FENCE: Mutex<Option<Fence>> = Mutex::new(None);
+
+// Some(reason) once the lease held by this process can no longer be trusted.
+// processes that never took a lease (file runs, trade) are not fenced.
+pub fn fenced() -> Option<String> {
+    FENCE
+        .lock()
+        .unwrap()
+        .as_ref()
+        .and_then(|fence| fence.check(time::now()))
+}
+
+// whether this process holds a lease it can still trust
+pub fn holding() -> bool {
+    FENCE.lock().unwrap().is_some() && fenced().is_none()
+}
+
+#[derive(Debug)]
+pub struct LeaseError {
+    pub msg: String,
+}
+
+impl std::error::Error for LeaseError {}
+
+impl fmt::Display for LeaseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+pub struct Lease {
+    pub token: String,
+    url: String,
+    stop: Arc<AtomicBool>,
+    renewer: Option<thread::JoinHandle<()>>,
+}
+
+impl Lease {
+    // SET NX PX with a fresh owner token. None when another instance holds it.
+    pub fn acquire(url: &str, ttl_ms: u64) -> Result<Option<Lease>, Error> {
+        let token = format!(
+            "{}-{:016x}",
+            std::process::id(),
+            rand::thread_rng().r#gen::<u64>()
+        );
+        let mut con = connect(url)?;
+        let asked = time::now();
+        let set: Option<String> = redis::cmd("SET")
+            .arg(KEY)
+            .arg(&token)
+            .arg("NX")
+            .arg("PX")
+            .arg(ttl_ms)
+            .query(&mut con)?;
+        if set.is_none() {
+            return Ok(None);
+        }
+        *FENCE.lock().unwrap() = Some(Fence {
+            token: token.clone(),
+            deadline: asked + Duration::from_millis(ttl_ms),
+            lost: false,
+        });
+        let stop = Arc::new(AtomicBool::new(false));
+        let renewer = {
+            let (url, token, stop) = (url.to_string(), token.clone(), stop.clone());
+            thread::spawn(move || renew(con, &url, &token, ttl_ms, &stop))
+        };
+        Ok(Some(Lease {
+            token,
+            url: url.to_string(),
+            stop,
+            renewer: Some(renewer),
+        }))
+    }
+
+    pub fn held(&self) -> bool {
+        fenced().is_none()
+    }
+}
+
+// give the lock up at once so a standby does not wait out the ttl
+impl Drop for Lease {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(renewer) = self.renewer.take() {
+            let _ = renewer.join();
+        }
+        let released = connect(&self.url).and_then(|mut con| {
+            redis::Script::new(RELEASE)
+                .key(KEY)
+                .arg(&self.token)
+                .invoke::<i32>(&mut con)
+        });
+        if let Err(e) = released {
+            log::warn!("lease {} not released: {}", self.token, e)
+        }
+        *FENCE.lock().unwrap() = None;
+    }
+}
+
+fn connect(url: &str) -> Result<redis::Connection, Error> {
+    redis::Client::open(url)?.get_connection()
+}
+
+// renew every third of the ttl. a failed renewal leaves the deadline alone,
+// so the fence closes when the lease would have expired in redis.
+fn renew(mut con: redis::Connection, url: &str, token: &str, ttl_ms: u64, stop: &AtomicBool) {
+    let script = redis::Script::new(RENEW);
+    let interval = ttl_ms / 3;
+    loop {
+        let mut waited = 0;
+        while waited < interval {
+            if stop.load(Ordering::SeqCst) {
+                return;
+            }
+            time::sleep(100.min(interval));
+            waited += 100.min(interval);
+        }
+        let asked = time::now();
+        match script
+            .key(KEY)
+            .arg(token)
+            .arg(ttl_ms)
+            .invoke::<i32>(&mut con)
+        {
+            Ok(1) => {
+                if let Some(fence) = FENCE.lock().unwrap().as_mut() {
+                    fence.deadline = asked + Duration::from_millis(ttl_ms)
+                }
+            }
+            Ok(_) => {
+                log::warn!("lease {} lost", token);
+                if let Some(fence) = FENCE.lock().unwrap().as_mut() {
+                    fence.lost = true
+                }
+                return;
+            }
+            Err(e) => {
+                log::warn!("lease {} not renewed: {}", token, e);
+                if let Ok(fresh) = connect(url) {
+                    con = fresh
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fence_check() {
+        let now = time::now();
+        let mut fence = Fence {
+            token: "1-ab".to_string(),
+            deadline: now + Duration::from_millis(30000),
+            lost: false,
+        };
+        assert_eq!(fence.check(now), None);
+        assert_eq!(
+            fence.check(now + Duration::from_millis(30000)),
+            Some("lease 1-ab expired without renewal".to_string())
+        );
+        fence.lost = true;
+        assert_eq!(
+            fence.check(now),
+            Some("lease 1-ab taken by another instance".to_string())
+        );
+        assert_eq!(fenced(), None); // no lease taken in this process
+    }
+}
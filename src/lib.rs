@@ -1,4 +1,6 @@
+pub mod breaker;
 pub mod config;
+pub mod control;
 pub mod email;
 pub mod erc20;
 pub mod errors;
@@ -6,12 +8,27 @@ pub mod eth;
 pub mod etherscan;
 pub mod exchange;
 pub mod exchanges;
+pub mod fees;
+pub mod gas;
 pub mod geth;
+pub mod history;
 pub mod http;
+pub mod lease;
+pub mod lifecycle;
+pub mod limits;
 pub mod log;
+pub mod metrics;
+pub mod notify;
 pub mod price;
+pub mod recheck;
 pub mod redis;
+pub mod refresh;
+pub mod report;
+pub mod shutdown;
 pub mod time;
+pub mod tokens;
 pub mod types;
+pub mod unwind;
+pub mod validate;
 pub mod wallet;
 pub mod weth;
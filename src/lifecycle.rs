@@ -0,0 +1,223 @@
+use crate::time;
+use serde::Serialize;
+use std::fmt;
+
+// the stages of one arb execution, in the order a full run passes them
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub enum State {
+    Received,
+    Validated,
+    AskBuilt,
+    AskSubmitted,
+    AskFilled,
+    Rebalanced,
+    BidBuilt,
+    BidSubmitted,
+    BidFilled,
+    Swept,
+    Done,
+    Aborted,
+    Unwinding,
+    Unwound, // the bid leg never ran and the ask fills were sold off
+}
+
+static SEQUENCE: [State; 11] = [
+    State::Received,
+    State::Validated,
+    State::AskBuilt,
+    State::AskSubmitted,
+    State::AskFilled,
+    State::Rebalanced,
+    State::BidBuilt,
+    State::BidSubmitted,
+    State::BidFilled,
+    State::Swept,
+    State::Done,
+];
+
+impl State {
+    fn position(&self) -> Option<usize> {
+        SEQUENCE.iter().position(|s| s == self)
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, State::Done | State::Aborted | State::Unwound)
+    }
+
+    // forward along the sequence (a one sided order skips a leg), abort from
+    // anywhere, and unwind once asks may have filled. an unwind ends unwound
+    // when the position was flattened, aborted when it was not
+    pub fn can_move_to(&self, to: State) -> bool {
+        if self.is_terminal() {
+            return false;
+        }
+        match (self, to) {
+            (_, State::Aborted) => true,
+            (State::Unwinding, to) => to == State::Unwound,
+            (from, State::Unwinding) => {
+                from.position() >= State::AskSubmitted.position() && *from != State::Swept
+            }
+            (from, to) => from.position() < to.position(),
+        }
+    }
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Event {
+    pub order_id: String,
+    pub from: State,
+    pub to: State,
+    pub detail: String,
+    pub time: String,
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "order {} {} -> {} {}",
+            self.order_id, self.from, self.to, self.detail
+        )
+    }
+}
+
+// hook for anything that follows a run: logs, email, redis, metrics
+pub trait Subscriber {
+    fn notify(&mut self, event: &Event);
+}
+
+// writes each transition to the log4rs log
+pub struct LogSubscriber {}
+
+impl Subscriber for LogSubscriber {
+    fn notify(&mut self, event: &Event) {
+        log::info!("{}", event);
+    }
+}
+
+#[derive(Debug)]
+pub struct LifecycleError {
+    pub msg: String,
+}
+
+impl std::error::Error for LifecycleError {}
+
+impl fmt::Display for LifecycleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+pub struct Lifecycle {
+    pub order_id: String,
+    state: State,
+    subscribers: Vec<Box<dyn Subscriber>>,
+    pub events: Vec<Event>,
+}
+
+impl Lifecycle {
+    pub fn new(order_id: &str) -> Lifecycle {
+        Lifecycle {
+            order_id: order_id.to_string(),
+            state: State::Received,
+            subscribers: vec![],
+            events: vec![],
+        }
+    }
+
+    pub fn subscribe(&mut self, subscriber: Box<dyn Subscriber>) {
+        self.subscribers.push(subscriber)
+    }
+
+    pub fn state(&self) -> State {
+        self.state
+    }
+
+    pub fn to(&mut self, to: State, detail: &str) -> Result<(), LifecycleError> {
+        if !self.state.can_move_to(to) {
+            return Err(LifecycleError {
+                msg: format!(
+                    "order {} cannot move {} -> {}",
+                    self.order_id, self.state, to
+                ),
+            });
+        }
+        let event = Event {
+            order_id: self.order_id.clone(),
+            from: self.state,
+            to,
+            detail: detail.to_string(),
+            time: time::now_string(),
+        };
+        self.state = to;
+        for subscriber in self.subscribers.iter_mut() {
+            subscriber.notify(&event)
+        }
+        self.events.push(event);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    struct Recorder {
+        seen: Arc<Mutex<Vec<State>>>,
+    }
+
+    impl Subscriber for Recorder {
+        fn notify(&mut self, event: &Event) {
+            self.seen.lock().unwrap().push(event.to)
+        }
+    }
+
+    #[test]
+    fn test_transitions() {
+        let seen = Arc::new(Mutex::new(vec![]));
+        let mut lifecycle = Lifecycle::new("abc");
+        lifecycle.subscribe(Box::new(Recorder { seen: seen.clone() }));
+        lifecycle.to(State::Validated, "").unwrap();
+        lifecycle.to(State::AskBuilt, "").unwrap();
+        assert!(lifecycle.to(State::Unwinding, "").is_err()); // nothing bought yet
+        lifecycle.to(State::AskSubmitted, "").unwrap();
+        lifecycle.to(State::AskFilled, "").unwrap();
+        assert!(lifecycle.to(State::AskBuilt, "").is_err()); // no going back
+        lifecycle.to(State::Unwinding, "bid leg failed").unwrap();
+        assert!(lifecycle.to(State::BidSubmitted, "").is_err());
+        lifecycle.to(State::Aborted, "").unwrap();
+        assert!(lifecycle.to(State::Done, "").is_err());
+        assert_eq!(lifecycle.state(), State::Aborted);
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                State::Validated,
+                State::AskBuilt,
+                State::AskSubmitted,
+                State::AskFilled,
+                State::Unwinding,
+                State::Aborted
+            ]
+        );
+        assert_eq!(lifecycle.events.len(), 6);
+    }
+
+    #[test]
+    fn test_unwound() {
+        let mut lifecycle = Lifecycle::new("abc");
+        assert!(lifecycle.to(State::Unwound, "").is_err()); // only from unwinding
+        lifecycle.to(State::AskSubmitted, "").unwrap();
+        lifecycle.to(State::Unwinding, "bid leg failed").unwrap();
+        assert!(lifecycle.to(State::Done, "").is_err());
+        lifecycle.to(State::Unwound, "sold back").unwrap();
+        assert!(lifecycle.state().is_terminal());
+        assert!(lifecycle.to(State::Aborted, "").is_err());
+    }
+}
@@ -0,0 +1,375 @@
+use crate::time;
+use crate::types;
+use crate::wallet;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+
+pub static VOLUME_FILENAME: &str = "volume.yaml";
+
+// risk limits from the wallet.yaml limits section. USD caps are converted
+// to sell token units using the order's quote_usd.
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Limits {
+    pub order_usd: Option<f64>,   // max notional per order
+    pub resting_usd: Option<f64>, // max held on any one exchange
+    pub daily_usd: Option<f64>,   // max daily volume across exchanges
+    #[serde(default)]
+    pub exchanges: HashMap<String, ExchangeLimits>,
+    #[serde(default)]
+    pub pairs: HashMap<String, f64>, // BASE-QUOTE -> max base qty per order
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct ExchangeLimits {
+    pub order_usd: Option<f64>,
+    pub resting_usd: Option<f64>,
+    pub daily_usd: Option<f64>,
+    #[serde(default)]
+    pub tokens: HashMap<String, f64>, // per exchange version of the "limit" coins
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Cap {
+    pub name: String,
+    pub amount: f64, // in sell token units
+}
+
+impl fmt::Display for Cap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {:0.5}", self.name, self.amount)
+    }
+}
+
+// usd prices for the two tokens of an order
+#[derive(Debug)]
+pub struct Pricing {
+    pub base: String,
+    pub quote: String,
+    pub base_usd: f64,
+    pub quote_usd: f64,
+}
+
+impl Pricing {
+    pub fn build(order: &types::Order) -> Pricing {
+        let best = order
+            .ask_books
+            .books
+            .iter()
+            .chain(order.bid_books.books.iter())
+            .find_map(|book| book.offers.first());
+        let base_quote = best.map(|offer| offer.quote).unwrap_or(0.0);
+        Pricing {
+            base: order.pair.base.to_uppercase(),
+            quote: order.pair.quote.to_uppercase(),
+            base_usd: base_quote * order.quote_usd,
+            quote_usd: order.quote_usd,
+        }
+    }
+
+    pub fn usd(&self, symbol: &str) -> Option<f64> {
+        let symbol = symbol.to_uppercase();
+        let price = if symbol == self.quote {
+            self.quote_usd
+        } else if symbol == self.base {
+            self.base_usd
+        } else {
+            return None;
+        };
+        if price > 0.0 { Some(price) } else { None }
+    }
+
+    // the other token of the pair
+    pub fn other(&self, symbol: &str) -> &str {
+        if symbol.to_uppercase() == self.base {
+            &self.quote
+        } else {
+            &self.base
+        }
+    }
+}
+
+// usd traded per exchange for one day, kept in volume.yaml
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct Volume {
+    pub date: String,
+    pub exchanges: HashMap<String, f64>,
+}
+
+impl Volume {
+    pub fn load(filename: &str) -> Volume {
+        let today = time::today_string();
+        match fs::read_to_string(filename) {
+            Ok(yaml) => match serde_yaml::from_str::<Volume>(&yaml) {
+                Ok(volume) if volume.date == today => volume,
+                Ok(_) => Volume::new(today),
+                Err(e) => panic!("{} {}", filename, e),
+            },
+            Err(_) => Volume::new(today),
+        }
+    }
+
+    pub fn new(date: String) -> Volume {
+        Volume {
+            date,
+            exchanges: HashMap::new(),
+        }
+    }
+
+    pub fn save(&self, filename: &str) -> Result<(), Box<dyn std::error::Error>> {
+        fs::write(filename, serde_yaml::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn add(&mut self, exchange_name: &str, usd: f64) {
+        *self
+            .exchanges
+            .entry(exchange_name.to_string())
+            .or_insert(0.0) += usd;
+    }
+
+    pub fn get(&self, exchange_name: &str) -> f64 {
+        *self.exchanges.get(exchange_name).unwrap_or(&0.0)
+    }
+
+    pub fn total(&self) -> f64 {
+        self.exchanges.values().sum()
+    }
+}
+
+// pricing and volume shared by every offer in a run
+#[derive(Debug)]
+pub struct Exposure {
+    pub pricing: Pricing,
+    pub volume: Volume,
+}
+
+impl Exposure {
+    pub fn build(order: &types::Order) -> Exposure {
+        Exposure {
+            pricing: Pricing::build(order),
+            volume: Volume::load(VOLUME_FILENAME),
+        }
+    }
+
+    pub fn record(&mut self, exchange_name: &str, sell_symbol: &str, amount: f64) {
+        match self.pricing.usd(sell_symbol) {
+            Some(price) => {
+                self.volume.add(exchange_name, amount * price);
+                if let Err(e) = self.volume.save(VOLUME_FILENAME) {
+                    log::warn!("{} not saved: {}", VOLUME_FILENAME, e)
+                }
+            }
+            None => log::warn!("no usd price for {}. volume not recorded", sell_symbol),
+        }
+    }
+}
+
+// usd value of coins held on an exchange, for the tokens we can price
+pub fn resting_usd(wallet: &wallet::Wallet, exchange_name: &str, pricing: &Pricing) -> f64 {
+    wallet
+        .coins
+        .iter()
+        .filter(|c| c.source == exchange_name)
+        .filter_map(|c| pricing.usd(&c.ticker_symbol).map(|p| p * c.base_total()))
+        .sum()
+}
+
+// every configured cap on an offer, in sell token units
+pub fn caps(
+    wallet: &wallet::Wallet,
+    exchange_name: &str,
+    market_id: &str,
+    askbid: types::AskBid,
+    sell_symbol: &str,
+    price: f64,
+    exposure: &Exposure,
+) -> Vec<Cap> {
+    let limits = &wallet.limits;
+    let mut caps = vec![];
+    let exchange_limits = limits.exchanges.get(exchange_name);
+
+    if let Some(amount) = exchange_limits.and_then(|e| e.tokens.get(sell_symbol)) {
+        caps.push(Cap {
+            name: format!("{} {} limit", exchange_name, sell_symbol),
+            amount: *amount,
+        });
+    }
+
+    if let Some(max_base) = limits.pairs.get(market_id) {
+        let amount = match askbid {
+            types::AskBid::Ask => max_base * price,
+            types::AskBid::Bid => *max_base,
+        };
+        caps.push(Cap {
+            name: format!("{} pair limit", market_id),
+            amount,
+        });
+    }
+
+    let mut usd_caps = vec![];
+    let order_usd = least(limits.order_usd, exchange_limits.and_then(|e| e.order_usd));
+    if let Some(usd) = order_usd {
+        usd_caps.push(("order_usd".to_string(), usd));
+    }
+    let resting = least(
+        limits.resting_usd,
+        exchange_limits.and_then(|e| e.resting_usd),
+    );
+    if let Some(usd) = resting {
+        let held = resting_usd(wallet, exchange_name, &exposure.pricing);
+        usd_caps.push((format!("{} resting_usd", exchange_name), usd - held));
+    }
+    if let Some(usd) = limits.daily_usd {
+        usd_caps.push(("daily_usd".to_string(), usd - exposure.volume.total()));
+    }
+    if let Some(usd) = exchange_limits.and_then(|e| e.daily_usd) {
+        usd_caps.push((
+            format!("{} daily_usd", exchange_name),
+            usd - exposure.volume.get(exchange_name),
+        ));
+    }
+    if !usd_caps.is_empty() {
+        match exposure.pricing.usd(sell_symbol) {
+            Some(sell_usd) => {
+                for (name, usd) in usd_caps {
+                    caps.push(Cap {
+                        name,
+                        amount: (usd / sell_usd).max(0.0),
+                    })
+                }
+            }
+            None => caps.push(Cap {
+                name: format!("no usd price for {}", sell_symbol),
+                amount: 0.0,
+            }),
+        }
+    }
+    caps
+}
+
+fn least(a: Option<f64>, b: Option<f64>) -> Option<f64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exposure() -> Exposure {
+        Exposure {
+            pricing: Pricing {
+                base: "DAI".to_string(),
+                quote: "ETH".to_string(),
+                base_usd: 1.0,
+                quote_usd: 200.0,
+            },
+            volume: Volume::new("2020-01-01".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_caps() {
+        let yaml = "
+order_usd: 100
+daily_usd: 1000
+exchanges:
+  idex:
+    order_usd: 50
+    tokens:
+      ETH: 2.0
+pairs:
+  DAI-ETH: 20
+";
+        let wallet = wallet::Wallet {
+            coins: vec![],
+            limits: serde_yaml::from_str(yaml).unwrap(),
+        };
+        let mut exposure = exposure();
+        exposure.volume.add("switcheo", 990.0);
+        let caps = caps(
+            &wallet,
+            "idex",
+            "DAI-ETH",
+            types::AskBid::Ask,
+            "ETH",
+            0.25,
+            &exposure,
+        );
+        assert_eq!(
+            caps,
+            vec![
+                Cap {
+                    name: "idex ETH limit".to_string(),
+                    amount: 2.0
+                },
+                Cap {
+                    name: "DAI-ETH pair limit".to_string(),
+                    amount: 5.0
+                },
+                Cap {
+                    name: "order_usd".to_string(),
+                    amount: 0.25
+                },
+                Cap {
+                    name: "daily_usd".to_string(),
+                    amount: 0.05
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_caps_unpriced() {
+        let wallet = wallet::Wallet {
+            coins: vec![wallet::WalletCoin::build("ETH", "none", "idex", 1.0)],
+            limits: Limits {
+                order_usd: Some(100.0),
+                resting_usd: Some(300.0),
+                ..Limits::default()
+            },
+        };
+        let caps = caps(
+            &wallet,
+            "idex",
+            "ZRX-ETH",
+            types::AskBid::Bid,
+            "ETH",
+            0.001,
+            &exposure(),
+        );
+        assert_eq!(caps[1].name, "idex resting_usd");
+        assert_eq!(caps[1].amount, 0.5); // 300 cap - 200 held
+        let caps = caps_for_zrx(&wallet);
+        assert_eq!(caps[0].amount, 0.0);
+    }
+
+    fn caps_for_zrx(wallet: &wallet::Wallet) -> Vec<Cap> {
+        caps(
+            wallet,
+            "idex",
+            "ZRX-ETH",
+            types::AskBid::Bid,
+            "ZRX",
+            0.001,
+            &exposure(),
+        )
+    }
+
+    #[test]
+    fn test_volume() {
+        let mut volume = Volume::new("2020-01-01".to_string());
+        volume.add("idex", 10.0);
+        volume.add("idex", 5.0);
+        volume.add("switcheo", 1.0);
+        assert_eq!(volume.get("idex"), 15.0);
+        assert_eq!(volume.total(), 16.0);
+        let volume = Volume::load("notes/missing-volume.yaml");
+        assert_eq!(volume.total(), 0.0);
+    }
+}
@@ -1,5 +1,6 @@
 pub use log::{debug, error, info, trace, warn};
 use log4rs;
+use std::collections::BTreeMap;
 
 #[macro_use]
 mod macros {
@@ -17,23 +18,90 @@ mod macros {
     }
 }
 
-pub fn init() {
-    log4rs::init_file("log4rs.yaml", Default::default()).unwrap();
+// structured fields, carried per thread and written to the json log's mdc
+pub static ARB_ID: &str = "arb_id";
+pub static EXCHANGE: &str = "exchange";
+pub static LEG: &str = "leg";
+pub static REQUEST_ID: &str = "request_id";
+pub static TX: &str = "tx";
+
+pub static FILENAME: &str = "log4rs.yaml";
+
+// console_stderr moves the stdout appender to stderr, keeping stdout for
+// json and csv output
+pub fn init(console_stderr: bool) {
+    let yaml = std::fs::read_to_string(FILENAME).unwrap();
+    let mut value: serde_yaml::Value = serde_yaml::from_str(&yaml).unwrap();
+    if console_stderr {
+        value["appenders"]["stdout"]["target"] = "stderr".into();
+    }
+    let raw: log4rs::file::RawConfig = serde_yaml::from_value(value).unwrap();
+    let (appenders, errors) = raw.appenders_lossy(&Default::default());
+    for e in errors {
+        eprintln!("{} {}", FILENAME, e);
+    }
+    let config = log4rs::config::Config::builder()
+        .appenders(appenders)
+        .loggers(raw.loggers())
+        .build(raw.root())
+        .unwrap();
+    log4rs::init_config(config).unwrap();
+}
+
+pub fn set_field(name: &str, value: &str) {
+    log_mdc::insert(name, value);
 }
 
+// set until the guard drops, then back to any earlier value
+pub fn scoped_field(name: &str, value: &str) -> log_mdc::InsertGuard {
+    log_mdc::insert_scoped(name, value)
+}
+
+// the current thread's fields, to hand to a spawned thread with set_fields
+pub fn fields() -> BTreeMap<String, String> {
+    let mut fields = BTreeMap::new();
+    log_mdc::iter(|name, value| {
+        fields.insert(name.to_string(), value.to_string());
+    });
+    fields
+}
+
+pub fn set_fields(fields: &BTreeMap<String, String>) {
+    for (name, value) in fields {
+        set_field(name, value)
+    }
+}
+
+// a transaction sent to the chain, logged with its hash in the tx field
+pub fn sent_tx(label: &str, hash: &str) {
+    let _tx = scoped_field(TX, hash);
+    log::info!("{} {}", label, hash);
+}
+
+// the lines of one arb, for its report and alerts. each line is logged with
+// the arb_id field set, so the json log holds the structured events.
 pub struct RunLog {
+    arb_id: String,
     lines: Vec<String>,
 }
 
 impl RunLog {
-    pub fn new() -> RunLog {
-        RunLog { lines: Vec::new() }
+    pub fn new(arb_id: &str) -> RunLog {
+        RunLog {
+            arb_id: arb_id.to_string(),
+            lines: Vec::new(),
+        }
     }
 
     pub fn add(&mut self, line: String) {
-        println!("{}", line);
+        let _arb_id = scoped_field(ARB_ID, &self.arb_id);
+        log::info!("{}", line);
         self.lines.push(line);
     }
+
+    pub fn lines(&self) -> Vec<String> {
+        self.lines.clone()
+    }
 }
 
 impl std::fmt::Display for RunLog {
@@ -41,3 +109,18 @@ impl std::fmt::Display for RunLog {
         write!(f, "{}", self.lines.join("\n"))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_run_log_fields() {
+        let mut run_log = RunLog::new("abc");
+        run_log.add("ask leg".to_string());
+        run_log.add("bid leg".to_string());
+        assert_eq!(run_log.lines(), vec!["ask leg", "bid leg"]);
+        assert_eq!(run_log.to_string(), "ask leg\nbid leg");
+        assert_eq!(fields().get(ARB_ID), None); // only set for the line
+    }
+}
@@ -1,30 +1,49 @@
 use clap;
 
+use yith::breaker;
 use yith::config;
+use yith::control;
 use yith::email;
 use yith::erc20;
 use yith::errors;
 use yith::eth;
 use yith::etherscan;
 use yith::exchange;
+use yith::fees;
+use yith::gas;
 use yith::geth;
+use yith::history;
+use yith::http;
+use yith::lease;
+use yith::lifecycle;
+use yith::limits;
 use yith::log;
+use yith::metrics;
+use yith::notify;
+use yith::recheck;
 use yith::redis;
+use yith::refresh;
+use yith::report;
+use yith::shutdown;
 use yith::time;
+use yith::tokens;
 use yith::types;
+use yith::unwind;
+use yith::validate;
 use yith::wallet;
 use yith::weth;
 
+use std::sync::atomic::{AtomicBool, Ordering};
+
 fn main() {
     let options_yaml = clap::load_yaml!("cli.yaml"); // load/parse at compile time
     let options = clap::App::from_yaml(options_yaml).get_matches();
-    log::init();
+    log::init(options.value_of("format") != Some("table"));
 
     let config_filename = options.value_of("config").unwrap_or(config::FILENAME);
     let config: config::Config = config::read_type(config_filename);
 
-    let wallet_filename = "wallet.yaml";
-    let wallet: wallet::Wallet = config::read_type(wallet_filename);
+    let wallet: wallet::Wallet = config::read_type(wallet::FILENAME);
 
     log::info!(
         "Yith {:#?} {} {}",
@@ -33,15 +52,25 @@ fn main() {
         if config.trade_live { "LIVE" } else { "DEMO" }
     );
 
+    if let Some(cassette) = &config.cassette {
+        http::use_cassette(cassette).unwrap_or_else(|e| panic!("cassette {}", e));
+    }
+
     let exchanges_filename = "exchanges.yaml";
     let exchanges = config::hydrate_exchanges(exchanges_filename, &config)
         .unwrap_or_else(|c| panic!("{} {}", exchanges_filename, c));
 
+    let registry = tokens::Registry::load(tokens::FILENAME);
+    config::TOKENS.set(registry).unwrap(); // set-once global
+
     let etherscan = etherscan::Etherscan::new(&config.etherscan_key);
     config::ETHERSCAN.set(etherscan).unwrap(); // set-once global
     config::CONFIG.set(config).unwrap(); // set-once global
 
-    match app(wallet, exchanges, options) {
+    let outcome = app(wallet, exchanges, options);
+    let notify = notify::Settings::from_config(config::CONFIG.get().unwrap());
+    notify::flush(&notify, true); // fills still waiting for a digest
+    match outcome {
         None => {
             std::process::exit(0);
         }
@@ -58,13 +87,18 @@ fn app(
     opts: clap::ArgMatches,
 ) -> Option<Box<dyn std::error::Error>> {
     let config = config::CONFIG.get().unwrap();
+    let format = report::Format::read(opts.value_of("format").unwrap()).unwrap();
 
     if let Some(_matches) = opts.subcommand_matches("balances") {
         scan_wallet(&mut wallet.coins, &exchanges);
-        wallet.print_with_price();
+        match format {
+            report::Format::Table => wallet.print_with_price(),
+            _ => print_report(format, &wallet.report()),
+        }
         None
     } else if let Some(_matches) = opts.subcommand_matches("orders") {
-        show_orders(&exchanges, &config.wallet_private_key);
+        let orders = show_orders(&exchanges, &config.wallet_private_key, format);
+        print_report(format, &orders);
         None
     } else if let Some(matches) = opts.subcommand_matches("weth") {
         let action = matches.value_of("action").unwrap();
@@ -126,6 +160,19 @@ fn app(
             _ => (),
         }
         None
+    } else if let Some(matches) = opts.subcommand_matches("tokens") {
+        let action = matches.value_of("action").unwrap();
+        match action {
+            "check" => tokens_check(),
+            _ => Some(errors::MainError::build_box(format!(
+                "unknown tokens action {}",
+                action
+            ))),
+        }
+    } else if let Some(matches) = opts.subcommand_matches("refresh") {
+        let exchange_name = matches.value_of("exchange");
+        let dryrun = matches.is_present("dryrun");
+        refresh_markets(&exchanges, exchange_name, dryrun)
     } else if let Some(matches) = opts.subcommand_matches("transfer") {
         let direction_str = matches.value_of("direction").unwrap();
         let direction = match exchange::TransferDirection::read(direction_str) {
@@ -143,7 +190,7 @@ fn app(
         let exchange = exchanges.find_by_name(exchange_name).unwrap();
 
         if amount == "sweep" {
-            sweep(&config.wallet_private_key, &exchange, &symbol.into())
+            sweep(&config.wallet_private_key, exchange, &symbol.into()).err()
         } else {
             match run_transfer(
                 &config.wallet_private_key,
@@ -158,44 +205,578 @@ fn app(
         }
     } else if let Some(matches) = opts.subcommand_matches("trade") {
         scan_wallet(&mut wallet.coins, &exchanges);
-        wallet.print_with_price();
-
-        let order = build_manual_order(matches);
-        let run_log = run_order(config, &mut wallet, &order, &exchanges);
-        if let Some(email) = config.email.as_ref() {
-            mail_log(&email, &order, &run_log)
+        if format == report::Format::Table {
+            wallet.print_with_price();
         }
+
+        let order = match build_manual_order(matches) {
+            Ok(order) => order,
+            Err(e) => return Some(e),
+        };
+        metrics::ORDERS_RECEIVED.inc();
+        let (run_log, run_report) =
+            run_order(config, &mut wallet, &order, &exchanges, vec![], None);
+        history::append(
+            history::FILENAME,
+            &history::Record::build(&order, &run_report),
+        );
+        notify_order(config, &order, &run_log, &run_report);
+        print_report(format, &run_report);
         None
     } else if let Some(matches) = opts.subcommand_matches("run") {
-        scan_wallet(&mut wallet.coins, &exchanges);
-        wallet.print_with_price();
-
-        let order = match matches.value_of("arb_file") {
-            Some(filename) => {
-                println!("loading {}", filename);
-                types::Order::from_file(filename.to_string())
+        if matches.is_present("daemon") {
+            run_daemon(config, &mut wallet, &exchanges, format)
+        } else {
+            let from_redis = matches.value_of("arb_file").is_none();
+            match from_redis.then(|| acquire_lease(config)).transpose() {
+                Ok(_lease) => match load_order(config, matches) {
+                    Ok(order) => {
+                        execute_order(config, &mut wallet, &order, &exchanges, format, from_redis);
+                        None
+                    }
+                    Err(e) => Some(e),
+                },
+                Err(e) => Some(e),
+            }
+        }
+    } else if let Some(matches) = opts.subcommand_matches("halt") {
+        let reason = matches.value_of("reason").unwrap_or("manual halt");
+        let mut breaker = breaker::Breaker::load(breaker::FILENAME);
+        breaker.halt(reason);
+        println!("halted: {}", reason);
+        breaker.save_halt(breaker::FILENAME).err()
+    } else if let Some(_matches) = opts.subcommand_matches("resume") {
+        let mut breaker = breaker::Breaker::load(breaker::FILENAME);
+        match &breaker.halted {
+            Some(halt) => println!("resuming from halt: {}", halt),
+            None => println!("not halted"),
+        }
+        breaker.resume();
+        breaker
+            .save(breaker::FILENAME)
+            .and_then(|_| breaker.save_halt(breaker::FILENAME))
+            .err()
+    } else if let Some(matches) = opts.subcommand_matches("plan") {
+        match load_order(config, matches) {
+            Ok(order) => {
+                if let Err(e) = validate_order(&order, &exchanges) {
+                    return Some(Box::new(e));
+                }
+                scan_wallet(&mut wallet.coins, &exchanges);
+                let plan_report = plan_order(config, &wallet, &order, &exchanges);
+                match report::render(format, &plan_report) {
+                    Some(out) => println!("{}", out),
+                    None => println!("{}", plan_report),
+                }
+                None
+            }
+            Err(e) => Some(e),
+        }
+    } else if let Some(matches) = opts.subcommand_matches("report") {
+        let period = report::Period::read(matches.value_of("period").unwrap()).unwrap();
+        let now = time::since_epoch().as_secs();
+        let records = history::load(history::FILENAME, now.saturating_sub(period.secs()));
+        let period_report = report::PeriodReport::build(period, &records, now);
+        if matches.is_present("html") {
+            println!("{}", period_report.html())
+        } else {
+            match report::render(format, &period_report) {
+                Some(out) => println!("{}", out),
+                None => println!("{}", period_report),
             }
+        }
+        if matches.is_present("email") {
+            email_report(config, &period_report)
+        } else {
+            None
+        }
+    } else if let Some(matches) = opts.subcommand_matches("order") {
+        let action = matches.value_of("action").unwrap();
+        let filename = matches.value_of("file").unwrap();
+        match action {
+            "validate" => match types::Order::from_file(filename)
+                .and_then(|order| validate_order(&order, &exchanges).map(|_| order))
+            {
+                Ok(order) => {
+                    println!(
+                        "{} ok. order {} {} {} ask books {} bid books",
+                        filename,
+                        order.id,
+                        order.pair,
+                        order.ask_books.books.len(),
+                        order.bid_books.books.len()
+                    );
+                    let unchecked = validate::unchecked_pairs(&order, &exchanges);
+                    if !unchecked.is_empty() {
+                        println!(
+                            "markets not checked on {}: no pair list",
+                            unchecked.join(", ")
+                        );
+                    }
+                    None
+                }
+                Err(e) => Some(Box::new(e)),
+            },
+            _ => Some(errors::MainError::build_box(format!(
+                "unknown order action {}",
+                action
+            ))),
+        }
+    } else {
+        Some(errors::MainError::build_box(format!(
+            "option not understood"
+        )))
+    }
+}
+
+fn execute_order(
+    config: &config::Config,
+    wallet: &mut wallet::Wallet,
+    order: &types::Order,
+    exchanges: &config::ExchangeList,
+    format: report::Format,
+    from_redis: bool, // write status back to arb:{id}
+) {
+    let _arb_id = log::scoped_field(log::ARB_ID, &order.id);
+    metrics::ORDERS_RECEIVED.inc();
+    let mut status = if from_redis {
+        redis_status(config)
+    } else {
+        None
+    };
+    let mut breaker = breaker::Breaker::load(breaker::FILENAME);
+    if let Some(halt) = &breaker.halted {
+        log::info!(
+            "trading halted: {}. order {} skipped. run yith resume",
+            halt,
+            order.id
+        );
+        reject(&mut status, order, &format!("halted: {}", halt));
+        return;
+    }
+    if let Err(e) = validate_order(order, exchanges) {
+        log::info!("{}", e);
+        reject(&mut status, order, &e.msg);
+        return;
+    }
+    wallet.reset();
+    scan_wallet(&mut wallet.coins, exchanges);
+    let start = wallet.report();
+    if format == report::Format::Table {
+        wallet.print_report(&start);
+    }
+    if let Some(reason) = breaker.check_balance(&config.breaker, start.total) {
+        trip_breaker(config, &breaker, &reason);
+        reject(&mut status, order, &reason);
+        return;
+    }
+
+    let mut subscribers: Vec<Box<dyn lifecycle::Subscriber>> = vec![Box::new(control::Tracker {})];
+    if from_redis && let Some(status) = redis_status(config) {
+        subscribers.push(Box::new(redis::StatusSubscriber { status }))
+    }
+    let max_age = config
+        .max_order_age_secs
+        .unwrap_or(validate::DEFAULT_MAX_ORDER_AGE_SECS);
+    let (run_log, run_report) =
+        run_order(config, wallet, order, exchanges, subscribers, Some(max_age));
+    notify_order(config, order, &run_log, &run_report);
+
+    // final balances
+    wallet.reset();
+    scan_wallet(&mut wallet.coins, exchanges);
+    let end = wallet.report();
+    if format == report::Format::Table {
+        wallet.print_report(&end);
+    }
+    metrics::REALIZED_PNL_USD.add(end.total - start.total);
+    let mut record = history::Record::build(order, &run_report);
+    record.balances(&start, &end);
+    history::append(history::FILENAME, &record);
+
+    // only orders that reached an exchange count. reloaded so a resume
+    // during the run is not overwritten
+    if run_report.submitted() {
+        let mut breaker = breaker::Breaker::load(breaker::FILENAME);
+        let legs: Vec<breaker::LegOutcome> = run_report
+            .legs
+            .iter()
+            .map(|leg| breaker::LegOutcome {
+                exchange: leg.exchange.clone(),
+                filled: leg.filled.len(),
+                failures: leg.failures,
+            })
+            .collect();
+        let tripped = breaker.record_order(
+            &config.breaker,
+            time::since_epoch().as_secs(),
+            run_report.aborted.is_some(),
+            end.total - start.total,
+            &legs,
+            end.total,
+        );
+        match tripped {
+            Some(reason) => trip_breaker(config, &breaker, &reason),
             None => {
-                let mut redis = redis::Redis::new(&config.redis_url);
-                redis.rd_next()
+                if let Err(e) = breaker.save(breaker::FILENAME) {
+                    log::warn!("{} not saved: {}", breaker::FILENAME, e)
+                }
             }
-        };
+        }
+    }
+
+    if let Some(status) = status.as_mut() {
+        write_result(status, &run_report, end.total - start.total)
+    }
+
+    print_report(format, &run_report);
+}
+
+fn validate_order(
+    order: &types::Order,
+    exchanges: &config::ExchangeList,
+) -> Result<(), types::OrderError> {
+    let problems = validate::check(order, exchanges, config::TOKENS.get().unwrap());
+    let unchecked = validate::unchecked_pairs(order, exchanges);
+    if !unchecked.is_empty() {
+        log::info!(
+            "order {} markets not checked on {}: no pair list",
+            order.id,
+            unchecked.join(", ")
+        );
+    }
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(types::OrderError {
+            msg: format!("order {} invalid: {}", order.id, problems.join("; ")),
+        })
+    }
+}
+
+fn redis_status(config: &config::Config) -> Option<redis::Status> {
+    match redis::Status::new(&config.redis_url) {
+        Ok(status) => Some(status),
+        Err(e) => {
+            log::warn!("redis status unavailable: {}", e);
+            None
+        }
+    }
+}
+
+// fills and realized profit for the scanner, next to the lifecycle fields
+fn write_result(status: &mut redis::Status, run_report: &report::RunReport, profit_usd: f64) {
+    let fills: Vec<serde_json::Value> = run_report
+        .legs
+        .iter()
+        .map(|leg| {
+            serde_json::json!({
+                "exchange": leg.exchange,
+                "askbid": leg.askbid.to_string(),
+                "filled": leg.filled,
+            })
+        })
+        .collect();
+    let fields = [
+        ("fills", serde_json::to_string(&fills).unwrap()),
+        ("profit_usd", format!("{:0.2}", profit_usd)),
+    ];
+    let result = serde_json::json!({
+        "order_id": run_report.order_id,
+        "profit_usd": profit_usd,
+        "aborted": run_report.aborted,
+        "unwound": run_report.unwound,
+        "fills": fills,
+    });
+    let written = status
+        .write(&run_report.order_id, &fields)
+        .and_then(|_| status.publish(&result.to_string()));
+    if let Err(e) = written {
+        log::warn!(
+            "redis result for {} not written: {}",
+            run_report.order_id,
+            e
+        )
+    }
+}
+
+// an order turned away before running: counted, kept in history and written
+// back to redis
+fn reject(status: &mut Option<redis::Status>, order: &types::Order, reason: &str) {
+    metrics::aborted(reason);
+    history::append(history::FILENAME, &history::Record::rejected(order, reason));
+    if let Some(status) = status.as_mut() {
+        write_rejected(status, &order.id, reason)
+    }
+}
+
+// an order turned away before it started, so the scanner stops offering it
+fn write_rejected(status: &mut redis::Status, order_id: &str, reason: &str) {
+    let now = time::now_string();
+    let fields = [
+        ("state", lifecycle::State::Aborted.to_string()),
+        ("finished", now),
+        ("error", reason.to_string()),
+    ];
+    let result = serde_json::json!({"order_id": order_id, "aborted": reason});
+    let written = status
+        .write(order_id, &fields)
+        .and_then(|_| status.publish(&result.to_string()));
+    if let Err(e) = written {
+        log::warn!("redis status for {} not written: {}", order_id, e)
+    }
+}
+
+fn trip_breaker(config: &config::Config, breaker: &breaker::Breaker, reason: &str) {
+    let alert = format!("CIRCUIT BREAKER tripped: {}. run yith resume", reason);
+    log::info!("{}", alert);
+    let saved = breaker
+        .save(breaker::FILENAME)
+        .and_then(|_| breaker.save_halt(breaker::FILENAME));
+    if let Err(e) = saved {
+        log::warn!("{} not saved: {}", breaker::FILENAME, e)
+    }
+    let notify = notify::Settings::from_config(config);
+    let message = notify.message(notify::Event::Halt, &[("reason", alert)]);
+    notify::send(&notify, message);
+}
 
-        let run_log = run_order(config, &mut wallet, &order, &exchanges);
-        if let Some(email) = &config.email {
-            mail_log(&email, &order, &run_log)
+// run orders from redis until a signal arrives, reusing the loaded exchanges.
+// the control api, when configured, answers from its own thread.
+fn run_daemon(
+    config: &config::Config,
+    wallet: &mut wallet::Wallet,
+    exchanges: &config::ExchangeList,
+    format: report::Format,
+) -> Option<Box<dyn std::error::Error>> {
+    shutdown::install();
+    let server = match config.control.as_ref().map(control::bind).transpose() {
+        Ok(server) => server,
+        Err(e) => return Some(Box::new(e)),
+    };
+    let ops = DaemonOps { config, exchanges };
+    let consumed = AtomicBool::new(false); // the api stops with the order loop
+    std::thread::scope(|scope| {
+        if let (Some(server), Some(settings)) = (server, &config.control) {
+            let api = control::Api {
+                token: settings.token.clone(),
+                breaker_file: breaker::FILENAME.to_string(),
+                ops: &ops,
+            };
+            let consumed = &consumed;
+            scope.spawn(move || {
+                control::serve(server, &api, || {
+                    shutdown::requested() || consumed.load(Ordering::SeqCst)
+                })
+            });
         }
+        let result = consume_orders(config, wallet, exchanges, format);
+        consumed.store(true, Ordering::SeqCst);
+        result
+    })
+}
 
-        // final balances
+// the control api's view of the daemon
+struct DaemonOps<'a> {
+    config: &'a config::Config,
+    exchanges: &'a config::ExchangeList,
+}
+
+impl control::Ops for DaemonOps<'_> {
+    fn balances(&self) -> Result<serde_json::Value, String> {
+        let mut wallet: wallet::Wallet = config::read_type(wallet::FILENAME);
         wallet.reset();
-        scan_wallet(&mut wallet.coins, &exchanges);
-        wallet.print_with_price();
+        scan_wallet(&mut wallet.coins, self.exchanges);
+        serde_json::to_value(wallet.report()).map_err(|e| e.to_string())
+    }
 
-        None
-    } else {
-        Some(errors::MainError::build_box(format!(
-            "option not understood"
-        )))
+    fn orders(&self) -> Result<serde_json::Value, String> {
+        let orders = show_orders(
+            self.exchanges,
+            &self.config.wallet_private_key,
+            report::Format::Json,
+        );
+        serde_json::to_value(orders).map_err(|e| e.to_string())
+    }
+
+    fn validate(&self, order: &types::Order) -> Result<(), String> {
+        validate_order(order, self.exchanges).map_err(|e| e.msg)
+    }
+
+    fn standby(&self) -> Option<String> {
+        if lease::holding() {
+            None
+        } else {
+            Some(
+                lease::fenced()
+                    .unwrap_or_else(|| format!("{} not held by this instance", lease::KEY)),
+            )
+        }
+    }
+}
+
+// the daemon loop. a dropped redis connection is retried with a backoff
+fn consume_orders(
+    config: &config::Config,
+    wallet: &mut wallet::Wallet,
+    exchanges: &config::ExchangeList,
+    format: report::Format,
+) -> Option<Box<dyn std::error::Error>> {
+    let mut lease: Option<lease::Lease> = None;
+    let mut retry_ms = redis::RETRY_MS;
+    log::info!("daemon started {}", time::now_string());
+    while !shutdown::requested() {
+        let fed = consume_feed(config, wallet, exchanges, format, &mut lease, &mut retry_ms);
+        match fed {
+            Ok(()) => (),
+            Err(e) if redis::transient(&e) => {
+                log::warn!("redis: {}. reconnecting in {}ms", e, retry_ms);
+                time::sleep(retry_ms);
+                retry_ms = (retry_ms * 2).min(redis::RETRY_MAX_MS);
+            }
+            Err(e) => return Some(Box::new(e)),
+        }
+    }
+    log::info!("daemon stopped {}", time::now_string());
+    None
+}
+
+// orders from one redis connection until shutdown or a redis error. the
+// orders subscription stays open between orders so none are missed.
+fn consume_feed(
+    config: &config::Config,
+    wallet: &mut wallet::Wallet,
+    exchanges: &config::ExchangeList,
+    format: report::Format,
+    lease: &mut Option<lease::Lease>,
+    retry_ms: &mut u64,
+) -> Result<(), redis::Error> {
+    let mut redis = redis::Redis::connect(&config.redis_url)?;
+    let mut feed = redis::connect(&config.redis_url)?;
+    let mut orders = redis::subscribe_orders(&mut feed)?;
+    *retry_ms = redis::RETRY_MS;
+    let ttl_ms = config.lease_ms.unwrap_or(lease::DEFAULT_TTL_MS);
+    let mut standby = false;
+    let notify = notify::Settings::from_config(config);
+    while !shutdown::requested() {
+        notify::flush(&notify, false); // checked between orders
+        if let Some(halt) = breaker::halted(breaker::FILENAME) {
+            log::info!("trading halted: {}. waiting for yith resume", halt);
+            while breaker::halted(breaker::FILENAME).is_some() && !shutdown::requested() {
+                time::sleep(5000);
+            }
+            continue;
+        }
+        if !lease.as_ref().is_some_and(|lease| lease.held()) {
+            if let Some(reason) = lease::fenced() {
+                log::warn!("{}. back to standby", reason)
+            }
+            *lease = None;
+            match lease::Lease::acquire(&config.redis_url, ttl_ms)? {
+                Some(acquired) => {
+                    log::info!("lease {} acquired {}", acquired.token, time::now_string());
+                    *lease = Some(acquired);
+                    standby = false;
+                }
+                None => {
+                    if !standby {
+                        log::info!("standby. {} held by another instance", lease::KEY);
+                        standby = true;
+                    }
+                    time::sleep(ttl_ms / 3);
+                    continue;
+                }
+            }
+        }
+        if let Some(order) = control::next_order() {
+            log::info!("running posted order {}", order.id);
+            execute_order(config, wallet, &order, exchanges, format, false);
+            continue;
+        }
+        let arb_id = if redis.rd_exists("inplay")? {
+            log::info!("active order found!");
+            redis.rd_inplay()?
+        } else {
+            log::info!("waiting for order. {}", time::now_string());
+            match redis::wait_order(&mut orders, stop_waiting)? {
+                Some(arb_id) => arb_id,
+                None => continue,
+            }
+        };
+        let order = match redis.rd_order(arb_id.clone()) {
+            Ok(order) => order,
+            Err(e) if redis::transient(&e) => return Err(e),
+            Err(e) => {
+                // turned away like an order that fails validation
+                let reason = format!("invalid order json: {}", e);
+                log::warn!("arb:{} not loaded: {}", arb_id, reason);
+                metrics::aborted(&reason);
+                if let Some(mut status) = redis_status(config) {
+                    write_rejected(&mut status, &arb_id, &reason)
+                }
+                redis.rd_clear_inplay()?;
+                continue;
+            }
+        };
+        redis.rd_set_inplay(&arb_id)?;
+        execute_order(config, wallet, &order, exchanges, format, true);
+        // a fenced instance leaves inplay for the new lease holder
+        if lease::fenced().is_none() {
+            redis.rd_clear_inplay()?;
+        }
+    }
+    Ok(())
+}
+
+fn stop_waiting() -> bool {
+    shutdown::requested() || lease::fenced().is_some() || control::queued() > 0
+}
+
+// the inplay lock for a single run from redis
+fn acquire_lease(config: &config::Config) -> Result<lease::Lease, Box<dyn std::error::Error>> {
+    let ttl_ms = config.lease_ms.unwrap_or(lease::DEFAULT_TTL_MS);
+    match lease::Lease::acquire(&config.redis_url, ttl_ms)? {
+        Some(lease) => Ok(lease),
+        None => Err(Box::new(lease::LeaseError {
+            msg: format!("{} is held by another yith instance", lease::KEY),
+        })),
+    }
+}
+
+// checked again before each leg, with the limits of that leg's exchanges
+fn order_stale(
+    order: &types::Order,
+    books: &types::Books,
+    exchanges: &config::ExchangeList,
+    max_age: Option<u64>,
+) -> Option<String> {
+    let limit = validate::max_age(order, books, exchanges, max_age)?;
+    validate::check_age(&order.date, limit, time::now_millis() as i64).err()
+}
+
+// halts and lost leases both stop new submits
+fn submit_blocked() -> Option<String> {
+    match breaker::halted(breaker::FILENAME) {
+        Some(halt) => Some(format!("halted: {}", halt)),
+        None => lease::fenced(),
+    }
+}
+
+fn load_order(
+    config: &config::Config,
+    matches: &clap::ArgMatches,
+) -> Result<types::Order, Box<dyn std::error::Error>> {
+    match matches.value_of("arb_file") {
+        Some(filename) => {
+            log::info!("loading {}", filename);
+            Ok(types::Order::from_file(filename)?)
+        }
+        None => {
+            let mut redis = redis::Redis::connect(&config.redis_url)?;
+            redis
+                .rd_next()
+                .map_err(|e| errors::MainError::build_box(format!("redis order not loaded: {}", e)))
+        }
     }
 }
 
@@ -207,30 +788,36 @@ fn run_transfer(
     token: &types::Ticker,
 ) -> Result<Option<String>, Box<dyn std::error::Error>> {
     let config = config::CONFIG.get().unwrap();
-    println!(
+    log::info!(
         "{:?} into {} {:0.5} {}",
-        direction, exchange.settings.name, amount, token
+        direction,
+        exchange.settings.name,
+        amount,
+        token
     );
     let public_addr = eth::privkey_to_addr(private_key);
-    let escan = config::ETHERSCAN.get().unwrap();
-    let etoken = escan.tokens.get(token).unwrap();
+    let registry = config::TOKENS.get().unwrap();
+    let etoken = registry.get(&token.symbol).unwrap();
     let start_wallet = etherscan_coin(
         &public_addr,
         &etoken.symbol,
-        &etoken.hash,
+        &etoken.address,
         &config.etherscan_key,
     );
-    println!(
+    log::info!(
         "run_transfer starting wallet balance {:0.5} {}",
-        start_wallet, token
+        start_wallet,
+        token
     );
     let start_exchange = match exchange_balance(&public_addr, exchange, token) {
         Some(balance) => balance,
         None => 0.0,
     };
-    println!(
+    log::info!(
         "run_transfer {} starting exchange balance {:0.5} {}",
-        exchange.settings.name, start_exchange, token
+        exchange.settings.name,
+        start_exchange,
+        token
     );
     let tid_opt = match direction {
         exchange::TransferDirection::Withdraw => {
@@ -252,19 +839,22 @@ fn run_transfer(
                         Some(balance) => balance,
                         None => 0.0,
                     };
-                    println!(
+                    log::info!(
                         "run_transfer {} stop exchange balance {:0.5} {}",
-                        exchange.settings.name, stop_exchange, token
+                        exchange.settings.name,
+                        stop_exchange,
+                        token
                     );
                     let stop_wallet = etherscan_coin(
                         &public_addr,
                         &etoken.symbol,
-                        &etoken.hash,
+                        &etoken.address,
                         &config.etherscan_key,
                     );
-                    println!(
+                    log::info!(
                         "run_transfer stop wallet balance {:0.5} {}",
-                        stop_wallet, token
+                        stop_wallet,
+                        token
                     );
                     let exchange_change = match direction {
                         exchange::TransferDirection::Withdraw => start_exchange - stop_exchange,
@@ -276,13 +866,19 @@ fn run_transfer(
                     };
                     let exchange_diff = amount - exchange_change;
                     let wallet_diff = amount - wallet_change;
-                    println!(
+                    log::info!(
                         "run_transfer {} actual exchange change {:0.5} fee {:0.5} (missing from amount {})",
-                        token, exchange_change, exchange_diff, amount
+                        token,
+                        exchange_change,
+                        exchange_diff,
+                        amount
                     );
-                    println!(
+                    log::info!(
                         "run_transfer {} actual wallet change {:0.5} fee {:0.5} (missing from amount {})",
-                        token, wallet_change, wallet_diff, amount
+                        token,
+                        wallet_change,
+                        wallet_diff,
+                        amount
                     );
                     Ok(None)
                 }
@@ -306,7 +902,7 @@ fn wait_transfer(
     public_addr: &str,
     exchange: &config::Exchange,
 ) -> exchange::BalanceStatus {
-    println!("wait_transfer watching {}", transfer_id);
+    log::info!("wait_transfer watching {}", transfer_id);
     let start = time::now();
     let mut status = exchange::BalanceStatus::InProgress;
     let mut done = false;
@@ -315,7 +911,7 @@ fn wait_transfer(
         status = exchange
             .api
             .transfer_status(transfer_id, public_addr, &exchange.settings);
-        println!(
+        log::info!(
             "wait_transfer {} {:?} {}",
             transfer_id,
             status,
@@ -327,111 +923,961 @@ fn wait_transfer(
                 time::sleep(10000);
                 false
             }
-            exchange::BalanceStatus::Error => true,
-        };
+            exchange::BalanceStatus::Error => true,
+        };
+    }
+    status
+}
+
+fn scan_wallet(coins: &mut Vec<wallet::WalletCoin>, exchanges: &config::ExchangeList) {
+    let config = config::CONFIG.get().unwrap();
+    let my_addr = eth::privkey_to_addr(&config.wallet_private_key);
+    let mut eth_coins = etherscan_coins(&my_addr, coins, &config.etherscan_key);
+    coins.append(&mut eth_coins);
+    for exchange in exchanges.enabled() {
+        let mut exchange_coins = exchange_coins(&my_addr, exchange);
+        coins.append(&mut exchange_coins);
+    }
+    for coin in coins.iter().filter(|coin| coin.source != "limit") {
+        metrics::BALANCE
+            .with_label_values(&[&coin.ticker_symbol, &coin.source])
+            .set(coin.base_total())
+    }
+}
+
+fn show_orders(
+    exchanges: &config::ExchangeList,
+    private_key: &str,
+    format: report::Format,
+) -> report::OrdersReport {
+    let mut reports = vec![];
+    for exchange in exchanges.enabled() {
+        let orders = exchange.api.open_orders(private_key, &exchange.settings);
+        if format == report::Format::Table {
+            println!("{} {} ORDERS", exchange.settings.name, orders.len());
+        }
+        for order in orders {
+            if format == report::Format::Table {
+                println!(
+                    "  {} {:?} {} {} {:.5}@{:.5} {}",
+                    order.id,
+                    order.state,
+                    order.side,
+                    order.market,
+                    order.base_qty,
+                    order.quote,
+                    &order.create_date[0..10],
+                );
+            }
+            reports.push(report::OrderReport::build(&exchange.settings.name, order));
+        }
+    }
+    report::OrdersReport { orders: reports }
+}
+
+fn print_report<T: serde::Serialize + report::Tabular>(format: report::Format, out: &T) {
+    if let Some(rendered) = report::render(format, out) {
+        println!("{}", rendered);
+    }
+}
+
+fn tokens_check() -> Option<Box<dyn std::error::Error>> {
+    let registry = config::TOKENS.get().unwrap();
+    println!("registry {} tokens", registry.len());
+    let conflicts = registry.check();
+    for conflict in &conflicts {
+        println!("{}", conflict);
+    }
+    if conflicts.is_empty() {
+        println!("tokens check: no conflicts");
+        None
+    } else {
+        Some(errors::MainError::build_box(format!(
+            "tokens check: {} conflicts",
+            conflicts.len()
+        )))
+    }
+}
+
+fn refresh_markets(
+    exchanges: &config::ExchangeList,
+    exchange_name: Option<&str>,
+    dryrun: bool,
+) -> Option<Box<dyn std::error::Error>> {
+    let selected: Vec<&config::Exchange> = match exchange_name {
+        Some(name) => match exchanges.find_by_name(name) {
+            Some(exchange) => vec![exchange],
+            None => {
+                return Some(errors::MainError::build_box(format!(
+                    "unknown exchange {}",
+                    name
+                )));
+            }
+        },
+        None => exchanges.enabled(),
+    };
+    let mut failures = 0;
+    for exchange in selected {
+        match exchange.api.markets(&exchange.settings) {
+            Ok(files) => {
+                for file in files {
+                    match refresh::apply(&file, dryrun) {
+                        Ok(changes) => {
+                            println!(
+                                "{} {} {} changes{}",
+                                exchange.settings.name,
+                                file.filename,
+                                changes.len(),
+                                if dryrun { " (dryrun)" } else { "" }
+                            );
+                            for change in changes {
+                                println!("  {}", change);
+                            }
+                        }
+                        Err(e) => {
+                            println!("{} {} {}", exchange.settings.name, file.filename, e);
+                            failures += 1;
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                println!("{} markets {}", exchange.settings.name, e);
+                failures += 1;
+            }
+        }
+    }
+    if failures > 0 {
+        Some(errors::MainError::build_box(format!(
+            "refresh: {} failures",
+            failures
+        )))
+    } else {
+        None
+    }
+}
+
+// to every smtp notify sink, as text with an html alternative
+fn email_report(
+    config: &config::Config,
+    period_report: &report::PeriodReport,
+) -> Option<Box<dyn std::error::Error>> {
+    let notify = notify::Settings::from_config(config);
+    let smtps: Vec<&email::Smtp> = notify
+        .sinks
+        .iter()
+        .filter_map(|sink| match &sink.sink {
+            notify::Sink::Smtp(smtp) => Some(smtp),
+            _ => None,
+        })
+        .collect();
+    if smtps.is_empty() {
+        return Some(errors::MainError::build_box(
+            "report --email: no smtp sink in config notify or email".to_string(),
+        ));
+    }
+    let (text, html) = (period_report.to_string(), period_report.html());
+    let mut failed = None;
+    for smtp in smtps {
+        match smtp.send_alternative(&period_report.subject(), &text, &html) {
+            Ok(()) => log::info!("report sent to {}", smtp),
+            Err(e) => {
+                log::warn!("report to {} failed: {}", smtp, e);
+                failed = Some(errors::MainError::build_box(format!("{}: {}", smtp, e)));
+            }
+        }
+    }
+    failed
+}
+
+// a finished order: a failure when aborted, with leg errors or unhedged,
+// otherwise a fill for the digest
+fn notify_order(
+    config: &config::Config,
+    order: &types::Order,
+    run_log: &log::RunLog,
+    run_report: &report::RunReport,
+) {
+    let notify = notify::Settings::from_config(config);
+    let failure = run_report.failure();
+    let (event, reason) = match &failure {
+        Some(reason) => (notify::Event::Failure, reason.as_str()),
+        None => (notify::Event::Fill, ""),
+    };
+    let values = order_values(order, run_log, reason);
+    notify::send(&notify, notify.message(event, &values));
+}
+
+// the template placeholders for an order
+fn order_values(
+    order: &types::Order,
+    run_log: &log::RunLog,
+    reason: &str,
+) -> Vec<(&'static str, String)> {
+    vec![
+        ("order_id", order.id.clone()),
+        ("pair", order.pair.to_string()),
+        ("cost", format!("{:0.4}", order.cost)),
+        ("profit", format!("{:0.4}", order.profit)),
+        ("reason", reason.to_string()),
+        ("log", run_log.to_string()),
+    ]
+}
+
+fn count_good_total<M, N, O, T, S>(booksheets: &Vec<(M, N, O, f64, Vec<Result<T, S>>)>) -> f64 {
+    booksheets
+        .into_iter()
+        .fold(0.0, |memo, (_m, _n, _o, total, _sheets)| memo + total)
+}
+
+fn run_order(
+    config: &config::Config,
+    wallet: &mut wallet::Wallet,
+    order: &types::Order,
+    exchanges: &config::ExchangeList,
+    subscribers: Vec<Box<dyn lifecycle::Subscriber>>,
+    max_age: Option<u64>, // default order age limit. None checks only an order ttl
+) -> (log::RunLog, report::RunReport) {
+    let _arb_id = log::scoped_field(log::ARB_ID, &order.id);
+    let mut run = RunState::new(order);
+    for subscriber in subscribers {
+        run.lifecycle.subscribe(subscriber)
+    }
+    let mut run_report = report::RunReport::new(order);
+    run.log.add(format!(
+        "{}/{} Cost {:0.5} Profit {:0.5} {}",
+        order.pair.base, order.pair.quote, order.cost, order.profit, order.id,
+    ));
+    let stale = order_stale(order, &order.ask_books, exchanges, max_age)
+        .or_else(|| order_stale(order, &order.bid_books, exchanges, max_age));
+    if let Some(reason) = stale {
+        run.abort(&mut run_report, format!("order expired! {}", reason));
+        run.finish(order, &mut run_report);
+        return (run.log, run_report);
+    }
+    run.to(lifecycle::State::Validated, "");
+
+    let ask_sheets = build_books(
+        config,
+        wallet,
+        &order.ask_books,
+        exchanges,
+        Mode::Real,
+        &mut run,
+    );
+    let ask_sheets_good_total = count_good_total(&ask_sheets);
+    run_report.legs.extend(leg_reports(&ask_sheets));
+    run.to(
+        lifecycle::State::AskBuilt,
+        &format!("good total {:0.5}", ask_sheets_good_total),
+    );
+
+    if order.ask_books.cost_total() == 0.0 || ask_sheets_good_total > 0.0 {
+        let sim_bid_sheets = build_books(
+            config,
+            wallet,
+            &order.bid_books,
+            exchanges,
+            Mode::Simulate,
+            &mut run,
+        );
+        let sim_bid_sheets_good_total = count_good_total(&sim_bid_sheets);
+        let profit_ok = check_profit(config, order, &mut run);
+
+        if (order.bid_books.cost_total() == 0.0 || sim_bid_sheets_good_total > 0.0) && profit_ok {
+            let blocked = submit_blocked()
+                .or_else(|| order_stale(order, &order.ask_books, exchanges, max_age));
+            if let Some(reason) = blocked {
+                run.abort(&mut run_report, format!("submit aborted! {}", reason));
+                run.finish(order, &mut run_report);
+                return (run.log, run_report);
+            }
+            let before = recheck::Holdings::build(wallet, &order.pair);
+            record_volume(&mut run.exposure, &ask_sheets);
+            run_deposits(config, exchanges, &mut run, types::AskBid::Ask);
+            run.to(lifecycle::State::AskSubmitted, "");
+            metrics::gas_limit(&run.gas, types::AskBid::Ask);
+            let ask_runs = run_sheets(config, ask_sheets);
+            let ask_detail = leg_runs_detail(&ask_runs);
+            let ask_filled = report::legs_filled(&ask_runs);
+            if ask_filled {
+                run.to(lifecycle::State::AskFilled, &ask_detail);
+            } else {
+                run.log.add(format!("ask leg incomplete {}", ask_detail));
+            }
+            let mut swept = ask_runs.iter().any(|leg_run| leg_run.swept);
+            run_report.record_runs(ask_runs);
+
+            // wallet refresh
+            log::info!("=wallet refresh");
+            wallet.reset();
+            scan_wallet(&mut wallet.coins, &exchanges);
+            let fills =
+                recheck::Fills::build(&before, &recheck::Holdings::build(wallet, &order.pair));
+            run.to(
+                lifecycle::State::Rebalanced,
+                &format!("bought {:0.5} spent {:0.5}", fills.bought, fills.spent),
+            );
+
+            run.ledger.clear(types::AskBid::Bid); // replace the simulated bids
+            run.gas.clear(types::AskBid::Bid);
+            let bid_books = refresh_books(&order.bid_books, exchanges, &mut run);
+            let bid_sheets =
+                build_books(config, wallet, &bid_books, exchanges, Mode::Real, &mut run);
+            let bid_sheets_good_total = count_good_total(&bid_sheets);
+            run_report.legs.extend(leg_reports(&bid_sheets));
+            run.to(
+                lifecycle::State::BidBuilt,
+                &format!("good total {:0.5}", bid_sheets_good_total),
+            );
+
+            let blocked = submit_blocked().or_else(|| {
+                // once asks have bought, the bid books were just refreshed
+                // and the order's age says nothing about them
+                if fills.bought > 0.0 {
+                    None
+                } else {
+                    order_stale(order, &order.bid_books, exchanges, max_age)
+                }
+            });
+            // why the bid leg did not run, when its sells go to the unwind policies
+            let unwind_reason = if order.bid_books.cost_total() == 0.0 {
+                // one sided, like a manual buy: the asks were the whole order
+                if !ask_filled {
+                    run.abort(
+                        &mut run_report,
+                        format!("ask leg incomplete! {}", ask_detail),
+                    );
+                } else {
+                    if swept {
+                        run.to(lifecycle::State::Swept, "");
+                    }
+                    run.to(lifecycle::State::Done, "");
+                }
+                None
+            } else if let Some(reason) = blocked {
+                Some(format!("submit aborted! {}", reason))
+            } else if bid_sheets_good_total > 0.0 {
+                match recheck_bids(config, &fills, &mut run) {
+                    None => {
+                        record_volume(&mut run.exposure, &bid_sheets);
+                        run_deposits(config, exchanges, &mut run, types::AskBid::Bid);
+                        run.to(lifecycle::State::BidSubmitted, "");
+                        metrics::gas_limit(&run.gas, types::AskBid::Bid);
+                        let bid_runs = run_sheets(config, bid_sheets);
+                        let detail = leg_runs_detail(&bid_runs);
+                        let filled = report::legs_filled(&bid_runs);
+                        swept |= bid_runs.iter().any(|leg_run| leg_run.swept);
+                        run_report.record_runs(bid_runs);
+                        if filled {
+                            run.to(lifecycle::State::BidFilled, &detail);
+                            if swept {
+                                run.to(lifecycle::State::Swept, "");
+                            }
+                            run.to(lifecycle::State::Done, "");
+                        } else {
+                            run.abort(&mut run_report, format!("bid leg incomplete! {}", detail));
+                        }
+                        None
+                    }
+                    Some(reason) => Some(format!("submit aborted! {}", reason)),
+                }
+            } else {
+                Some(format!(
+                    "sumbit aborted! {} good total bids",
+                    bid_sheets_good_total
+                ))
+            };
+            if let Some(reason) = unwind_reason {
+                if fills.bought > 0.0 {
+                    run.to(lifecycle::State::Unwinding, &reason);
+                    if unwind_order(config, wallet, order, exchanges, &before, &fills, &mut run) {
+                        run.unwound(&mut run_report, reason);
+                    } else {
+                        run.abort(&mut run_report, reason);
+                    }
+                } else {
+                    run.abort(&mut run_report, reason);
+                }
+            }
+        } else if !profit_ok && run.gas_quote.is_none() {
+            run.abort(
+                &mut run_report,
+                format!(
+                    "submit aborted! gas cannot be priced. network_usd {} quote_usd {}",
+                    order.network_usd, order.quote_usd
+                ),
+            );
+        } else if !profit_ok {
+            let net_usd = net_profit_usd(order, &run);
+            run.abort(
+                &mut run_report,
+                format!(
+                    "submit aborted! expected profit after fees and gas {:0.2} usd below {:0.2} usd",
+                    net_usd,
+                    config.min_profit_usd.unwrap_or(0.0)
+                ),
+            );
+        } else {
+            run.abort(
+                &mut run_report,
+                format!(
+                    "submit aborted! {} good total sim_bids",
+                    sim_bid_sheets_good_total
+                ),
+            );
+        }
+    } else {
+        run.abort(
+            &mut run_report,
+            format!("submit aborted! {} good total asks", ask_sheets_good_total),
+        );
     }
-    status
+    run.finish(order, &mut run_report);
+    (run.log, run_report)
 }
 
-fn scan_wallet(coins: &mut Vec<wallet::WalletCoin>, exchanges: &config::ExchangeList) {
-    let config = config::CONFIG.get().unwrap();
-    let my_addr = eth::privkey_to_addr(&config.wallet_private_key);
-    print!("etherscan BALANCES for ");
-    let mut eth_coins = etherscan_coins(&my_addr, coins, &config.etherscan_key);
-    println!();
-    coins.append(&mut eth_coins);
-    for exchange in exchanges.enabled() {
-        let mut exchange_coins = exchange_coins(&my_addr, exchange);
-        coins.append(&mut exchange_coins);
-    }
+fn leg_runs_detail(leg_runs: &[report::LegRun]) -> String {
+    leg_runs
+        .iter()
+        .map(|leg_run| leg_run.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
 }
 
-fn show_orders(exchanges: &config::ExchangeList, private_key: &str) {
-    for exchange in exchanges.enabled() {
-        let orders = exchange.api.open_orders(private_key, &exchange.settings);
-        println!("{} {} ORDERS", exchange.settings.name, orders.len());
-        for order in orders {
-            println!(
-                "  {} {:?} {} {} {:.5}@{:.5} {}",
-                order.id,
-                order.state,
-                order.side,
-                order.market,
-                order.base_qty,
-                order.quote,
-                &order.create_date[0..10],
+// sell what the ask leg bought after the bid leg failed, trying each
+// configured policy in turn. every attempt goes in the run log. true when
+// no more than dust is left.
+fn unwind_order(
+    config: &config::Config,
+    wallet: &mut wallet::Wallet,
+    order: &types::Order,
+    exchanges: &config::ExchangeList,
+    before: &recheck::Holdings,
+    fills: &recheck::Fills,
+    run: &mut RunState,
+) -> bool {
+    let policies = config
+        .unwind_policies
+        .clone()
+        .unwrap_or_else(unwind::default_policies);
+    let max_loss = config.max_unwind_loss.unwrap_or(unwind::DEFAULT_MAX_LOSS);
+    let ask_price = match fills.price() {
+        Some(price) => price,
+        None => return true, // nothing bought, nothing to sell
+    };
+    let floor = unwind::floor_price(ask_price, max_loss);
+    let dust = fills.bought * unwind::DUST_FRACTION;
+    let mut remaining = fills.bought;
+    run.log.add(format!(
+        "unwinding {:0.5} {} bought at {:0.8}. floor {:0.8} policies {:?}",
+        remaining, order.pair.base, ask_price, floor, policies
+    ));
+    for policy in policies {
+        let books = match policy {
+            unwind::Policy::SellBack => unwind::sell_back(&order.ask_books, remaining, floor),
+            unwind::Policy::NextBest => unwind::next_best(&order.bid_books, floor),
+            unwind::Policy::Hold => break,
+        };
+        for book in books {
+            if remaining <= dust {
+                break;
+            }
+            let exchange = match exchanges.find_by_name(&book.market.source.name) {
+                Some(exchange) if exchange.settings.enabled => exchange,
+                _ => continue,
+            };
+            run.ledger.clear(types::AskBid::Bid);
+            let (total, sheets) = build_book(
+                config,
+                wallet,
+                &types::AskBid::Bid,
+                &book,
+                exchange,
+                Mode::Real,
+                run,
+            );
+            let booksheets = vec![(
+                exchange,
+                types::AskBid::Bid,
+                book.market.quote.clone(),
+                total,
+                sheets,
+            )];
+            if total > 0.0 {
+                record_volume(&mut run.exposure, &booksheets);
+                run_deposits(config, exchanges, run, types::AskBid::Bid);
+                for leg_run in run_sheets(config, booksheets) {
+                    run.log.add(leg_run.to_string());
+                }
+                wallet.reset();
+                scan_wallet(&mut wallet.coins, exchanges);
+            }
+            let after = (recheck::Holdings::build(wallet, &order.pair).base - before.base).max(0.0);
+            run.log.add(
+                unwind::Attempt {
+                    policy,
+                    exchange: exchange.settings.name.clone(),
+                    floor,
+                    offered: total,
+                    before: remaining,
+                    after,
+                }
+                .to_string(),
             );
+            remaining = after;
+        }
+        if remaining <= dust {
+            break;
         }
     }
+    if remaining > dust {
+        let alert = format!(
+            "ALERT holding {:0.5} {} unhedged from order {}",
+            remaining, order.pair.base, order.id
+        );
+        run.log.add(alert.clone());
+        let notify = notify::Settings::from_config(config);
+        let values = order_values(order, &run.log, &alert);
+        notify::send(&notify, notify.message(notify::Event::Failure, &values));
+        false
+    } else {
+        true
+    }
 }
 
-fn mail_log(email: &str, order: &types::Order, run_log: &log::RunLog) {
-    let subject = format!("{}", order.pair);
-    let out = format!(
-        "order #{} {} {:0.4} {:0.4}\n{}",
-        order.id, order.pair, order.cost, order.profit, run_log
-    );
-    email::send(email, &subject, &out);
+// re-read the books from their exchanges so the bid leg and its recheck
+// price against the market as it is now. a book whose exchange cannot be
+// read keeps the order's offers.
+fn refresh_books(
+    books: &types::Books,
+    exchanges: &config::ExchangeList,
+    run: &mut RunState,
+) -> types::Books {
+    let mut fresh = vec![];
+    for book in &books.books {
+        let exchange = match exchanges.find_by_name(&book.market.source.name) {
+            Some(exchange) if exchange.settings.enabled => exchange,
+            _ => {
+                fresh.push(book.clone());
+                continue;
+            }
+        };
+        let none = types::Offer {
+            base_qty: 0.0,
+            quote: 0.0,
+        };
+        let (askbid, market, _) = unswap(&books.askbid, &book.market, &none);
+        exchange.rate_limit.wait();
+        match exchange
+            .api
+            .order_book(&exchange.settings, &market, &askbid)
+        {
+            Ok(offers) => {
+                let offers = offers
+                    .iter()
+                    .map(|offer| reswap(&book.market, offer))
+                    .collect();
+                let refreshed = book.refreshed(offers);
+                run.log.add(format!(
+                    "refresh {} {} book planned {} now {}",
+                    exchange.settings.name,
+                    books.askbid,
+                    book.rollup(),
+                    refreshed.rollup()
+                ));
+                fresh.push(refreshed)
+            }
+            Err(e) => {
+                run.log.add(format!(
+                    "refresh {} {} book kept as planned: {}",
+                    exchange.settings.name, books.askbid, e
+                ));
+                fresh.push(book.clone())
+            }
+        }
+    }
+    types::Books {
+        askbid: books.askbid,
+        books: fresh,
+    }
 }
 
-fn count_good_total<M, N, O, T, S>(booksheets: &Vec<(M, N, O, f64, Vec<Result<T, S>>)>) -> f64 {
-    booksheets
-        .into_iter()
-        .fold(0.0, |memo, (_m, _n, _o, total, _sheets)| memo + total)
+// the ask leg is done. compare its fills with the rebuilt bids before selling.
+// Some(reason) means hold: skip the bid leg and unwind.
+fn recheck_bids(
+    config: &config::Config,
+    fills: &recheck::Fills,
+    run: &mut RunState,
+) -> Option<String> {
+    if !run.ledger.has_asks() {
+        return None;
+    }
+    let max_slippage = config.max_slippage.unwrap_or(recheck::DEFAULT_MAX_SLIPPAGE);
+    let policy = config.slippage_policy.unwrap_or_default();
+    match recheck::Recheck::build(fills, &run.ledger, max_slippage) {
+        Some(recheck) => {
+            run.log.add(recheck.to_string());
+            if recheck.ok() {
+                None
+            } else {
+                let reason = format!(
+                    "bid leg loss {:0.5} beyond tolerance {:0.5}. policy {}",
+                    recheck.profit, recheck.tolerance, policy
+                );
+                match policy {
+                    recheck::Policy::Hold => Some(reason),
+                    recheck::Policy::Sell => {
+                        run.log.add(format!("selling at a loss! {}", reason));
+                        None
+                    }
+                }
+            }
+        }
+        None => {
+            run.log.add(format!(
+                "recheck: no ask fills seen (bought {:0.5} spent {:0.5})",
+                fills.bought, fills.spent
+            ));
+            None
+        }
+    }
 }
 
-fn run_order(
+// build both sides from real balances without signing or sending anything
+fn plan_order(
     config: &config::Config,
-    wallet: &mut wallet::Wallet,
+    wallet: &wallet::Wallet,
     order: &types::Order,
     exchanges: &config::ExchangeList,
-) -> log::RunLog {
-    let mut run_out = log::RunLog::new();
-    run_out.add(format!(
-        "{}/{} Cost {:0.5} Profit {:0.5} {}",
+) -> report::PlanReport {
+    let mut run = RunState::new(order);
+    let mut plan_report = report::PlanReport::new(order);
+    run.log.add(format!(
+        "PLAN {}/{} Cost {:0.5} Profit {:0.5} {}",
         order.pair.base, order.pair.quote, order.cost, order.profit, order.id,
     ));
+    let ask_sheets = build_books(
+        config,
+        wallet,
+        &order.ask_books,
+        exchanges,
+        Mode::Plan,
+        &mut run,
+    );
+    // the bid side sells what the asks buy, so its balances are simulated
+    let bid_sheets = build_books(
+        config,
+        wallet,
+        &order.bid_books,
+        exchanges,
+        Mode::Simulate,
+        &mut run,
+    );
+    plan_report.legs.extend(leg_reports(&ask_sheets));
+    plan_report.legs.extend(leg_reports(&bid_sheets));
+    plan_report.go = check_profit(config, order, &mut run);
 
-    let ask_sheets = build_books(config, wallet, &order.ask_books, exchanges, Mode::Real);
-    let ask_sheets_good_total = count_good_total(&ask_sheets);
+    for entry in &run.ledger.entries {
+        plan_report.actions.push(report::PlanAction {
+            askbid: entry.askbid,
+            exchange: entry.exchange.clone(),
+            action: "fill".to_string(),
+            token: order.pair.base.clone(),
+            amount: entry.offer.base_qty,
+            price: Some(entry.offer.quote),
+            fee: Some(entry.fee),
+        })
+    }
+    for deposit in &run.deposits {
+        plan_report.actions.push(report::PlanAction {
+            askbid: deposit.askbid,
+            exchange: deposit.exchange.clone(),
+            action: "deposit".to_string(),
+            token: deposit.token.symbol.clone(),
+            amount: deposit.amount,
+            price: None,
+            fee: None,
+        })
+    }
+    for (exchange, askbid, buy_token, total, _sheets) in ask_sheets.iter().chain(bid_sheets.iter())
+    {
+        if exchange.settings.has_balances && *total > 0.0 {
+            plan_report.actions.push(report::PlanAction {
+                askbid: *askbid,
+                exchange: exchange.settings.name.clone(),
+                action: "sweep".to_string(),
+                token: buy_token.symbol.clone(),
+                amount: 0.0, // the whole exchange balance
+                price: None,
+                fee: None,
+            })
+        }
+    }
+    plan_report.gas_steps = run
+        .gas
+        .steps
+        .iter()
+        .map(|(askbid, step)| format!("{} {}", askbid, step))
+        .collect();
+    plan_report.expected_profit = run.ledger.expected_profit();
+    plan_report.fees = run.ledger.fees_paid();
+    plan_report.gas = run.gas_quote;
+    plan_report.net_profit_usd = net_profit_usd(order, &run);
+    plan_report.log = run.log.lines();
+    plan_report
+}
 
-    if order.ask_books.cost_total() == 0.0 || ask_sheets_good_total > 0.0 {
-        let sim_bid_sheets =
-            build_books(config, wallet, &order.bid_books, exchanges, Mode::Simulate);
-        let sim_bid_sheets_good_total = count_good_total(&sim_bid_sheets);
+// per order state threaded through the build steps
+struct RunState {
+    log: log::RunLog,
+    exposure: limits::Exposure,
+    ledger: fees::Ledger,
+    gas: gas::Plan,
+    gas_quote: Option<f64>,
+    deposits: Vec<Deposit>,
+    lifecycle: lifecycle::Lifecycle,
+}
 
-        if order.bid_books.cost_total() == 0.0 || sim_bid_sheets_good_total > 0.0 {
-            let _ask_runs = run_sheets(config, ask_sheets);
+impl RunState {
+    fn new(order: &types::Order) -> RunState {
+        let mut lifecycle = lifecycle::Lifecycle::new(&order.id);
+        lifecycle.subscribe(Box::new(lifecycle::LogSubscriber {}));
+        lifecycle.subscribe(Box::new(metrics::MetricsSubscriber {}));
+        RunState {
+            lifecycle,
+            log: log::RunLog::new(&order.id),
+            exposure: limits::Exposure::build(order),
+            ledger: fees::Ledger::new(),
+            gas: gas::Plan::new(),
+            gas_quote: None,
+            deposits: vec![],
+        }
+    }
 
-            // wallet refresh
-            println!("=wallet refresh");
-            wallet.reset();
-            scan_wallet(&mut wallet.coins, &exchanges);
+    fn to(&mut self, state: lifecycle::State, detail: &str) {
+        if let Err(e) = self.lifecycle.to(state, detail) {
+            log::warn!("{}", e)
+        }
+    }
 
-            let bid_sheets = build_books(config, wallet, &order.bid_books, exchanges, Mode::Real);
-            let bid_sheets_good_total = count_good_total(&bid_sheets);
+    fn abort(&mut self, run_report: &mut report::RunReport, reason: String) {
+        self.to(lifecycle::State::Aborted, &reason);
+        run_report.abort(&mut self.log, reason);
+    }
 
-            if bid_sheets_good_total > 0.0 {
-                let _bid_runs = run_sheets(config, bid_sheets);
-            } else {
-                run_out.add(format!(
-                    "sumbit aborted! {} good total bids",
-                    bid_sheets_good_total
-                ));
-            }
-        } else {
-            run_out.add(format!(
-                "submit aborted! {} good total sim_bids",
-                sim_bid_sheets_good_total
+    fn unwound(&mut self, run_report: &mut report::RunReport, reason: String) {
+        self.to(lifecycle::State::Unwound, &reason);
+        run_report.unwind(&mut self.log, reason);
+    }
+
+    // copy the log and the expected costs into the report
+    fn finish(&self, order: &types::Order, run_report: &mut report::RunReport) {
+        run_report.log = self.log.lines();
+        run_report.fees = self.ledger.fees_paid();
+        run_report.gas = self.gas_quote;
+        run_report.net_profit_usd = net_profit_usd(order, self);
+    }
+}
+
+// a deposit found while building a book, sent once the leg is cleared to run
+struct Deposit {
+    askbid: types::AskBid,
+    exchange: String,
+    amount: f64,
+    token: types::Ticker,
+}
+
+// recompute profit from the capped ask and simulated bid offers, net of taker
+// fees and the gas of every on-chain step, against the min_profit_usd threshold
+fn check_profit(config: &config::Config, order: &types::Order, run: &mut RunState) -> bool {
+    if !run.ledger.has_asks() || order.bid_books.cost_total() == 0.0 {
+        return true;
+    }
+    let profit = run.ledger.expected_profit();
+    run.log.add(format!(
+        "expected profit after fees {:0.5} {} ({:0.2} usd) fees {:0.5} {}",
+        profit,
+        order.pair.quote,
+        profit * order.quote_usd,
+        run.ledger.fees_paid(),
+        order.pair.quote
+    ));
+    let gas_price = match geth::ethgasstation_fast() {
+        Ok(gas_price) => gas_price,
+        Err(e) => {
+            run.log.add(format!("gas price not read: {}", e));
+            return false;
+        }
+    };
+    run.gas_quote = run
+        .gas
+        .cost_quote(gas_price, order.network_usd, order.quote_usd);
+    match run.gas_quote {
+        Some(gas_quote) => run.log.add(format!(
+            "gas {} @{}gwei = {:0.5} eth = {:0.5} {} (order fee_network {:0.5}) steps: {}",
+            run.gas.total_gas(),
+            gas_price / 1_000_000_000,
+            run.gas.cost_eth(gas_price),
+            gas_quote,
+            order.pair.quote,
+            order.fee_network,
+            run.gas
+        )),
+        None => {
+            run.log.add(format!(
+                "gas {} cannot be priced. network_usd {} quote_usd {}",
+                run.gas.total_gas(),
+                order.network_usd,
+                order.quote_usd
             ));
+            return false;
+        }
+    }
+    let min_profit_usd = config.min_profit_usd.unwrap_or(0.0);
+    let net_usd = net_profit_usd(order, run);
+    run.log.add(format!(
+        "expected profit after fees and gas {:0.2} usd (min {:0.2} usd)",
+        net_usd, min_profit_usd
+    ));
+    net_usd > min_profit_usd
+}
+
+fn net_profit_usd(order: &types::Order, run: &RunState) -> f64 {
+    let gas_quote = run.gas_quote.unwrap_or(0.0);
+    (run.ledger.expected_profit() - gas_quote) * order.quote_usd
+}
+
+// on-chain steps a built book needs: exchange fills, deposit, sweep and approval
+fn plan_gas(
+    config: &config::Config,
+    askbid: types::AskBid,
+    exchange: &config::Exchange,
+    sell_token: &types::Ticker,
+    total: f64,
+    deposit: bool,
+    run: &mut RunState,
+) {
+    let name = &exchange.settings.name;
+    let mut steps = exchange.api.gas_steps(&exchange.settings, sell_token);
+    let contract_pulls = deposit
+        || steps
+            .iter()
+            .any(|s| s.kind == gas::Kind::Fill || s.kind == gas::Kind::Offer);
+    if deposit {
+        steps.push(gas::Step::build(name, gas::Kind::Deposit));
+    }
+    if exchange.settings.has_balances {
+        steps.push(gas::Step::build(name, gas::Kind::Withdraw)); // sweep
+    }
+    if contract_pulls
+        && sell_token.symbol != "ETH"
+        && needs_approval(config, exchange, sell_token, total)
+    {
+        steps.push(gas::Step::build(name, gas::Kind::Approve));
+    }
+    for step in steps {
+        log::info!("gas step {} {}", askbid, step);
+        run.gas.add(askbid, step);
+    }
+}
+
+fn needs_approval(
+    config: &config::Config,
+    exchange: &config::Exchange,
+    token: &types::Ticker,
+    amount: f64,
+) -> bool {
+    let spender = match &exchange.settings.contract_address {
+        Some(address) => address,
+        None => return false,
+    };
+    let registry = config::TOKENS.get().unwrap();
+    let etoken = match registry.get_on(&exchange.settings.name, &token.symbol) {
+        Some(etoken) => etoken,
+        None => return true,
+    };
+    let geth = geth::Client::build(&config.geth_url);
+    match erc20::Erc20::allowance(geth, &config.wallet_private_key, &etoken.address, spender) {
+        Ok(allowance) => {
+            let units = exchange::quantity_in_base_units(amount, etoken.decimals, etoken.decimals);
+            num_bigint::BigInt::from(allowance) < units
+        }
+        Err(e) => {
+            log::warn!("{} allowance unknown: {}", token, e);
+            true
+        }
+    }
+}
+
+fn run_deposits(
+    config: &config::Config,
+    exchanges: &config::ExchangeList,
+    run: &mut RunState,
+    askbid: types::AskBid,
+) {
+    let (deposits, rest) = run
+        .deposits
+        .drain(..)
+        .partition(|deposit| deposit.askbid == askbid);
+    run.deposits = rest;
+    for deposit in deposits {
+        if let Some(reason) = submit_blocked() {
+            run.log.add(format!(
+                "deposit {} {} refused: {}",
+                deposit.exchange, deposit.token, reason
+            ));
+            continue;
+        }
+        if let Some(exchange) = exchanges.find_by_name(&deposit.exchange) {
+            let direction = exchange::TransferDirection::Deposit;
+            let _deposit_id = run_transfer(
+                &config.wallet_private_key,
+                direction,
+                exchange,
+                deposit.amount,
+                &deposit.token,
+            );
+        }
+    }
+}
+
+fn record_volume(exposure: &mut limits::Exposure, booksheets: &[BookSheet]) {
+    for (exchange, _askbid, buy_token, total, _sheets) in booksheets {
+        if *total > 0.0 {
+            let sell_symbol = exposure.pricing.other(&buy_token.symbol).to_string();
+            exposure.record(&exchange.settings.name, &sell_symbol, *total);
         }
-    } else {
-        run_out.add(format!(
-            "submit aborted! {} good total asks",
-            ask_sheets_good_total
-        ));
     }
-    run_out
+}
+
+type BookSheet<'a> = (
+    &'a config::Exchange,
+    types::AskBid,
+    types::Ticker,
+    f64,
+    Vec<Result<exchange::OrderSheet, Box<dyn std::error::Error>>>,
+);
+
+fn leg_reports(booksheets: &[BookSheet]) -> Vec<report::LegReport> {
+    booksheets
+        .iter()
+        .map(
+            |(exchange, askbid, ticker, total, sheets)| report::LegReport {
+                exchange: exchange.settings.name.clone(),
+                askbid: *askbid,
+                token: ticker.symbol.clone(),
+                total: *total,
+                sheets: sheets.iter().filter(|s| s.is_ok()).count(),
+                errors: sheets
+                    .iter()
+                    .filter_map(|s| s.as_ref().err().map(|e| e.to_string()))
+                    .collect(),
+                filled: vec![],
+                failures: 0,
+            },
+        )
+        .collect()
 }
 
 // rust to learn
@@ -439,6 +1885,7 @@ fn run_order(
 enum Mode {
     Simulate,
     Real,
+    Plan, // real balances. nothing is signed or sent
 }
 
 fn build_books<'a>(
@@ -447,6 +1894,7 @@ fn build_books<'a>(
     books: &types::Books,
     exchanges: &'a config::ExchangeList,
     mode: Mode,
+    run: &mut RunState,
 ) -> Vec<(
     &'a config::Exchange,
     types::AskBid,
@@ -464,7 +1912,7 @@ fn build_books<'a>(
             Some(exchange) => {
                 let full = if exchange.settings.enabled {
                     let (total, sheets) =
-                        build_book(config, wallet, &books.askbid, book, exchange, mode);
+                        build_book(config, wallet, &books.askbid, book, exchange, mode, run);
                     (
                         exchange,
                         books.askbid.clone(),
@@ -484,10 +1932,10 @@ fn build_books<'a>(
                         )))]),
                     )
                 };
-                println!("->{} sheets {:?}", exchange_name, full.4);
+                log::info!("->{} sheets {:?}", exchange_name, full.4);
                 memo.push(full)
             }
-            None => println!("exchange detail not found for: {:#?}", exchange_name),
+            None => log::info!("exchange detail not found for: {:#?}", exchange_name),
         }
         memo
     })
@@ -500,6 +1948,7 @@ fn build_book(
     book: &types::Book,
     exchange: &config::Exchange,
     mode: Mode,
+    run: &mut RunState,
 ) -> (
     f64,
     Vec<Result<exchange::OrderSheet, Box<dyn std::error::Error>>>,
@@ -508,11 +1957,12 @@ fn build_book(
         types::AskBid::Ask => &book.market.quote,
         types::AskBid::Bid => &book.market.base,
     };
-    println!(
+    log::info!(
         "** {} {} {} sell_token: {}",
         match mode {
             Mode::Real => "BOOK",
             Mode::Simulate => "SIMBOOK",
+            Mode::Plan => "PLANBOOK",
         },
         askbid,
         &book.market,
@@ -524,20 +1974,25 @@ fn build_book(
             Ok(coin) => {
                 let wallet_pre_dust = match mode {
                     Mode::Simulate => book.cost_total(askbid.clone()), // simulate a full wallet
-                    Mode::Real => coin.base_total(),
+                    Mode::Real | Mode::Plan => coin.base_total(),
                 };
                 if sell_token.symbol == "ETH" {
                     let wallet_post_dust = if wallet_pre_dust > config.eth_dust {
                         let subtotal = wallet_pre_dust - config.eth_dust;
-                        println!(
+                        log::info!(
                             "wallet balance {} {} - {} dust min = {}",
-                            wallet_pre_dust, sell_token.symbol, config.eth_dust, subtotal
+                            wallet_pre_dust,
+                            sell_token.symbol,
+                            config.eth_dust,
+                            subtotal
                         );
                         subtotal
                     } else {
-                        println!(
+                        log::info!(
                             "wallet balance {} {} below {} dust min. skip.",
-                            wallet_pre_dust, sell_token.symbol, config.eth_dust
+                            wallet_pre_dust,
+                            sell_token.symbol,
+                            config.eth_dust
                         );
                         0.0
                     };
@@ -548,11 +2003,11 @@ fn build_book(
             }
             Err(_e) => {
                 let modeword = match mode {
-                    Mode::Simulate => "WARNING",
+                    Mode::Simulate | Mode::Plan => "WARNING",
                     Mode::Real => "ERROR",
                 };
                 match mode {
-                    Mode::Simulate => (),
+                    Mode::Simulate | Mode::Plan => (),
                     Mode::Real => {
                         panic!(
                             "{}: no balance available for {} (in {}). panicing.",
@@ -571,13 +2026,13 @@ fn build_book(
                 Ok(coin) => {
                     match mode {
                         Mode::Simulate => book.cost_total(askbid.clone()), // pretend its full
-                        Mode::Real => coin.base_total(),
+                        Mode::Real | Mode::Plan => coin.base_total(),
                     }
                 }
                 Err(_e) => 0.0, // not found means 0.0
             };
         exchange_balance = Some(exchange_token_balance);
-        println!(
+        log::info!(
             "wallet balance {} {} enhanced by {} balance {} {}",
             wallet_token_balance,
             &sell_token.symbol,
@@ -588,28 +2043,29 @@ fn build_book(
         wallet_token_balance += exchange_token_balance;
     }
 
-    let rollup_offer = book.offers.iter().fold(
-        types::Offer {
-            base_qty: 0.0,
-            quote: 0.0,
-        },
-        |mut rolled, offer| {
-            rolled.base_qty += offer.base_qty;
-            rolled.quote = offer.quote;
-            println!("rollup {} added {}", rolled, offer);
-            rolled
-        },
+    let sheet_offers = book.sheet_offers(exchange.settings.sheets);
+    log::info!(
+        "{:?} sheets {}",
+        exchange.settings.sheets,
+        sheet_offers
+            .iter()
+            .map(|o| o.to_string())
+            .collect::<Vec<String>>()
+            .join(", ")
     );
+    let book_askbid = *askbid;
+    // each sheet is capped by the balance left over from the ones before it
     let (total, processed_offers) =
-        vec![rollup_offer]
+        sheet_offers
             .iter()
             .fold((0.0, Vec::new()), |(mut total, mut offers), offer| {
                 let (askbid, market, offer) = unswap(askbid, &book.market, offer);
-                println!(
+                log::info!(
                     "** {} {} {} {} => {}{}",
                     match mode {
                         Mode::Real => "BUILD",
                         Mode::Simulate => "SIMBUILD",
+                        Mode::Plan => "PLANBUILD",
                     },
                     askbid,
                     &book.market,
@@ -624,12 +2080,20 @@ fn build_book(
                     &offer,
                     &market,
                     wallet_token_balance - total,
+                    total,
                     wallet,
+                    run,
                 ) {
                     Ok(capped_offer) => {
                         let value = capped_offer.cost(askbid);
                         if value > 0_f64 {
                             total += value;
+                            run.ledger.add(fees::Entry {
+                                askbid: book_askbid,
+                                exchange: exchange.settings.name.clone(),
+                                offer: reswap(&book.market, &capped_offer),
+                                fee: exchange.settings.taker_fee,
+                            });
                             Ok((capped_offer, market))
                         } else {
                             Err(errors::MainError::build_box(format!(
@@ -642,39 +2106,52 @@ fn build_book(
                 offers.push(capped_offer_opt);
                 (total, offers)
             });
-    println!("{} processed_offers done", processed_offers.len());
+    log::info!("{} processed_offers done", processed_offers.len());
+    let mut deposit = false;
     if let Some(exchange_token_balance) = exchange_balance {
         if total > exchange_token_balance {
-            println!(
+            deposit = true;
+            log::info!(
                 "order total {} exceeds exchange balance {}",
-                total, exchange_token_balance
+                total,
+                exchange_token_balance
             );
             let missing = total - exchange_token_balance;
-            println!(
+            log::info!(
                 "Deposit: {:0.4}{} from wallet (offer_cost {:0.4})",
-                missing, &sell_token.symbol, total
+                missing,
+                &sell_token.symbol,
+                total
             );
             match mode {
-                Mode::Simulate => println!("Simulate deposit skipped"), // not a limitation in simulate
-                Mode::Real => {
-                    let direction = exchange::TransferDirection::Deposit;
-                    let _deposit_id = run_transfer(
-                        &config.wallet_private_key,
-                        direction,
-                        &exchange,
-                        missing,
-                        &sell_token,
-                    );
-                }
+                Mode::Simulate => log::info!("Simulate deposit skipped"), // not a limitation in simulate
+                Mode::Real | Mode::Plan => run.deposits.push(Deposit {
+                    askbid: book_askbid,
+                    exchange: exchange.settings.name.clone(),
+                    amount: missing,
+                    token: sell_token.clone(),
+                }),
             }
         } else {
-            println!(
+            log::info!(
                 "order total {:0.5} is met by exchange balance {:0.5}. no despoit necessary.",
-                total, exchange_token_balance
+                total,
+                exchange_token_balance
             );
         }
     }
-    println!("submitting {} processed_offers", processed_offers.len());
+    if total > 0.0 {
+        plan_gas(
+            config,
+            book_askbid,
+            exchange,
+            sell_token,
+            total,
+            deposit,
+            run,
+        );
+    }
+    log::info!("submitting {} processed_offers", processed_offers.len());
     let sheets = processed_offers
         .into_iter()
         .map(|offer_opt| match offer_opt {
@@ -686,7 +2163,7 @@ fn build_book(
                     &market,
                     &capped_offer,
                 ),
-                Mode::Simulate => Ok(exchange::OrderSheet::Placebo),
+                Mode::Simulate | Mode::Plan => Ok(exchange::OrderSheet::Placebo),
             },
             Err(e) => Err(e),
         })
@@ -694,6 +2171,7 @@ fn build_book(
     (total, sheets)
 }
 
+#[allow(clippy::too_many_arguments)]
 fn build_offer(
     config: &config::Config,
     askbid: &types::AskBid,
@@ -701,9 +2179,11 @@ fn build_offer(
     offer: &types::Offer,
     market: &exchange::Market,
     wallet_token_balance: f64,
+    committed: f64, // spent by earlier sheets of this book
     wallet: &wallet::Wallet,
+    run: &mut RunState,
 ) -> Result<types::Offer, Box<dyn std::error::Error>> {
-    println!("Building offer {} {}", exchange, offer);
+    log::info!("Building offer {} {}", exchange, offer);
     let sell_token = match askbid {
         types::AskBid::Ask => &market.quote,
         types::AskBid::Bid => &market.base,
@@ -717,9 +2197,13 @@ fn build_offer(
             types::AskBid::Bid => 1.0,
         };
         offer_quote_adjusted = offer_quote_adjusted * adjustor;
-        println!(
+        log::info!(
             "quote {}{} spread premium {} adjusted by x{} to {}",
-            offer.quote, &market.quote, premium, adjustor, offer_quote_adjusted
+            offer.quote,
+            &market.quote,
+            premium,
+            adjustor,
+            offer_quote_adjusted
         );
     }
     let premium_offer = types::Offer {
@@ -730,10 +2214,10 @@ fn build_offer(
     let mut amount_limits = vec![];
     let offer_cost = premium_offer.cost(*askbid);
     amount_limits.push(offer_cost);
-    println!("added amount_limit of {:0.5} from offer_cost", offer_cost);
+    log::info!("added amount_limit of {:0.5} from offer_cost", offer_cost);
 
     amount_limits.push(wallet_token_balance);
-    println!(
+    log::info!(
         "added amount_limit of {:0.5} from wallet balance",
         wallet_token_balance
     );
@@ -741,12 +2225,18 @@ fn build_offer(
     // limit
     match wallet.find_coin_by_source_symbol("limit", &sell_token.symbol) {
         Ok(_coin) => {
-            let wallet_coin_limit = wallet.coin_limit(&sell_token.symbol);
+            let wallet_coin_limit = (wallet.coin_limit(&sell_token.symbol) - committed).max(0.0);
             amount_limits.push(wallet_coin_limit);
-            println!(
+            log::info!(
                 "added amount_limit of {:0.5} from wallet_coin_limit",
                 wallet_coin_limit
             );
+            if wallet_coin_limit < offer_cost {
+                run.log.add(format!(
+                    "{} {} cap binds: wallet limit {:0.5} {} < offer cost {:0.5}",
+                    exchange.settings.name, market, wallet_coin_limit, sell_token, offer_cost
+                ));
+            }
         }
         Err(_e) => {
             let _err = exchange::ExchangeError::build_box(format!(
@@ -756,19 +2246,66 @@ fn build_offer(
         }
     };
 
+    // bid side sells only what the ask leg bought, net of its taker fees
+    if run.ledger.has_asks() && sell_token.symbol.to_uppercase() == run.exposure.pricing.base {
+        let unsold = run.ledger.unsold();
+        amount_limits.push(unsold);
+        log::info!(
+            "added amount_limit of {:0.5} from ask leg net of fees",
+            unsold
+        );
+    }
+    log::info!(
+        "{} taker fee {} on {}",
+        exchange.settings.name,
+        exchange.settings.taker_fee,
+        match askbid {
+            types::AskBid::Ask => &market.base,
+            types::AskBid::Bid => &market.quote,
+        }
+    );
+
+    // exposure caps
+    let caps = limits::caps(
+        wallet,
+        &exchange.settings.name,
+        &market.id("-"),
+        *askbid,
+        &sell_token.symbol,
+        premium_offer.quote,
+        &run.exposure,
+    );
+    for cap in &caps {
+        let cap = limits::Cap {
+            name: cap.name.clone(),
+            amount: (cap.amount - committed).max(0.0),
+        };
+        amount_limits.push(cap.amount);
+        log::info!("added amount_limit of {:0.5} from {}", cap.amount, cap.name);
+        if cap.amount < offer_cost {
+            run.log.add(format!(
+                "{} {} cap binds: {} {} < offer cost {:0.5}",
+                exchange.settings.name, market, cap, sell_token, offer_cost
+            ));
+        }
+    }
+
     let least_cost = eth::minimum(&amount_limits);
-    println!(
+    log::info!(
         "least_cost {:0.5} = min of {:?}",
-        least_cost, &amount_limits
+        least_cost,
+        &amount_limits
     );
     let least_qty = match askbid {
         types::AskBid::Ask => least_cost / premium_offer.quote,
         types::AskBid::Bid => least_cost,
     };
     if least_cost < offer_cost {
-        println!(
+        log::info!(
             "{} balance capped at {:0.5}. adj qty {:0.5}",
-            sell_token, least_cost, least_qty
+            sell_token,
+            least_cost,
+            least_qty
         );
     }
 
@@ -784,9 +2321,12 @@ fn build_offer(
     let minimums = exchange.api.market_minimums(&market, &exchange.settings);
     match minimums {
         Some((base_minimum, quote_minimum)) => {
-            println!(
+            log::info!(
                 "{} market minimums {} base_minimum={:?} quote_minimum={:?}",
-                exchange.settings.name, market, base_minimum, quote_minimum
+                exchange.settings.name,
+                market,
+                base_minimum,
+                quote_minimum
             );
             match base_minimum {
                 Some(minimum) => {
@@ -797,9 +2337,12 @@ fn build_offer(
                         ));
                         return Err(err);
                     } else {
-                        println!(
+                        log::info!(
                             "{} base minimum {:0.4} met with {}{}",
-                            &market, minimum, least_base, &market.base
+                            &market,
+                            minimum,
+                            least_base,
+                            &market.base
                         );
                     }
                 }
@@ -814,9 +2357,12 @@ fn build_offer(
                         ));
                         return Err(err);
                     } else {
-                        println!(
+                        log::info!(
                             "{} quote minimum {:0.4} met with {}{}",
-                            &market, minimum, least_quote, &market.quote
+                            &market,
+                            minimum,
+                            least_quote,
+                            &market.quote
                         );
                     }
                 }
@@ -824,9 +2370,10 @@ fn build_offer(
             };
         }
         None => {
-            println!(
-                "{} market minimums {} WARNING: no data",
-                exchange.settings.name, market
+            log::warn!(
+                "{} market minimums {} no data",
+                exchange.settings.name,
+                market
             );
         }
     }
@@ -838,59 +2385,109 @@ fn build_offer(
     Ok(capped_offer)
 }
 
-fn run_sheets(
-    config: &config::Config,
-    sheets: Vec<(
-        &config::Exchange,
-        types::AskBid,
-        types::Ticker,
-        f64,
-        Vec<Result<exchange::OrderSheet, Box<dyn std::error::Error>>>,
-    )>,
-) {
-    sheets
+// each exchange leg runs on its own thread. sheets within a leg stay in order.
+fn run_sheets(config: &config::Config, sheets: Vec<BookSheet>) -> Vec<report::LegRun> {
+    let legs: Vec<_> = sheets
         .into_iter()
-        .for_each(|(exchange, askbid, token, total, sheets)| {
-            sheets.into_iter().for_each(|sheet_opt| {
-                let _quiet = match sheet_opt {
-                    Ok(sheet) => run_sheet(config, sheet, exchange),
-                    Err(_e) => {
-                        println!(
-                            "order_sheet skipped {} {} {}",
-                            exchange.settings.name, askbid, token
-                        );
-                        Ok("-skipped-".to_string())
-                    }
-                };
-            });
-            if exchange.settings.has_balances {
-                if total > 0.0 {
-                    sweep(&config.wallet_private_key, &exchange, &token);
+        .map(|(exchange, askbid, token, total, sheets)| {
+            let mut ready = vec![];
+            for sheet_opt in sheets {
+                match sheet_opt {
+                    Ok(sheet) => ready.push(sheet),
+                    Err(_e) => log::info!(
+                        "order_sheet skipped {} {} {}",
+                        exchange.settings.name,
+                        askbid,
+                        token
+                    ),
                 }
-            };
-        });
+            }
+            (exchange, askbid, token, total, ready)
+        })
+        .collect();
+    let fields = log::fields(); // the arb id, into each leg's thread
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = legs
+            .into_iter()
+            .map(|(exchange, askbid, token, total, ready)| {
+                let fields = &fields;
+                let name = exchange.settings.name.clone();
+                let handle = scope.spawn(move || {
+                    log::set_fields(fields);
+                    log::set_field(log::EXCHANGE, &exchange.settings.name);
+                    log::set_field(log::LEG, &askbid.to_string().to_lowercase());
+                    let mut leg_run = report::LegRun {
+                        exchange: exchange.settings.name.clone(),
+                        askbid,
+                        filled: vec![],
+                        errors: vec![],
+                        swept: false,
+                    };
+                    for sheet in ready {
+                        match run_sheet(config, sheet, exchange) {
+                            Ok(order_id) => leg_run.filled.push(order_id),
+                            Err(e) => leg_run.errors.push(e.to_string()),
+                        }
+                    }
+                    if exchange.settings.has_balances && total > 0.0 {
+                        match submit_blocked() {
+                            Some(reason) => {
+                                leg_run.errors.push(format!("sweep refused: {}", reason))
+                            }
+                            None => match sweep(&config.wallet_private_key, exchange, &token) {
+                                Ok(transfer) => leg_run.swept = transfer.is_some(),
+                                Err(e) => leg_run.errors.push(format!("sweep {}", e)),
+                            },
+                        }
+                    }
+                    leg_run
+                });
+                (name, askbid, handle)
+            })
+            .collect();
+        // a panicked leg is an error in its report, not the end of the run
+        let runs: Vec<report::LegRun> = handles
+            .into_iter()
+            .map(|(exchange, askbid, handle)| {
+                handle.join().unwrap_or_else(|panic| {
+                    let msg = panic
+                        .downcast_ref::<&str>()
+                        .map(|msg| msg.to_string())
+                        .or_else(|| panic.downcast_ref::<String>().cloned())
+                        .unwrap_or_default();
+                    report::LegRun {
+                        exchange,
+                        askbid,
+                        filled: vec![],
+                        errors: vec![format!("leg panicked: {}", msg)],
+                        swept: false,
+                    }
+                })
+            })
+            .collect();
+        metrics::record_fills(&runs);
+        runs
+    })
 }
 
+// withdraw the exchange balance of token. None when nothing was withdrawn
 fn sweep(
     private_key: &str,
     exchange: &config::Exchange,
     token: &types::Ticker,
-) -> Option<Box<dyn std::error::Error>> {
-    println!("** Sweep {} {}", exchange.settings.name, token);
+) -> Result<Option<String>, Box<dyn std::error::Error>> {
+    log::info!("** Sweep {} {}", exchange.settings.name, token);
     let my_addr = eth::privkey_to_addr(private_key);
     let direction = exchange::TransferDirection::Withdraw;
     let balance_opt = exchange_balance(&my_addr, exchange, token);
     match balance_opt {
-        Some(balance) => match run_transfer(private_key, direction, exchange, balance, token) {
-            Ok(_tid) => None,
-            Err(e) => Some(e),
-        },
+        Some(balance) => run_transfer(private_key, direction, exchange, balance, token),
         None => {
-            println!(
+            log::info!(
                 "no balance found for {}. skipping withdraw/sweep",
                 exchange.settings.name
             );
-            None
+            Ok(None)
         }
     }
 }
@@ -907,7 +2504,7 @@ fn exchange_balance(
     match winner {
         Some(coin) => {
             let total = coin.base_total();
-            println!("{} balance {} {}", exchange.settings.name, token, total);
+            log::info!("{} balance {} {}", exchange.settings.name, token, total);
             Some(total)
         }
         None => None,
@@ -919,18 +2516,25 @@ fn run_sheet(
     sheet: exchange::OrderSheet,
     exchange: &config::Exchange,
 ) -> Result<String, Box<dyn std::error::Error>> {
-    println!("** RUN sheet {}", exchange);
+    log::info!("** RUN sheet {}", exchange);
+    if let Some(reason) = submit_blocked() {
+        return Err(exchange::ExchangeError::build_box(format!(
+            "submit refused: {}",
+            reason
+        )));
+    }
+    exchange.rate_limit.wait();
     let submit_opt = if config.trade_live {
         exchange
             .api
             .submit(&config.wallet_private_key, &exchange.settings, sheet)
     } else {
-        println!("=DEMO mode no submit placeholder-order-id");
+        log::info!("=DEMO mode no submit placeholder-order-id");
         Ok("placeholder-order-id".to_string())
     };
     match submit_opt {
         Ok(order_id) => {
-            println!("* {} ORDER ID {}", exchange.settings.name, order_id);
+            log::info!("* {} ORDER ID {}", exchange.settings.name, order_id);
             match wait_order(&exchange, &order_id) {
                 exchange::OrderState::Filled => Ok(order_id),
                 state => Err(exchange::ExchangeError::build_box(format!(
@@ -943,6 +2547,19 @@ fn run_sheet(
     }
 }
 
+// a capped exchange offer back in order terms
+fn reswap(market: &types::Market, offer: &types::Offer) -> types::Offer {
+    if market.swapped {
+        let (base_qty, quote) = offer.swap();
+        types::Offer { base_qty, quote }
+    } else {
+        types::Offer {
+            base_qty: offer.base_qty,
+            quote: offer.quote,
+        }
+    }
+}
+
 fn unswap(
     askbid: &types::AskBid,
     market: &types::Market,
@@ -965,7 +2582,7 @@ fn unswap(
         let (swap_qty, swap_price) = offer.swap();
         qty = swap_qty;
         price = swap_price;
-        println!("unswapped {:#?} {} {}@{}", askbid_align, market, qty, price);
+        log::info!("unswapped {:#?} {} {}@{}", askbid_align, market, qty, price);
     }
     // market after flip
     let exmarket = exchange::Market {
@@ -995,8 +2612,9 @@ fn wait_order(exchange: &config::Exchange, order_id: &str) -> exchange::OrderSta
     while repeat {
         repeat = match waiting_states.iter().find(|&s| *s == state) {
             Some(_s) => {
+                exchange.rate_limit.wait();
                 state = exchange.api.order_status(order_id, &exchange.settings);
-                println!("{} {} => {:?}", exchange.settings.name, order_id, state);
+                log::info!("{} {} => {:?}", exchange.settings.name, order_id, state);
                 let delay = std::time::Duration::from_secs(3);
                 std::thread::sleep(delay);
                 true
@@ -1010,7 +2628,7 @@ fn wait_order(exchange: &config::Exchange, order_id: &str) -> exchange::OrderSta
 fn exchange_coins(my_addr: &str, exchange: &config::Exchange) -> Vec<wallet::WalletCoin> {
     let mut exchange_coins = Vec::<wallet::WalletCoin>::new();
     if exchange.settings.has_balances {
-        println!("{} balance check for 0x{}", exchange.settings.name, my_addr);
+        log::info!("{} balance check for 0x{}", exchange.settings.name, my_addr);
         let balances = exchange.api.balances(&my_addr, &exchange.settings);
         for (symbol, balance) in balances {
             let exchange_coin =
@@ -1028,33 +2646,52 @@ fn etherscan_coins(
 ) -> Vec<wallet::WalletCoin> {
     let mut coins = Vec::<wallet::WalletCoin>::new();
     for coin in wallet_coins {
-        print!("{} ", coin.ticker_symbol);
         let balance = etherscan_coin(my_addr, &coin.ticker_symbol, &coin.contract, api_key);
         let eth_coin =
             wallet::WalletCoin::build(&coin.ticker_symbol, &coin.contract, &my_addr, balance);
         coins.push(eth_coin);
     }
+    let symbols: Vec<&str> = coins
+        .iter()
+        .map(|coin| coin.ticker_symbol.as_str())
+        .collect();
+    log::info!("etherscan BALANCES for {}", symbols.join(" "));
     coins
 }
 
 fn etherscan_coin(my_addr: &str, symbol: &str, contract_addr: &str, api_key: &str) -> f64 {
     let balance = etherscan::balance(my_addr, contract_addr, api_key);
-    let token = types::Ticker {
-        symbol: symbol.to_string(),
-    };
-    let escan = config::ETHERSCAN.get().unwrap();
-    let decimals = match escan.tokens.get(&token) {
+    let registry = config::TOKENS.get().unwrap();
+    let decimals = match registry.get(symbol) {
         Some(token_detail) => token_detail.decimals,
         None => 0,
     };
     eth::wei_to_eth(balance, decimals)
 }
 
-fn build_manual_order(matches: &clap::ArgMatches) -> types::Order {
+// a number argument, or an error naming it
+fn parse_arg<T: std::str::FromStr>(
+    matches: &clap::ArgMatches,
+    name: &str,
+) -> Result<Option<T>, Box<dyn std::error::Error>> {
+    match matches.value_of(name) {
+        Some(value) => match value.parse::<T>() {
+            Ok(parsed) => Ok(Some(parsed)),
+            Err(_) => Err(errors::MainError::build_box(format!(
+                "{} {:?} is not a number",
+                name, value
+            ))),
+        },
+        None => Ok(None),
+    }
+}
+
+fn build_manual_order(
+    matches: &clap::ArgMatches,
+) -> Result<types::Order, Box<dyn std::error::Error>> {
     let exchange = matches.value_of("exchange").unwrap();
     let side = matches.value_of("side").unwrap();
-    let quantity_str = matches.value_of("quantity").unwrap();
-    let quantity = quantity_str.parse::<f64>().unwrap();
+    let quantity = parse_arg::<f64>(matches, "quantity")?.unwrap();
     let base_symbol = matches.value_of("base_token").unwrap();
     let ask_base = types::Ticker {
         symbol: base_symbol.to_uppercase(),
@@ -1062,8 +2699,7 @@ fn build_manual_order(matches: &clap::ArgMatches) -> types::Order {
     let bid_base = types::Ticker {
         symbol: base_symbol.to_uppercase(),
     };
-    let price_str = matches.value_of("price").unwrap();
-    let price = price_str.parse::<f64>().unwrap();
+    let price = parse_arg::<f64>(matches, "price")?.unwrap();
     let quote_symbol = matches.value_of("quote_token").unwrap();
     let ask_quote = types::Ticker {
         symbol: quote_symbol.to_uppercase(),
@@ -1072,7 +2708,7 @@ fn build_manual_order(matches: &clap::ArgMatches) -> types::Order {
         symbol: quote_symbol.to_uppercase(),
     };
     let swapped = matches.is_present("swapped");
-    println!(
+    log::info!(
         "** MANUAL {} {} {}{}@{}{}{}",
         exchange,
         side,
@@ -1138,10 +2774,10 @@ fn build_manual_order(matches: &clap::ArgMatches) -> types::Order {
     match side {
         "buy" => asks.books[0].offers.push(offer),
         "sell" => bids.books[0].offers.push(offer),
-        unknown => println!("pick buy/sell: {}", unknown),
+        unknown => log::warn!("pick buy/sell: {}", unknown),
     }
 
-    types::Order {
+    Ok(types::Order {
         id: "#manual-id".to_string(),
         date: time::now_string(),
         pair: pair,
@@ -1153,5 +2789,6 @@ fn build_manual_order(matches: &clap::ArgMatches) -> types::Order {
         network_usd: 0.0,
         ask_books: asks,
         bid_books: bids,
-    }
+        ttl_secs: parse_arg::<u64>(matches, "ttl")?,
+    })
 }
@@ -0,0 +1,220 @@
+use crate::gas;
+use crate::lifecycle;
+use crate::report;
+use once_cell::sync::Lazy;
+use prometheus::{
+    CounterVec, Gauge, GaugeVec, HistogramVec, IntCounter, IntCounterVec, register_counter_vec,
+    register_gauge, register_gauge_vec, register_histogram_vec, register_int_counter,
+    register_int_counter_vec,
+};
+use std::time::Duration;
+
+// exported on GET /metrics by the daemon's control api
+
+pub static ORDERS_RECEIVED: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!("yith_orders_received_total", "orders taken for execution").unwrap()
+});
+
+pub static ORDERS_EXECUTED: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!("yith_orders_executed_total", "orders run to done").unwrap()
+});
+
+pub static ORDERS_ABORTED: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "yith_orders_aborted_total",
+        "orders aborted or rejected",
+        &["reason"]
+    )
+    .unwrap()
+});
+
+pub static FILLS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!("yith_fills_total", "sheets filled", &["exchange", "askbid"]).unwrap()
+});
+
+pub static HTTP_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "yith_http_request_duration_seconds",
+        "outgoing http request latency",
+        &["host"]
+    )
+    .unwrap()
+});
+
+pub static HTTP_REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "yith_http_requests_total",
+        "outgoing http requests by response status",
+        &["host", "status"]
+    )
+    .unwrap()
+});
+
+pub static RPC_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "yith_rpc_duration_seconds",
+        "geth json-rpc latency",
+        &["method"]
+    )
+    .unwrap()
+});
+
+// the planned limits, not receipt gasUsed. an upper bound on gas spent
+pub static GAS_LIMIT: Lazy<CounterVec> = Lazy::new(|| {
+    register_counter_vec!(
+        "yith_gas_limit_total",
+        "gas limit of the on-chain steps in submitted legs",
+        &["exchange"]
+    )
+    .unwrap()
+});
+
+pub static BALANCE: Lazy<GaugeVec> = Lazy::new(|| {
+    register_gauge_vec!(
+        "yith_wallet_balance",
+        "token balance at the last wallet scan",
+        &["token", "source"]
+    )
+    .unwrap()
+});
+
+// wallet usd value after each order minus before, at the prices of the
+// moment. held tokens moving in price count too, not only the trades.
+pub static REALIZED_PNL_USD: Lazy<Gauge> = Lazy::new(|| {
+    register_gauge!(
+        "yith_realized_pnl_usd",
+        "wallet usd value change over the orders run by this process, price moves included"
+    )
+    .unwrap()
+});
+
+// free text abort reasons folded into a few label values
+pub fn abort_label(reason: &str) -> &'static str {
+    let reason = reason.to_lowercase();
+    let labels = [
+        ("halted", "halted"),
+        ("lease", "lease"),
+        ("expired", "expired"),
+        ("invalid", "invalid"),
+        ("slippage", "slippage"), // recheck reasons also mention profit
+        ("profit", "profit"),
+        ("cannot be priced", "gas"),
+        ("good total", "no_sheets"),
+        ("balance dropped", "breaker"),
+        ("incomplete", "leg"),
+    ];
+    labels
+        .iter()
+        .find(|(word, _label)| reason.contains(word))
+        .map(|(_word, label)| *label)
+        .unwrap_or("other")
+}
+
+pub fn aborted(reason: &str) {
+    ORDERS_ABORTED
+        .with_label_values(&[abort_label(reason)])
+        .inc()
+}
+
+pub fn record_fills(runs: &[report::LegRun]) {
+    for run in runs {
+        FILLS
+            .with_label_values(&[&run.exchange, &run.askbid.to_string().to_lowercase()])
+            .inc_by(run.filled.len() as u64)
+    }
+}
+
+pub fn gas_limit(plan: &gas::Plan, askbid: crate::types::AskBid) {
+    for (_side, step) in plan.steps.iter().filter(|(side, _step)| *side == askbid) {
+        GAS_LIMIT
+            .with_label_values(&[&step.exchange])
+            .inc_by(step.gas as f64)
+    }
+}
+
+pub fn http_request(host: &str, status: &str, elapsed: Duration) {
+    HTTP_SECONDS
+        .with_label_values(&[host])
+        .observe(elapsed.as_secs_f64());
+    HTTP_REQUESTS.with_label_values(&[host, status]).inc()
+}
+
+pub fn rpc_call(method: &str, elapsed: Duration) {
+    RPC_SECONDS
+        .with_label_values(&[method])
+        .observe(elapsed.as_secs_f64())
+}
+
+// counts executed and aborted orders from their lifecycle
+pub struct MetricsSubscriber {}
+
+impl lifecycle::Subscriber for MetricsSubscriber {
+    fn notify(&mut self, event: &lifecycle::Event) {
+        match event.to {
+            lifecycle::State::Done => ORDERS_EXECUTED.inc(),
+            lifecycle::State::Aborted => aborted(&event.detail),
+            _ => (),
+        }
+    }
+}
+
+// the prometheus text format
+pub fn render() -> String {
+    prometheus::TextEncoder::new()
+        .encode_to_string(&prometheus::gather())
+        .unwrap_or_else(|e| format!("# {}\n", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_abort_label() {
+        assert_eq!(
+            abort_label("submit aborted! halted: deploy (2020)"),
+            "halted"
+        );
+        assert_eq!(abort_label("order expired! order is 61.0s old"), "expired");
+        assert_eq!(
+            abort_label("submit aborted! expected profit after fees and gas -1.00 usd"),
+            "profit"
+        );
+        assert_eq!(
+            abort_label("submit aborted! 0 good total asks"),
+            "no_sheets"
+        );
+        assert_eq!(
+            abort_label("submit aborted! gas cannot be priced. network_usd 0 quote_usd 200"),
+            "gas"
+        );
+        assert_eq!(
+            abort_label("bid leg incomplete! idex bid filled [] errors [\"x\"]"),
+            "leg"
+        );
+        assert_eq!(abort_label("exchange exploded"), "other");
+    }
+
+    #[test]
+    fn test_render() {
+        aborted("order expired! order is 61.0s old");
+        record_fills(&[report::LegRun {
+            exchange: "idex".to_string(),
+            askbid: crate::types::AskBid::Ask,
+            filled: vec!["0x1".to_string()],
+            errors: vec![],
+            swept: false,
+        }]);
+        let text = render();
+        assert!(
+            text.contains("yith_orders_aborted_total{reason=\"expired\"}"),
+            "{}",
+            text
+        );
+        assert!(
+            text.contains("yith_fills_total{askbid=\"ask\",exchange=\"idex\"} 1"),
+            "{}",
+            text
+        );
+    }
+}
@@ -0,0 +1,429 @@
+use crate::config;
+use crate::email;
+use crate::http;
+use crate::log;
+use crate::time;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
+use std::sync::Mutex;
+
+pub static DEFAULT_DIGEST_SECS: u64 = 3600;
+
+// what a message is about. failures and halts go out at once, fills are
+// batched into a digest.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Event {
+    Fill,    // an order ran to the end
+    Failure, // an aborted order or an unhedged position
+    Halt,    // the circuit breaker tripped
+}
+
+impl Event {
+    pub fn urgent(&self) -> bool {
+        *self != Event::Fill
+    }
+}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let word = match self {
+            Event::Fill => "fill",
+            Event::Failure => "failure",
+            Event::Halt => "halt",
+        };
+        write!(f, "{}", word)
+    }
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq)]
+pub struct Message {
+    pub event: Event,
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(Debug)]
+pub struct NotifyError {
+    pub msg: String,
+}
+
+impl NotifyError {
+    pub fn wrap<E: fmt::Display>(e: E) -> NotifyError {
+        NotifyError { msg: e.to_string() }
+    }
+}
+
+impl std::error::Error for NotifyError {}
+
+impl fmt::Display for NotifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.msg)
+    }
+}
+
+pub trait Notifier: fmt::Display {
+    fn send(&self, message: &Message) -> Result<(), NotifyError>;
+}
+
+// posts the message as json: event, subject, body and time
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Webhook {
+    pub url: String,
+}
+
+impl Notifier for Webhook {
+    fn send(&self, message: &Message) -> Result<(), NotifyError> {
+        let payload = json!({
+            "event": message.event,
+            "subject": message.subject,
+            "body": message.body,
+            "time": time::now_string(),
+        });
+        post(&self.url, &payload)
+    }
+}
+
+impl fmt::Display for Webhook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "webhook {}", self.url)
+    }
+}
+
+// slack incoming webhooks and matrix hookshot both take {"text": ...}
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Chat {
+    pub url: String,
+}
+
+impl Notifier for Chat {
+    fn send(&self, message: &Message) -> Result<(), NotifyError> {
+        let text = format!("*{}*\n{}", message.subject, message.body);
+        post(&self.url, &json!({ "text": text }))
+    }
+}
+
+impl fmt::Display for Chat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "chat {}", self.url)
+    }
+}
+
+fn post(url: &str, payload: &serde_json::Value) -> Result<(), NotifyError> {
+    let client = http::LoggingClient::new(reqwest::blocking::Client::new());
+    let resp = client
+        .post(url)
+        .json(payload)
+        .send()
+        .map_err(NotifyError::wrap)?;
+    if resp.status().is_success() {
+        Ok(())
+    } else {
+        Err(NotifyError {
+            msg: format!("{} {}", url, resp.status()),
+        })
+    }
+}
+
+// appends one json line per message
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct File {
+    pub path: String,
+}
+
+impl Notifier for File {
+    fn send(&self, message: &Message) -> Result<(), NotifyError> {
+        let line = json!({
+            "time": time::now_string(),
+            "event": message.event,
+            "subject": message.subject,
+            "body": message.body,
+        });
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(NotifyError::wrap)?;
+        writeln!(file, "{}", line).map_err(NotifyError::wrap)
+    }
+}
+
+impl fmt::Display for File {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "file {}", self.path)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Sink {
+    Smtp(email::Smtp),
+    Webhook(Webhook),
+    Chat(Chat),
+    File(File),
+}
+
+impl Sink {
+    pub fn notifier(&self) -> &dyn Notifier {
+        match self {
+            Sink::Smtp(smtp) => smtp,
+            Sink::Webhook(webhook) => webhook,
+            Sink::Chat(chat) => chat,
+            Sink::File(file) => file,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SinkSettings {
+    #[serde(flatten)]
+    pub sink: Sink,
+    pub events: Option<Vec<Event>>, // default all
+}
+
+impl SinkSettings {
+    pub fn routes(&self, event: Event) -> bool {
+        match &self.events {
+            Some(events) => events.contains(&event),
+            None => true,
+        }
+    }
+}
+
+// subject and body with {name} placeholders
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Template {
+    pub subject: String,
+    pub body: String,
+}
+
+impl Template {
+    pub fn default_for(event: Event) -> Template {
+        let (subject, body) = match event {
+            Event::Fill => ("{pair}", "order #{order_id} {pair} {cost} {profit}\n{log}"),
+            Event::Failure => (
+                "{reason}",
+                "order #{order_id} {pair} {cost} {profit}\n{reason}\n{log}",
+            ),
+            Event::Halt => ("{reason}", "{reason}"),
+        };
+        Template {
+            subject: subject.to_string(),
+            body: body.to_string(),
+        }
+    }
+
+    pub fn render(&self, event: Event, values: &[(&str, String)]) -> Message {
+        Message {
+            event,
+            subject: fill(&self.subject, values),
+            body: fill(&self.body, values),
+        }
+    }
+}
+
+fn fill(template: &str, values: &[(&str, String)]) -> String {
+    values
+        .iter()
+        .fold(template.to_string(), |text, (name, value)| {
+            text.replace(&format!("{{{}}}", name), value)
+        })
+}
+
+// the config.yaml notify section
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+pub struct Settings {
+    #[serde(default)]
+    pub sinks: Vec<SinkSettings>,
+    pub digest_secs: Option<u64>, // fills batched this long. 0 sends each. default 3600
+    #[serde(default)]
+    pub templates: HashMap<Event, Template>, // overrides by event
+}
+
+impl Settings {
+    // the notify section plus the plain email address, as a localhost smtp sink
+    pub fn from_config(config: &config::Config) -> Settings {
+        let mut settings = config.notify.clone().unwrap_or_default();
+        if let Some(to) = &config.email {
+            settings.sinks.push(SinkSettings {
+                sink: Sink::Smtp(email::Smtp::localhost(to)),
+                events: None,
+            })
+        }
+        settings
+    }
+
+    pub fn template(&self, event: Event) -> Template {
+        self.templates
+            .get(&event)
+            .cloned()
+            .unwrap_or_else(|| Template::default_for(event))
+    }
+
+    pub fn message(&self, event: Event, values: &[(&str, String)]) -> Message {
+        self.template(event).render(event, values)
+    }
+}
+
+struct Digest {
+    since: u64, // epoch seconds of the oldest queued message
+    messages: Vec<Message>,
+}
+
+static DIGEST: Mutex<Digest> = Mutex::new(Digest {
+    since: 0,
+    messages: vec![],
+});
+
+// urgent events go to their sinks now, fills wait for the digest
+pub fn send(settings: &Settings, message: Message) {
+    if settings.sinks.is_empty() {
+        return;
+    }
+    let digest_secs = settings.digest_secs.unwrap_or(DEFAULT_DIGEST_SECS);
+    if message.event.urgent() || digest_secs == 0 {
+        deliver(settings, &message)
+    } else {
+        {
+            let mut digest = DIGEST.lock().unwrap();
+            if digest.messages.is_empty() {
+                digest.since = time::since_epoch().as_secs()
+            }
+            digest.messages.push(message);
+        }
+        flush(settings, false)
+    }
+}
+
+// send the digest once its oldest message has waited digest_secs, or now
+// when forced (a one-off run exiting)
+pub fn flush(settings: &Settings, force: bool) {
+    let digest_secs = settings.digest_secs.unwrap_or(DEFAULT_DIGEST_SECS);
+    let messages = {
+        let mut digest = DIGEST.lock().unwrap();
+        let due = time::since_epoch().as_secs() >= digest.since + digest_secs;
+        if digest.messages.is_empty() || !(force || due) {
+            return;
+        }
+        std::mem::take(&mut digest.messages)
+    };
+    deliver(settings, &digest(&messages))
+}
+
+pub fn digest(messages: &[Message]) -> Message {
+    let body = messages
+        .iter()
+        .map(|message| format!("{}\n{}", message.subject, message.body))
+        .collect::<Vec<String>>()
+        .join("\n\n");
+    Message {
+        event: Event::Fill,
+        subject: format!("yith digest: {} orders", messages.len()),
+        body,
+    }
+}
+
+fn deliver(settings: &Settings, message: &Message) {
+    for sink in settings
+        .sinks
+        .iter()
+        .filter(|sink| sink.routes(message.event))
+    {
+        let notifier = sink.sink.notifier();
+        match notifier.send(message) {
+            Ok(()) => log::info!(
+                "notify {} {} sent: {}",
+                notifier,
+                message.event,
+                message.subject
+            ),
+            Err(e) => log::warn!("notify {} {} failed: {}", notifier, message.event, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings() {
+        let yaml = r#"
+sinks:
+  - kind: smtp
+    to: ops@example.com
+    host: mail.example.com
+    tls: starttls
+    events: [failure, halt]
+  - kind: chat
+    url: https://hooks.slack.com/services/x
+  - kind: file
+    path: notify.log
+digest_secs: 600
+templates:
+  halt:
+    subject: "HALT {reason}"
+    body: "{reason}"
+"#;
+        let settings: Settings = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!(settings.sinks.len(), 3);
+        assert!(!settings.sinks[0].routes(Event::Fill));
+        assert!(settings.sinks[0].routes(Event::Halt));
+        assert!(settings.sinks[1].routes(Event::Fill));
+        assert_eq!(
+            settings.sinks[0].sink.notifier().to_string(),
+            "smtp ops@example.com"
+        );
+        let halt = settings.message(Event::Halt, &[("reason", "3 failures".to_string())]);
+        assert_eq!(halt.subject, "HALT 3 failures");
+    }
+
+    #[test]
+    fn test_templates() {
+        let values = [
+            ("order_id", "abc".to_string()),
+            ("pair", "DAI/ETH".to_string()),
+            ("cost", "1.0000".to_string()),
+            ("profit", "0.0100".to_string()),
+            ("log", "ask leg filled".to_string()),
+        ];
+        let fill = Template::default_for(Event::Fill).render(Event::Fill, &values);
+        assert_eq!(fill.subject, "DAI/ETH");
+        assert_eq!(
+            fill.body,
+            "order #abc DAI/ETH 1.0000 0.0100\nask leg filled"
+        );
+        let digest = digest(&[fill.clone(), fill]);
+        assert_eq!(digest.subject, "yith digest: 2 orders");
+        assert!(digest.body.starts_with("DAI/ETH\norder #abc"));
+    }
+
+    #[test]
+    fn test_file_sink() {
+        let path = std::env::temp_dir().join(format!("yith-notify-{}.log", std::process::id()));
+        let settings = Settings {
+            sinks: vec![SinkSettings {
+                sink: Sink::File(File {
+                    path: path.to_str().unwrap().to_string(),
+                }),
+                events: None,
+            }],
+            digest_secs: Some(3600),
+            templates: HashMap::new(),
+        };
+        let halt = settings.message(Event::Halt, &[("reason", "deploy".to_string())]);
+        send(&settings, halt);
+        let fill = settings.message(Event::Fill, &[("pair", "DAI/ETH".to_string())]);
+        send(&settings, fill);
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(written.lines().count(), 1); // the fill waits for the digest
+        flush(&settings, true);
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(written.lines().count(), 2);
+        assert!(written.contains("yith digest: 1 orders"));
+        let _ = std::fs::remove_file(path);
+    }
+}
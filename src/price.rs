@@ -1,3 +1,4 @@
+use crate::http;
 use crate::http_info;
 use reqwest::header;
 use serde::{Deserialize, Serialize};
@@ -22,12 +23,12 @@ pub struct PriceResponse {
 
 pub struct CoinGecko {
     coins: Vec<Coin>,
-    client: reqwest::blocking::Client,
+    client: http::LoggingClient,
 }
 
 impl CoinGecko {
     pub fn new() -> CoinGecko {
-        let client = build_http_client();
+        let client = http::LoggingClient::new(build_http_client());
         let coins_json = coins_cache(&client);
         let coins = serde_json::from_str::<Vec<Coin>>(&coins_json).unwrap();
         CoinGecko {
@@ -77,14 +78,14 @@ impl CoinGecko {
     }
 }
 
-pub fn coins_json(client: &reqwest::blocking::Client) -> String {
+pub fn coins_json(client: &http::LoggingClient) -> String {
     let url = format!("{}/coins/list", COIN_GECKO_API);
-    println!("{}", url);
-    let json = client.get(&url).send().unwrap().text().unwrap();
-    json
+    log::info!("{}", url);
+    let resp = client.get(&url).send().unwrap();
+    resp.text().unwrap().to_string()
 }
 
-pub fn coins_cache(client: &reqwest::blocking::Client) -> String {
+pub fn coins_cache(client: &http::LoggingClient) -> String {
     let filename = "notes/coingecko-tokens.json";
     let file_ok = fs::read_to_string(filename);
     match file_ok {
@@ -0,0 +1,157 @@
+use crate::fees;
+use crate::types;
+use crate::wallet;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+pub static DEFAULT_MAX_SLIPPAGE: f64 = 0.01;
+
+// what to do when the bid leg would lock in a loss beyond max_slippage
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Policy {
+    #[default]
+    Hold, // skip the bid leg, hand the bought tokens to the unwind policies
+    Sell, // sell them through the bid leg anyway, taking the loss
+}
+
+impl fmt::Display for Policy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let word = match self {
+            Policy::Hold => "hold",
+            Policy::Sell => "sell",
+        };
+        write!(f, "{}", word)
+    }
+}
+
+// pair balances across the wallet and exchanges
+#[derive(Debug)]
+pub struct Holdings {
+    pub base: f64,
+    pub quote: f64,
+}
+
+impl Holdings {
+    pub fn build(wallet: &wallet::Wallet, pair: &types::Pair) -> Holdings {
+        Holdings {
+            base: wallet.held(&pair.base),
+            quote: wallet.held(&pair.quote),
+        }
+    }
+}
+
+// what the ask leg actually did, from the balance change. quote spent
+// includes any gas paid when the quote token is ETH.
+#[derive(Debug, PartialEq)]
+pub struct Fills {
+    pub bought: f64,
+    pub spent: f64,
+}
+
+impl Fills {
+    pub fn build(before: &Holdings, after: &Holdings) -> Fills {
+        Fills {
+            bought: (after.base - before.base).max(0.0),
+            spent: (before.quote - after.quote).max(0.0),
+        }
+    }
+
+    pub fn price(&self) -> Option<f64> {
+        if self.bought > 0.0 {
+            Some(self.spent / self.bought)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Recheck {
+    pub planned_price: f64,
+    pub realized_price: f64,
+    pub slippage: f64,
+    pub profit: f64, // real bid proceeds minus realized ask cost, in quote
+    pub tolerance: f64,
+}
+
+impl Recheck {
+    // compare the filled asks with the plan and the rebuilt bids
+    pub fn build(fills: &Fills, ledger: &fees::Ledger, max_slippage: f64) -> Option<Recheck> {
+        let planned_qty: f64 = ledger
+            .entries
+            .iter()
+            .filter(|e| e.askbid == types::AskBid::Ask)
+            .map(|e| e.offer.base_qty)
+            .sum();
+        let realized_price = fills.price()?;
+        if planned_qty <= 0.0 {
+            return None;
+        }
+        let planned_price = ledger.ask_spent() / planned_qty;
+        Some(Recheck {
+            planned_price,
+            realized_price,
+            slippage: realized_price / planned_price - 1.0,
+            profit: ledger.bid_received() - fills.spent,
+            tolerance: fills.spent * max_slippage,
+        })
+    }
+
+    pub fn ok(&self) -> bool {
+        self.profit >= -self.tolerance
+    }
+}
+
+impl fmt::Display for Recheck {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "recheck ask price planned {:0.8} realized {:0.8} slippage {:0.2}% bid profit {:0.5} (tolerance {:0.5})",
+            self.planned_price,
+            self.realized_price,
+            self.slippage * 100.0,
+            self.profit,
+            self.tolerance
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(askbid: types::AskBid, base_qty: f64, quote: f64) -> fees::Entry {
+        fees::Entry {
+            askbid,
+            exchange: "idex".to_string(),
+            offer: types::Offer { base_qty, quote },
+            fee: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_recheck() {
+        let before = Holdings {
+            base: 0.0,
+            quote: 2.0,
+        };
+        let after = Holdings {
+            base: 100.0,
+            quote: 0.95,
+        };
+        let fills = Fills::build(&before, &after);
+        assert_eq!(fills.price(), Some(0.0105));
+        let mut ledger = fees::Ledger::new();
+        ledger.add(entry(types::AskBid::Ask, 100.0, 0.01));
+        ledger.add(entry(types::AskBid::Bid, 100.0, 0.0104));
+        let recheck = Recheck::build(&fills, &ledger, 0.01).unwrap();
+        assert!((recheck.slippage - 0.05).abs() < 1e-9);
+        assert!((recheck.profit + 0.01).abs() < 1e-9); // 1.04 - 1.05
+        assert!(recheck.ok()); // within 0.0105 tolerance
+        let tight = Recheck::build(&fills, &ledger, 0.005).unwrap();
+        assert!(!tight.ok());
+        let unfilled = Fills::build(&before, &before);
+        assert!(Recheck::build(&unfilled, &ledger, 0.01).is_none());
+    }
+}
@@ -1,9 +1,15 @@
+use crate::lifecycle;
 use crate::time;
 use crate::types;
 pub use redis::Commands; // re-export
 
 pub type Connection = redis::Connection;
 pub type Error = redis::RedisError;
+pub type PubSub<'a> = redis::PubSub<'a>;
+
+pub static ORDERS_CHANNEL: &str = "orders";
+pub static RETRY_MS: u64 = 1000; // reconnect backoff, doubling up to RETRY_MAX_MS
+pub static RETRY_MAX_MS: u64 = 60_000;
 
 pub struct Redis<'a> {
     pub url: &'a str,
@@ -17,47 +23,60 @@ impl Redis<'_> {
         Redis { url: url, con: con }
     }
 
-    pub fn rd_next(&mut self) -> types::Order {
-        let inplay_exists = self.rd_exists("inplay");
-        let arb_id = match inplay_exists {
-            true => {
-                println!("active order found!");
-                self.rd_inplay().unwrap()
-            }
-            false => {
-                println!("no active order. waiting for order. {}", time::now_string());
-                self.rd_next_order().unwrap()
-            }
+    // like new, for callers that retry a failed connect
+    pub fn connect(url: &str) -> Result<Redis<'_>, Error> {
+        let client = redis::Client::open(url)?;
+        Ok(Redis {
+            url,
+            con: client.get_connection()?,
+        })
+    }
+
+    pub fn rd_next(&mut self) -> Result<types::Order, Error> {
+        let arb_id = if self.rd_exists("inplay")? {
+            log::info!("active order found!");
+            self.rd_inplay()?
+        } else {
+            log::info!("no active order. waiting for order. {}", time::now_string());
+            self.rd_next_order()?
         };
-        self.rd_order(arb_id).unwrap()
+        self.rd_order(arb_id)
     }
 
     pub fn rd_next_order(&self) -> Result<String, Error> {
         let client = redis::Client::open(self.url)?;
         let mut con = client.get_connection()?;
-        let mut ps = rdsub(&mut con, "orders");
+        let mut ps = rdsub(&mut con, ORDERS_CHANNEL);
 
         let msg = ps.get_message()?;
         let new_id: String = msg.get_payload()?;
-        println!("new Order {:#?}", new_id);
+        log::info!("new Order {:#?}", new_id);
         Ok(new_id)
     }
 
     pub fn rd_order(&mut self, arb_id: String) -> Result<types::Order, Error> {
         let hkey = format!("arb:{}", arb_id);
         let json: String = self.con.hget(&hkey, "json")?;
-        let order: types::Order = serde_yaml::from_str(&json).unwrap();
-        Ok(order)
+        types::Order::parse(&json, false)
+            .map_err(|e| Error::from((redis::ErrorKind::TypeError, "bad order json", e.msg)))
     }
 
-    pub fn rd_exists(&mut self, key: &str) -> bool {
-        self.con.exists(key).unwrap()
+    pub fn rd_exists(&mut self, key: &str) -> Result<bool, Error> {
+        self.con.exists(key)
     }
 
     pub fn rd_inplay(&mut self) -> Result<String, Error> {
         let inplay: String = self.con.get("inplay")?;
         Ok(inplay)
     }
+
+    pub fn rd_set_inplay(&mut self, arb_id: &str) -> Result<(), Error> {
+        self.con.set("inplay", arb_id)
+    }
+
+    pub fn rd_clear_inplay(&mut self) -> Result<(), Error> {
+        self.con.del("inplay")
+    }
 }
 
 pub fn rdsub<'a>(con: &'a mut Connection, channel: &str) -> redis::PubSub<'a> {
@@ -65,3 +84,98 @@ pub fn rdsub<'a>(con: &'a mut Connection, channel: &str) -> redis::PubSub<'a> {
     let _ = ps.subscribe(channel);
     ps
 }
+
+pub fn connect(url: &str) -> Result<Connection, Error> {
+    redis::Client::open(url)?.get_connection()
+}
+
+// the orders channel for as long as the subscription lives. ids published
+// while an order runs wait on the connection until the next wait_order.
+pub fn subscribe_orders(con: &mut Connection) -> Result<PubSub<'_>, Error> {
+    let mut ps = con.as_pubsub();
+    ps.subscribe(ORDERS_CHANNEL)?;
+    ps.set_read_timeout(Some(std::time::Duration::from_secs(1)))?;
+    Ok(ps)
+}
+
+// the next order id published, or None once stop() is true
+pub fn wait_order(orders: &mut PubSub<'_>, stop: fn() -> bool) -> Result<Option<String>, Error> {
+    loop {
+        match orders.get_message() {
+            Ok(msg) => {
+                let new_id: String = msg.get_payload()?;
+                log::info!("new Order {:#?}", new_id);
+                return Ok(Some(new_id));
+            }
+            Err(e) if e.is_timeout() => {
+                if stop() {
+                    return Ok(None);
+                }
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// a refused, dropped or timed out connection, worth reconnecting after
+pub fn transient(e: &Error) -> bool {
+    e.is_io_error() || e.is_connection_refusal() || e.is_connection_dropped() || e.is_timeout()
+}
+
+pub static RESULTS_CHANNEL: &str = "results";
+
+// status written back to arb:{id} and published on the results channel
+pub struct Status {
+    con: Connection,
+}
+
+impl Status {
+    pub fn new(url: &str) -> Result<Status, Error> {
+        let client = redis::Client::open(url)?;
+        Ok(Status {
+            con: client.get_connection()?,
+        })
+    }
+
+    pub fn write(&mut self, arb_id: &str, fields: &[(&str, String)]) -> Result<(), Error> {
+        let hkey = format!("arb:{}", arb_id);
+        self.con.hset_multiple(&hkey, fields)
+    }
+
+    pub fn publish(&mut self, message: &str) -> Result<(), Error> {
+        self.con.publish(RESULTS_CHANNEL, message)
+    }
+}
+
+// lifecycle transitions into arb:{id} fields and the results channel
+pub struct StatusSubscriber {
+    pub status: Status,
+}
+
+impl StatusSubscriber {
+    fn update(&mut self, event: &lifecycle::Event) -> Result<(), Error> {
+        let mut fields = vec![
+            ("state", event.to.to_string()),
+            ("updated", event.time.clone()),
+        ];
+        if event.from == lifecycle::State::Received {
+            fields.push(("started", event.time.clone()))
+        }
+        if event.to.is_terminal() {
+            fields.push(("finished", event.time.clone()))
+        }
+        if event.to == lifecycle::State::Aborted || event.to == lifecycle::State::Unwound {
+            fields.push(("error", event.detail.clone()))
+        }
+        self.status.write(&event.order_id, &fields)?;
+        self.status.publish(&serde_json::to_string(event).unwrap())
+    }
+}
+
+impl lifecycle::Subscriber for StatusSubscriber {
+    fn notify(&mut self, event: &lifecycle::Event) {
+        if let Err(e) = self.update(event) {
+            log::warn!("redis status for {} not written: {}", event.order_id, e)
+        }
+    }
+}
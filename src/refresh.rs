@@ -0,0 +1,107 @@
+use crate::exchange;
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::error;
+use std::fmt;
+use std::fs;
+
+#[derive(Debug, PartialEq)]
+pub enum Change {
+    Added(String),
+    Removed(String),
+    Changed(String),
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::Added(key) => write!(f, "+ {}", key),
+            Change::Removed(key) => write!(f, "- {}", key),
+            Change::Changed(key) => write!(f, "~ {}", key),
+        }
+    }
+}
+
+// compare a fetched file against the one on disk, writing it unless dryrun
+pub fn apply(
+    file: &exchange::MetadataFile,
+    dryrun: bool,
+) -> Result<Vec<Change>, Box<dyn error::Error>> {
+    let current = fs::read_to_string(&file.filename).unwrap_or_default();
+    let changes = diff(&current, &file.json)?;
+    if !dryrun && !changes.is_empty() {
+        fs::write(&file.filename, format!("{}\n", file.json))?;
+    }
+    Ok(changes)
+}
+
+// entry level diff of two json documents. objects compare by key,
+// arrays by each element's id/name/symbol/address field.
+pub fn diff(old: &str, new: &str) -> Result<Vec<Change>, Box<dyn error::Error>> {
+    let old_entries = if old.trim().is_empty() {
+        BTreeMap::new()
+    } else {
+        entries(serde_json::from_str::<Value>(old)?)
+    };
+    let new_entries = entries(serde_json::from_str::<Value>(new)?);
+    let mut changes = vec![];
+    for (key, value) in &old_entries {
+        match new_entries.get(key) {
+            Some(new_value) if new_value != value => changes.push(Change::Changed(key.clone())),
+            Some(_) => (),
+            None => changes.push(Change::Removed(key.clone())),
+        }
+    }
+    for key in new_entries.keys() {
+        if !old_entries.contains_key(key) {
+            changes.push(Change::Added(key.clone()))
+        }
+    }
+    Ok(changes)
+}
+
+fn entries(doc: Value) -> BTreeMap<String, Value> {
+    match doc {
+        Value::Object(map) => map.into_iter().collect(),
+        Value::Array(list) => list.into_iter().map(|v| (entry_key(&v), v)).collect(),
+        other => vec![("".to_string(), other)].into_iter().collect(),
+    }
+}
+
+fn entry_key(value: &Value) -> String {
+    ["id", "name", "symbol", "address"]
+        .iter()
+        .find_map(|field| value.get(field).and_then(|v| v.as_str()))
+        .map(|key| key.to_string())
+        .unwrap_or_else(|| value.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_objects() {
+        let old = r#"{"DAI": {"decimals": 18}, "OLD": {"decimals": 8}}"#;
+        let new = r#"{"DAI": {"decimals": 6}, "NEW": {"decimals": 8}}"#;
+        let changes = diff(old, new).unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                Change::Changed("DAI".to_string()),
+                Change::Removed("OLD".to_string()),
+                Change::Added("NEW".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_arrays() {
+        let old = r#"[{"name": "SWTH_ETH", "precision": 8}]"#;
+        let new = r#"[{"name": "SWTH_ETH", "precision": 8}, {"name": "DAI_ETH", "precision": 4}]"#;
+        let changes = diff(old, new).unwrap();
+        assert_eq!(changes, vec![Change::Added("DAI_ETH".to_string())]);
+        assert_eq!(diff("", "[]").unwrap(), vec![]);
+        assert_eq!(diff(new, new).unwrap(), vec![]);
+    }
+}
@@ -0,0 +1,978 @@
+use crate::exchange;
+use crate::history;
+use crate::log;
+use crate::metrics;
+use crate::time;
+use crate::types;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    Csv,
+    Table,
+}
+
+impl Format {
+    pub fn read(format: &str) -> Option<Self> {
+        match format {
+            "json" => Some(Self::Json),
+            "csv" => Some(Self::Csv),
+            "table" => Some(Self::Table),
+            _ => None,
+        }
+    }
+}
+
+// a report that flattens into csv rows
+pub trait Tabular {
+    fn headers() -> Vec<&'static str>;
+    fn rows(&self) -> Vec<Vec<String>>;
+}
+
+pub fn render<T: Serialize + Tabular>(format: Format, report: &T) -> Option<String> {
+    match format {
+        Format::Json => Some(serde_json::to_string(report).unwrap()),
+        Format::Csv => Some(csv(report)),
+        Format::Table => None, // human output is printed as the command runs
+    }
+}
+
+pub fn csv<T: Tabular>(report: &T) -> String {
+    let mut lines = vec![T::headers().join(",")];
+    for row in report.rows() {
+        let fields: Vec<String> = row.iter().map(|f| csv_field(f)).collect();
+        lines.push(fields.join(","));
+    }
+    lines.join("\n")
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains(',') || field.contains('"') || field.contains('\n') {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct BalanceReport {
+    pub time: String,
+    pub quote_symbol: String,
+    pub coins: Vec<CoinReport>,
+    pub subtotals: BTreeMap<String, f64>,
+    pub total: f64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CoinReport {
+    pub source: String,
+    pub symbol: String,
+    pub contract: String,
+    pub qty: f64,
+    pub price: f64,
+    pub value: f64,
+}
+
+impl Tabular for BalanceReport {
+    fn headers() -> Vec<&'static str> {
+        vec!["source", "symbol", "contract", "qty", "price", "value"]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.coins
+            .iter()
+            .map(|c| {
+                vec![
+                    c.source.clone(),
+                    c.symbol.clone(),
+                    c.contract.clone(),
+                    c.qty.to_string(),
+                    c.price.to_string(),
+                    c.value.to_string(),
+                ]
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct OrdersReport {
+    pub orders: Vec<OrderReport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OrderReport {
+    pub exchange: String,
+    pub id: String,
+    pub state: exchange::OrderState,
+    pub side: exchange::BuySell,
+    pub market: String,
+    pub base_qty: f64,
+    pub quote: f64,
+    pub create_date: String,
+}
+
+impl OrderReport {
+    pub fn build(exchange_name: &str, order: exchange::Order) -> OrderReport {
+        OrderReport {
+            exchange: exchange_name.to_string(),
+            id: order.id,
+            state: order.state,
+            side: order.side,
+            market: order.market,
+            base_qty: order.base_qty,
+            quote: order.quote,
+            create_date: order.create_date,
+        }
+    }
+}
+
+impl Tabular for OrdersReport {
+    fn headers() -> Vec<&'static str> {
+        vec![
+            "exchange",
+            "id",
+            "state",
+            "side",
+            "market",
+            "base_qty",
+            "quote",
+            "create_date",
+        ]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.orders
+            .iter()
+            .map(|o| {
+                vec![
+                    o.exchange.clone(),
+                    o.id.clone(),
+                    format!("{:?}", o.state),
+                    o.side.to_string(),
+                    o.market.clone(),
+                    o.base_qty.to_string(),
+                    o.quote.to_string(),
+                    o.create_date.clone(),
+                ]
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct RunReport {
+    pub order_id: String,
+    pub pair: String,
+    pub cost: f64,
+    pub profit: f64,
+    pub legs: Vec<LegReport>,
+    pub aborted: Option<String>,
+    pub unwound: bool,    // aborted after the ask leg, and its fills were sold off
+    pub fees: f64,        // quote token, from the fee ledger
+    pub gas: Option<f64>, // quote token
+    pub net_profit_usd: f64, // expected, after fees and gas
+    pub log: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LegReport {
+    pub exchange: String,
+    pub askbid: types::AskBid,
+    pub token: String,
+    pub total: f64,
+    pub sheets: usize,
+    pub errors: Vec<String>,
+    pub filled: Vec<String>, // order ids
+    pub failures: usize,     // sheets submitted that did not fill
+}
+
+// what running one leg's sheets did
+#[derive(Debug)]
+pub struct LegRun {
+    pub exchange: String,
+    pub askbid: types::AskBid,
+    pub filled: Vec<String>,
+    pub errors: Vec<String>,
+    pub swept: bool, // a balance was withdrawn back to the wallet
+}
+
+// a leg is filled when some sheet filled and none failed
+pub fn legs_filled(runs: &[LegRun]) -> bool {
+    runs.iter().any(|run| !run.filled.is_empty()) && runs.iter().all(|run| run.errors.is_empty())
+}
+
+impl fmt::Display for LegRun {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} filled {:?} errors {:?}",
+            self.exchange, self.askbid, self.filled, self.errors
+        )
+    }
+}
+
+impl RunReport {
+    pub fn new(order: &types::Order) -> RunReport {
+        RunReport {
+            order_id: order.id.clone(),
+            pair: order.pair.to_string(),
+            cost: order.cost,
+            profit: order.profit,
+            legs: vec![],
+            aborted: None,
+            unwound: false,
+            fees: 0.0,
+            gas: None,
+            net_profit_usd: 0.0,
+            log: vec![],
+        }
+    }
+
+    pub fn record_runs(&mut self, runs: Vec<LegRun>) {
+        for run in runs {
+            match self
+                .legs
+                .iter_mut()
+                .find(|leg| leg.exchange == run.exchange && leg.askbid == run.askbid)
+            {
+                Some(leg) => {
+                    leg.filled.extend(run.filled);
+                    leg.failures += run.errors.len();
+                    leg.errors.extend(run.errors);
+                }
+                None => log::warn!("no leg for {} {}", run.exchange, run.askbid),
+            }
+        }
+    }
+
+    pub fn abort(&mut self, run_log: &mut log::RunLog, reason: String) {
+        run_log.add(reason.clone());
+        self.aborted = Some(reason);
+    }
+
+    // whether any sheet went to an exchange, filled or not
+    pub fn submitted(&self) -> bool {
+        self.legs
+            .iter()
+            .any(|leg| !leg.filled.is_empty() || leg.failures > 0)
+    }
+
+    // why the run should alert: an abort, a leg with errors, or asks that
+    // filled with no bid fill to hedge them. a one sided order has no bid leg
+    pub fn failure(&self) -> Option<String> {
+        if let Some(reason) = &self.aborted {
+            return Some(reason.clone());
+        }
+        let errors: Vec<String> = self
+            .legs
+            .iter()
+            .filter(|leg| !leg.errors.is_empty())
+            .map(|leg| format!("{} {} {}", leg.exchange, leg.askbid, leg.errors.join("; ")))
+            .collect();
+        if !errors.is_empty() {
+            return Some(format!("leg errors: {}", errors.join(", ")));
+        }
+        let filled = |askbid| {
+            self.legs
+                .iter()
+                .any(|leg| leg.askbid == askbid && !leg.filled.is_empty())
+        };
+        let bid_leg = self.legs.iter().any(|leg| leg.askbid == types::AskBid::Bid);
+        if bid_leg && filled(types::AskBid::Ask) && !filled(types::AskBid::Bid) {
+            return Some("unhedged: asks filled without a bid fill".to_string());
+        }
+        None
+    }
+
+    pub fn unwind(&mut self, run_log: &mut log::RunLog, reason: String) {
+        self.abort(run_log, format!("{}. position unwound", reason));
+        self.unwound = true;
+    }
+}
+
+impl Tabular for RunReport {
+    fn headers() -> Vec<&'static str> {
+        vec![
+            "order_id", "pair", "cost", "profit", "exchange", "askbid", "token", "total", "sheets",
+            "filled", "errors", "aborted",
+        ]
+    }
+
+    // one row per leg. a run aborted before any leg gets one row for its reason
+    fn rows(&self) -> Vec<Vec<String>> {
+        if self.legs.is_empty() {
+            return vec![vec![
+                self.order_id.clone(),
+                self.pair.clone(),
+                self.cost.to_string(),
+                self.profit.to_string(),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+                "".to_string(),
+                self.aborted.clone().unwrap_or_default(),
+            ]];
+        }
+        self.legs
+            .iter()
+            .map(|leg| {
+                vec![
+                    self.order_id.clone(),
+                    self.pair.clone(),
+                    self.cost.to_string(),
+                    self.profit.to_string(),
+                    leg.exchange.clone(),
+                    leg.askbid.to_string(),
+                    leg.token.clone(),
+                    leg.total.to_string(),
+                    leg.sheets.to_string(),
+                    leg.filled.join("; "),
+                    leg.errors.join("; "),
+                    self.aborted.clone().unwrap_or_default(),
+                ]
+            })
+            .collect()
+    }
+}
+
+// the actions a run would take, built without signing or sending anything
+#[derive(Debug, Serialize, Default)]
+pub struct PlanReport {
+    pub order_id: String,
+    pub pair: String,
+    pub cost: f64,
+    pub profit: f64,
+    pub legs: Vec<LegReport>,
+    pub actions: Vec<PlanAction>,
+    pub gas_steps: Vec<String>,
+    pub expected_profit: f64, // quote token, after fees
+    pub fees: f64,
+    pub gas: Option<f64>, // quote token
+    pub net_profit_usd: f64,
+    pub go: bool,
+    pub log: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlanAction {
+    pub askbid: types::AskBid,
+    pub exchange: String,
+    pub action: String, // fill, deposit or sweep
+    pub token: String,
+    pub amount: f64,
+    pub price: Option<f64>,
+    pub fee: Option<f64>,
+}
+
+impl PlanReport {
+    pub fn new(order: &types::Order) -> PlanReport {
+        PlanReport {
+            order_id: order.id.clone(),
+            pair: order.pair.to_string(),
+            cost: order.cost,
+            profit: order.profit,
+            ..PlanReport::default()
+        }
+    }
+}
+
+impl fmt::Display for PlanReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "plan {} {} cost {:0.5} profit {:0.5}",
+            self.order_id, self.pair, self.cost, self.profit
+        )?;
+        for leg in &self.legs {
+            writeln!(
+                f,
+                "  {} {} {} total {:0.5} sheets {} {}",
+                leg.askbid,
+                leg.exchange,
+                leg.token,
+                leg.total,
+                leg.sheets,
+                leg.errors.join("; ")
+            )?;
+        }
+        for action in &self.actions {
+            let price = action
+                .price
+                .map(|p| format!(" @{:0.8}", p))
+                .unwrap_or_default();
+            let fee = action
+                .fee
+                .map(|p| format!(" fee {}", p))
+                .unwrap_or_default();
+            writeln!(
+                f,
+                "  {} {} {} {:0.5} {}{}{}",
+                action.askbid,
+                action.exchange,
+                action.action,
+                action.amount,
+                action.token,
+                price,
+                fee
+            )?;
+        }
+        for step in &self.gas_steps {
+            writeln!(f, "  gas {}", step)?;
+        }
+        let gas = self
+            .gas
+            .map(|g| format!("{:0.5}", g))
+            .unwrap_or_else(|| "unpriced".to_string());
+        write!(
+            f,
+            "expected profit {:0.5} fees {:0.5} gas {} net {:0.2} usd. {}",
+            self.expected_profit,
+            self.fees,
+            gas,
+            self.net_profit_usd,
+            if self.go { "GO" } else { "NO GO" }
+        )
+    }
+}
+
+impl Tabular for PlanReport {
+    fn headers() -> Vec<&'static str> {
+        vec![
+            "order_id", "pair", "askbid", "exchange", "action", "token", "amount", "price", "fee",
+        ]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.actions
+            .iter()
+            .map(|a| {
+                vec![
+                    self.order_id.clone(),
+                    self.pair.clone(),
+                    a.askbid.to_string(),
+                    a.exchange.clone(),
+                    a.action.clone(),
+                    a.token.clone(),
+                    a.amount.to_string(),
+                    a.price.map(|p| p.to_string()).unwrap_or_default(),
+                    a.fee.map(|p| p.to_string()).unwrap_or_default(),
+                ]
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Period {
+    Daily,
+    Weekly,
+}
+
+impl Period {
+    pub fn read(period: &str) -> Option<Self> {
+        match period {
+            "daily" => Some(Self::Daily),
+            "weekly" => Some(Self::Weekly),
+            _ => None,
+        }
+    }
+
+    pub fn secs(&self) -> u64 {
+        match self {
+            Self::Daily => 24 * 60 * 60,
+            Self::Weekly => 7 * 24 * 60 * 60,
+        }
+    }
+}
+
+impl fmt::Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let word = match self {
+            Self::Daily => "daily",
+            Self::Weekly => "weekly",
+        };
+        write!(f, "{}", word)
+    }
+}
+
+// the orders in history.jsonl over one period
+#[derive(Debug, Serialize)]
+pub struct PeriodReport {
+    pub period: Period,
+    pub from: String,
+    pub to: String,
+    pub attempted: usize,
+    pub executed: usize,
+    pub exchanges: Vec<ExchangeFills>,
+    pub predicted_usd: f64,
+    pub realized_usd: f64,
+    pub fees_usd: f64,
+    pub gas_usd: f64,
+    pub balance_change: BTreeMap<String, f64>, // usd by source
+    pub failures: Vec<FailureCount>,           // most frequent first
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct ExchangeFills {
+    pub exchange: String,
+    pub sheets: usize,
+    pub filled: usize,
+    pub failures: usize,
+    pub fill_rate: f64, // filled over submitted
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct FailureCount {
+    pub reason: String, // metrics abort label
+    pub count: usize,
+}
+
+impl PeriodReport {
+    pub fn build(period: Period, records: &[history::Record], to: u64) -> PeriodReport {
+        let from = to.saturating_sub(period.secs());
+        let records: Vec<&history::Record> = records
+            .iter()
+            .filter(|record| record.time >= from && record.time <= to)
+            .collect();
+        let mut exchanges: BTreeMap<String, ExchangeFills> = BTreeMap::new();
+        let mut balance_change = BTreeMap::new();
+        let mut failures: BTreeMap<&str, usize> = BTreeMap::new();
+        for record in &records {
+            for leg in &record.legs {
+                let fills =
+                    exchanges
+                        .entry(leg.exchange.clone())
+                        .or_insert_with(|| ExchangeFills {
+                            exchange: leg.exchange.clone(),
+                            sheets: 0,
+                            filled: 0,
+                            failures: 0,
+                            fill_rate: 0.0,
+                        });
+                fills.sheets += leg.sheets;
+                fills.filled += leg.filled;
+                fills.failures += leg.failures;
+            }
+            for (source, change) in &record.balance_change {
+                *balance_change.entry(source.clone()).or_insert(0.0) += change;
+            }
+            if let Some(reason) = &record.aborted {
+                *failures.entry(metrics::abort_label(reason)).or_insert(0) += 1;
+            }
+        }
+        let exchanges = exchanges
+            .into_values()
+            .map(|mut fills| {
+                let submitted = fills.filled + fills.failures;
+                if submitted > 0 {
+                    fills.fill_rate = fills.filled as f64 / submitted as f64
+                }
+                fills
+            })
+            .collect();
+        let mut failures: Vec<FailureCount> = failures
+            .into_iter()
+            .map(|(reason, count)| FailureCount {
+                reason: reason.to_string(),
+                count,
+            })
+            .collect();
+        failures.sort_by_key(|failure| std::cmp::Reverse(failure.count));
+        // fees and gas are only paid by records with a filled leg, and only
+        // executed records keep their predicted profit
+        let filled: Vec<&&history::Record> = records
+            .iter()
+            .filter(|record| record.legs.iter().any(|leg| leg.filled > 0))
+            .collect();
+        PeriodReport {
+            period,
+            from: time::epoch_string(from),
+            to: time::epoch_string(to),
+            attempted: records.len(),
+            executed: records
+                .iter()
+                .filter(|record| record.aborted.is_none())
+                .count(),
+            exchanges,
+            predicted_usd: filled
+                .iter()
+                .filter(|record| record.aborted.is_none())
+                .map(|record| record.predicted_usd)
+                .sum(),
+            realized_usd: records
+                .iter()
+                .filter_map(|record| record.realized_usd)
+                .sum(),
+            fees_usd: filled.iter().map(|record| record.fees_usd).sum(),
+            gas_usd: filled.iter().map(|record| record.gas_usd).sum(),
+            balance_change,
+            failures,
+        }
+    }
+
+    pub fn subject(&self) -> String {
+        format!(
+            "yith {} report: {} of {} orders, {:0.2} usd",
+            self.period, self.executed, self.attempted, self.realized_usd
+        )
+    }
+
+    pub fn html(&self) -> String {
+        let mut out = vec![format!("<h2>{}</h2>", escape(&self.subject()))];
+        out.push(format!(
+            "<p>{} to {}</p>",
+            escape(&self.from),
+            escape(&self.to)
+        ));
+        out.push("<table>".to_string());
+        for (name, value) in self.totals() {
+            out.push(format!("<tr><th>{}</th><td>{}</td></tr>", name, value));
+        }
+        out.push("</table>".to_string());
+        out.push("<h3>fills</h3><table>".to_string());
+        out.push("<tr><th>exchange</th><th>sheets</th><th>filled</th><th>failures</th><th>fill rate</th></tr>".to_string());
+        for fills in &self.exchanges {
+            out.push(format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{:0.1}%</td></tr>",
+                escape(&fills.exchange),
+                fills.sheets,
+                fills.filled,
+                fills.failures,
+                fills.fill_rate * 100.0
+            ));
+        }
+        out.push("</table>".to_string());
+        out.push("<h3>balance change</h3><table>".to_string());
+        for (source, change) in &self.balance_change {
+            out.push(format!(
+                "<tr><td>{}</td><td>{:0.2} usd</td></tr>",
+                escape(source),
+                change
+            ));
+        }
+        out.push("</table>".to_string());
+        out.push("<h3>top failures</h3><table>".to_string());
+        for failure in &self.failures {
+            out.push(format!(
+                "<tr><td>{}</td><td>{}</td></tr>",
+                escape(&failure.reason),
+                failure.count
+            ));
+        }
+        out.push("</table>".to_string());
+        out.join("\n")
+    }
+
+    fn totals(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("attempted", self.attempted.to_string()),
+            ("executed", self.executed.to_string()),
+            ("predicted", format!("{:0.2} usd", self.predicted_usd)),
+            ("realized", format!("{:0.2} usd", self.realized_usd)),
+            ("fees", format!("{:0.2} usd", self.fees_usd)),
+            ("gas", format!("{:0.2} usd", self.gas_usd)),
+        ]
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+impl fmt::Display for PeriodReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.subject())?;
+        writeln!(f, "{} to {}", self.from, self.to)?;
+        for (name, value) in self.totals() {
+            writeln!(f, "  {:10} {}", name, value)?;
+        }
+        writeln!(f, "fills")?;
+        for fills in &self.exchanges {
+            writeln!(
+                f,
+                "  {:10} {} of {} submitted, {:0.1}% ({} sheets)",
+                fills.exchange,
+                fills.filled,
+                fills.filled + fills.failures,
+                fills.fill_rate * 100.0,
+                fills.sheets
+            )?;
+        }
+        writeln!(f, "balance change")?;
+        for (source, change) in &self.balance_change {
+            writeln!(f, "  {:10} {:0.2} usd", source, change)?;
+        }
+        write!(f, "top failures")?;
+        for failure in &self.failures {
+            write!(f, "\n  {:10} {}", failure.reason, failure.count)?;
+        }
+        Ok(())
+    }
+}
+
+impl Tabular for PeriodReport {
+    fn headers() -> Vec<&'static str> {
+        vec![
+            "period",
+            "from",
+            "to",
+            "exchange",
+            "sheets",
+            "filled",
+            "failures",
+            "fill_rate",
+        ]
+    }
+
+    fn rows(&self) -> Vec<Vec<String>> {
+        self.exchanges
+            .iter()
+            .map(|fills| {
+                vec![
+                    self.period.to_string(),
+                    self.from.clone(),
+                    self.to.clone(),
+                    fills.exchange.clone(),
+                    fills.sheets.to_string(),
+                    fills.filled.to_string(),
+                    fills.failures.to_string(),
+                    fills.fill_rate.to_string(),
+                ]
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_csv() {
+        let report = OrdersReport {
+            orders: vec![OrderReport {
+                exchange: "idex".to_string(),
+                id: "0xab".to_string(),
+                state: exchange::OrderState::Open,
+                side: exchange::BuySell::Buy,
+                market: "DAI,ETH".to_string(),
+                base_qty: 1.5,
+                quote: 0.004,
+                create_date: "2020-01-01".to_string(),
+            }],
+        };
+        let out = csv(&report);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(
+            lines[0],
+            "exchange,id,state,side,market,base_qty,quote,create_date"
+        );
+        assert_eq!(
+            lines[1],
+            "idex,0xab,Open,buy,\"DAI,ETH\",1.5,0.004,2020-01-01"
+        );
+    }
+
+    #[test]
+    fn test_plan_csv() {
+        let mut report = PlanReport {
+            order_id: "abc".to_string(),
+            pair: "DAI-ETH".to_string(),
+            ..PlanReport::default()
+        };
+        report.actions.push(PlanAction {
+            askbid: types::AskBid::Ask,
+            exchange: "idex".to_string(),
+            action: "deposit".to_string(),
+            token: "ETH".to_string(),
+            amount: 0.5,
+            price: None,
+            fee: None,
+        });
+        let out = csv(&report);
+        assert_eq!(
+            out.lines().nth(1),
+            Some("abc,DAI-ETH,ASK,idex,deposit,ETH,0.5,,")
+        );
+        assert!(report.to_string().ends_with("NO GO"));
+    }
+
+    #[test]
+    fn test_record_runs() {
+        let mut report = RunReport {
+            order_id: "abc".to_string(),
+            pair: "DAI-ETH".to_string(),
+            cost: 1.0,
+            profit: 0.01,
+            legs: vec![LegReport {
+                exchange: "idex".to_string(),
+                askbid: types::AskBid::Ask,
+                token: "DAI".to_string(),
+                total: 1.0,
+                sheets: 2,
+                errors: vec![],
+                filled: vec![],
+                failures: 0,
+            }],
+            aborted: None,
+            unwound: false,
+            fees: 0.0,
+            gas: None,
+            net_profit_usd: 0.0,
+            log: vec![],
+        };
+        assert!(!report.submitted());
+        report.record_runs(vec![LegRun {
+            exchange: "idex".to_string(),
+            askbid: types::AskBid::Ask,
+            filled: vec!["0x01".to_string()],
+            errors: vec!["transaction Cancelled".to_string()],
+            swept: false,
+        }]);
+        assert_eq!(report.legs[0].filled, vec!["0x01"]);
+        assert_eq!(report.legs[0].failures, 1);
+        assert!(report.submitted());
+        assert_eq!(
+            report.failure(),
+            Some("leg errors: idex ASK transaction Cancelled".to_string())
+        );
+        report.legs[0].errors.clear();
+        assert_eq!(report.failure(), None); // one sided, nothing to hedge
+        report.legs.push(LegReport {
+            exchange: "idex".to_string(),
+            askbid: types::AskBid::Bid,
+            token: "ETH".to_string(),
+            total: 1.0,
+            sheets: 1,
+            errors: vec![],
+            filled: vec![],
+            failures: 0,
+        });
+        assert_eq!(
+            report.failure(),
+            Some("unhedged: asks filled without a bid fill".to_string())
+        );
+        report.legs.pop();
+        report.legs[0].errors = vec!["transaction Cancelled".to_string()];
+        assert_eq!(
+            report.rows()[0][9..],
+            [
+                "0x01".to_string(),
+                "transaction Cancelled".to_string(),
+                "".to_string()
+            ]
+        );
+        report.legs.clear();
+        report.aborted = Some("order expired".to_string());
+        assert_eq!(
+            csv(&report).lines().nth(1),
+            Some("abc,DAI-ETH,1,0.01,,,,,,,,order expired")
+        );
+    }
+
+    #[test]
+    fn test_legs_filled() {
+        let leg_run = |filled: &[&str], errors: &[&str]| LegRun {
+            exchange: "idex".to_string(),
+            askbid: types::AskBid::Bid,
+            filled: filled.iter().map(|id| id.to_string()).collect(),
+            errors: errors.iter().map(|e| e.to_string()).collect(),
+            swept: false,
+        };
+        assert!(legs_filled(&[leg_run(&["0x01"], &[]), leg_run(&[], &[])]));
+        assert!(!legs_filled(&[
+            leg_run(&["0x01"], &[]),
+            leg_run(&[], &["Cancelled"])
+        ]));
+        assert!(!legs_filled(&[leg_run(&[], &[])]));
+        assert!(!legs_filled(&[]));
+    }
+
+    #[test]
+    fn test_render() {
+        let report = OrdersReport { orders: vec![] };
+        assert_eq!(
+            render(Format::Json, &report),
+            Some("{\"orders\":[]}".to_string())
+        );
+        assert_eq!(render(Format::Table, &report), None);
+        assert_eq!(Format::read("csv"), Some(Format::Csv));
+        assert_eq!(Format::read("xml"), None);
+    }
+
+    #[test]
+    fn test_period_report() {
+        let record = |time, aborted: Option<&str>, filled, failures| history::Record {
+            time,
+            order_id: "abc".to_string(),
+            pair: "DAI-ETH".to_string(),
+            aborted: aborted.map(|reason| reason.to_string()),
+            predicted_usd: 1.0,
+            realized_usd: Some(0.5),
+            fees_usd: 0.1,
+            gas_usd: 0.2,
+            legs: vec![history::Leg {
+                exchange: "idex".to_string(),
+                askbid: types::AskBid::Ask,
+                sheets: 2,
+                filled,
+                failures,
+            }],
+            balance_change: [("idex".to_string(), 0.5)].into_iter().collect(),
+        };
+        let now = 10 * 24 * 60 * 60;
+        let records = vec![
+            record(now - 8 * 24 * 60 * 60, None, 1, 0), // outside the week
+            record(
+                now - 3 * 24 * 60 * 60,
+                Some("order expired! 61.0s old"),
+                0,
+                0,
+            ),
+            record(now - 60, None, 1, 1),
+            record(now - 30, Some("submit aborted! halted: deploy"), 0, 0),
+            record(now - 10, Some("order expired! 70.0s old"), 0, 0),
+        ];
+        let daily = PeriodReport::build(Period::Daily, &records, now);
+        assert_eq!((daily.attempted, daily.executed), (3, 1));
+        assert_eq!(daily.exchanges[0].fill_rate, 0.5);
+        assert_eq!(daily.balance_change["idex"], 1.5);
+        assert_eq!(daily.predicted_usd, 1.0);
+        assert!((daily.fees_usd - 0.1).abs() < 1e-9);
+        assert!((daily.gas_usd - 0.2).abs() < 1e-9);
+        assert_eq!(
+            daily.failures,
+            vec![
+                FailureCount {
+                    reason: "expired".to_string(),
+                    count: 1
+                },
+                FailureCount {
+                    reason: "halted".to_string(),
+                    count: 1
+                },
+            ]
+        );
+        let weekly = PeriodReport::build(Period::Weekly, &records, now);
+        assert_eq!(weekly.attempted, 4);
+        assert_eq!(weekly.failures[0].count, 2);
+        assert!((weekly.realized_usd - 2.0).abs() < 1e-9);
+        assert!(
+            weekly
+                .to_string()
+                .starts_with("yith weekly report: 1 of 4 orders")
+        );
+        assert!(weekly.html().contains("<td>idex</td>"));
+        assert_eq!(Period::read("monthly"), None);
+    }
+}
@@ -0,0 +1,20 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static STOP: AtomicBool = AtomicBool::new(false);
+
+// SIGINT/SIGTERM ask the daemon to stop once the current order is done.
+// a second signal exits right away.
+pub fn install() {
+    ctrlc::set_handler(|| {
+        if STOP.swap(true, Ordering::SeqCst) {
+            log::warn!("second signal. exiting now");
+            std::process::exit(130);
+        }
+        log::info!("shutdown requested. finishing the current order");
+    })
+    .expect("signal handler")
+}
+
+pub fn requested() -> bool {
+    STOP.load(Ordering::SeqCst)
+}
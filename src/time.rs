@@ -19,6 +19,36 @@ pub fn now_string() -> String {
     now.format("%FT%T%.3f").to_string()
 }
 
+// epoch seconds in the format of now_string
+pub fn epoch_string(secs: u64) -> String {
+    match chrono::DateTime::from_timestamp(secs as i64, 0) {
+        Some(datetime) => datetime
+            .with_timezone(&chrono::Local)
+            .format("%FT%T%.3f")
+            .to_string(),
+        None => secs.to_string(),
+    }
+}
+
+pub fn today_string() -> String {
+    let now = chrono::Local::now();
+    now.format("%F").to_string()
+}
+
+// order timestamps: rfc3339, or local time as written by now_string
+pub fn parse_millis(date: &str) -> Option<i64> {
+    if let Ok(datetime) = chrono::DateTime::parse_from_rfc3339(date) {
+        return Some(datetime.timestamp_millis());
+    }
+    let naive = chrono::NaiveDateTime::parse_from_str(date, "%FT%T%.f")
+        .or_else(|_| chrono::NaiveDateTime::parse_from_str(date, "%F %T%.f"))
+        .ok()?;
+    naive
+        .and_local_timezone(chrono::Local)
+        .single()
+        .map(|datetime| datetime.timestamp_millis())
+}
+
 pub fn sleep(ms: u64) {
     thread::sleep(Duration::from_millis(ms))
 }
@@ -0,0 +1,445 @@
+use crate::config;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::error;
+use std::fmt;
+use std::fs;
+
+pub static FILENAME: &str = "tokens.yaml";
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Token {
+    pub symbol: String,
+    pub address: String,
+    pub decimals: i32,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TokenFile {
+    pub tokens: Vec<Token>,
+    #[serde(default)]
+    pub overrides: HashMap<String, HashMap<String, String>>, // exchange -> exchange symbol -> symbol
+    #[serde(default)]
+    pub sources: Vec<SourceFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SourceFile {
+    pub name: String,
+    pub format: SourceFormat,
+    pub file: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub enum SourceFormat {
+    #[serde(rename = "switcheo")]
+    Switcheo,
+    #[serde(rename = "idex")]
+    Idex,
+    #[serde(rename = "0x")]
+    Zeroex,
+}
+
+#[derive(Debug)]
+pub struct Registry {
+    tokens: HashMap<String, Token>,   // keyed by contract address
+    symbols: HashMap<String, String>, // symbol and aliases -> contract address
+    overrides: HashMap<String, HashMap<String, String>>,
+    sources: Vec<SourceFile>,
+    collisions: Vec<Conflict>,
+}
+
+impl Registry {
+    pub fn load(filename: &str) -> Registry {
+        let file: TokenFile = config::read_type(filename);
+        Registry::build(file)
+    }
+
+    pub fn build(file: TokenFile) -> Registry {
+        let mut registry = Registry {
+            tokens: HashMap::new(),
+            symbols: HashMap::new(),
+            overrides: file.overrides,
+            sources: file.sources,
+            collisions: vec![],
+        };
+        for token in file.tokens {
+            registry.add(token);
+        }
+        registry
+    }
+
+    fn add(&mut self, mut token: Token) {
+        token.symbol = token.symbol.to_uppercase();
+        token.address = normalize_address(&token.address);
+        let mut names = vec![token.symbol.clone()];
+        names.extend(token.aliases.iter().map(|a| a.to_uppercase()));
+        for name in names {
+            match self.symbols.get(&name) {
+                Some(address) if *address != token.address => {
+                    self.collisions.push(Conflict::Address {
+                        symbol: name.clone(),
+                        sources: sorted(
+                            vec![
+                                ("registry".to_string(), address.clone()),
+                                ("registry".to_string(), token.address.clone()),
+                            ]
+                            .into_iter(),
+                        ),
+                    });
+                }
+                _ => {
+                    self.symbols.insert(name, token.address.clone());
+                }
+            }
+        }
+        if let Some(existing) = self.tokens.get(&token.address) {
+            if existing.decimals != token.decimals {
+                self.collisions.push(Conflict::Decimals {
+                    symbol: token.symbol.clone(),
+                    address: token.address.clone(),
+                    sources: sorted(
+                        vec![
+                            ("registry".to_string(), existing.decimals),
+                            ("registry".to_string(), token.decimals),
+                        ]
+                        .into_iter(),
+                    ),
+                });
+            }
+        } else {
+            self.tokens.insert(token.address.clone(), token);
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.tokens.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens.is_empty()
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<&Token> {
+        self.symbols
+            .get(&symbol.to_uppercase())
+            .and_then(|address| self.tokens.get(address))
+    }
+
+    pub fn by_addr(&self, address: &str) -> Option<&Token> {
+        self.tokens.get(&normalize_address(address))
+    }
+
+    // lookup a symbol as named by an exchange
+    pub fn get_on(&self, exchange: &str, symbol: &str) -> Option<&Token> {
+        self.get(&self.canonical_symbol(exchange, symbol))
+    }
+
+    pub fn canonical_symbol(&self, exchange: &str, symbol: &str) -> String {
+        let symbol = symbol.to_uppercase();
+        match self.overrides.get(exchange).and_then(|o| o.get(&symbol)) {
+            Some(canonical) => canonical.to_uppercase(),
+            None => symbol,
+        }
+    }
+
+    // the name an exchange uses for a registry symbol
+    pub fn exchange_symbol(&self, exchange: &str, symbol: &str) -> String {
+        let symbol = symbol.to_uppercase();
+        let overridden = self.overrides.get(exchange).and_then(|o| {
+            o.iter()
+                .find(|(_theirs, ours)| ours.to_uppercase() == symbol)
+                .map(|(theirs, _ours)| theirs.clone())
+        });
+        overridden.unwrap_or(symbol)
+    }
+
+    pub fn check(&self) -> Vec<Conflict> {
+        let mut entries: Vec<(String, Token)> = self
+            .tokens
+            .values()
+            .map(|t| ("registry".to_string(), t.clone()))
+            .collect();
+        let mut conflicts = self.collisions.clone();
+        for source in &self.sources {
+            match read_source(source) {
+                Ok(tokens) => {
+                    log::info!(
+                        "{} {} tokens from {}",
+                        source.name,
+                        tokens.len(),
+                        source.file
+                    );
+                    for mut token in tokens {
+                        token.symbol = self.canonical_symbol(&source.name, &token.symbol);
+                        entries.push((source.name.clone(), token));
+                    }
+                }
+                Err(e) => log::warn!("{} source {} unreadable: {}", source.name, source.file, e),
+            }
+        }
+        for conflict in find_conflicts(&entries) {
+            if !conflicts.contains(&conflict) {
+                conflicts.push(conflict);
+            }
+        }
+        conflicts
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Conflict {
+    Decimals {
+        symbol: String,
+        address: String,
+        sources: Vec<(String, i32)>,
+    },
+    Address {
+        symbol: String,
+        sources: Vec<(String, String)>,
+    },
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Conflict::Decimals {
+                symbol,
+                address,
+                sources,
+            } => {
+                let parts: Vec<String> = sources
+                    .iter()
+                    .map(|(source, decimals)| format!("{}={}", source, decimals))
+                    .collect();
+                write!(
+                    f,
+                    "{} {} decimals conflict: {}",
+                    symbol,
+                    address,
+                    parts.join(" ")
+                )
+            }
+            Conflict::Address { symbol, sources } => {
+                let parts: Vec<String> = sources
+                    .iter()
+                    .map(|(source, address)| format!("{}={}", source, address))
+                    .collect();
+                write!(f, "{} address conflict: {}", symbol, parts.join(" "))
+            }
+        }
+    }
+}
+
+pub fn find_conflicts(entries: &[(String, Token)]) -> Vec<Conflict> {
+    let mut by_addr: BTreeMap<&str, Vec<(&str, &Token)>> = BTreeMap::new();
+    let mut by_symbol: BTreeMap<&str, Vec<(&str, &Token)>> = BTreeMap::new();
+    for (source, token) in entries {
+        by_addr
+            .entry(token.address.as_str())
+            .or_default()
+            .push((source, token));
+        by_symbol
+            .entry(token.symbol.as_str())
+            .or_default()
+            .push((source, token));
+    }
+
+    let mut conflicts = vec![];
+    for (address, group) in by_addr {
+        if group
+            .iter()
+            .any(|(_s, t)| t.decimals != group[0].1.decimals)
+        {
+            conflicts.push(Conflict::Decimals {
+                symbol: group[0].1.symbol.clone(),
+                address: address.to_string(),
+                sources: sorted(group.iter().map(|(s, t)| (s.to_string(), t.decimals))),
+            });
+        }
+    }
+    for (symbol, group) in by_symbol {
+        if group.iter().any(|(_s, t)| t.address != group[0].1.address) {
+            conflicts.push(Conflict::Address {
+                symbol: symbol.to_string(),
+                sources: sorted(
+                    group
+                        .iter()
+                        .map(|(s, t)| (s.to_string(), t.address.clone())),
+                ),
+            });
+        }
+    }
+    conflicts
+}
+
+fn sorted<T: Ord>(items: impl Iterator<Item = T>) -> Vec<T> {
+    let mut items: Vec<T> = items.collect();
+    items.sort();
+    items
+}
+
+pub fn normalize_address(address: &str) -> String {
+    format!("0x{}", address.trim_start_matches("0x").to_lowercase())
+}
+
+#[derive(Debug, Deserialize)]
+struct SwitcheoToken {
+    symbol: String,
+    hash: String,
+    decimals: i32,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdexToken {
+    address: String,
+    decimals: i32,
+}
+
+#[derive(Debug, Deserialize)]
+struct ZeroexToken {
+    symbol: String,
+    address: String,
+    decimals: i32,
+}
+
+pub fn read_source(source: &SourceFile) -> Result<Vec<Token>, Box<dyn error::Error>> {
+    let yaml = fs::read_to_string(&source.file)?;
+    let tokens: Vec<Token> = match source.format {
+        SourceFormat::Switcheo => serde_yaml::from_str::<HashMap<String, SwitcheoToken>>(&yaml)?
+            .into_values()
+            .map(|t| source_token(&t.symbol, &t.hash, t.decimals))
+            .collect(),
+        SourceFormat::Idex => serde_yaml::from_str::<HashMap<String, IdexToken>>(&yaml)?
+            .into_iter()
+            .map(|(symbol, t)| source_token(&symbol, &t.address, t.decimals))
+            .collect(),
+        SourceFormat::Zeroex => serde_yaml::from_str::<Vec<ZeroexToken>>(&yaml)?
+            .into_iter()
+            .map(|t| source_token(&t.symbol, &t.address, t.decimals))
+            .collect(),
+    };
+    Ok(tokens
+        .into_iter()
+        .filter(|t| t.address != "0x" && !t.symbol.is_empty())
+        .collect())
+}
+
+fn source_token(symbol: &str, address: &str, decimals: i32) -> Token {
+    Token {
+        symbol: symbol.to_uppercase(),
+        address: normalize_address(address),
+        decimals,
+        aliases: vec![],
+    }
+}
+
+// the global registry with DAI and ETH, for adapter tests
+#[cfg(test)]
+pub fn test_registry() -> &'static Registry {
+    config::TOKENS.get_or_init(|| {
+        Registry::build(TokenFile {
+            tokens: vec![
+                source_token("DAI", "0x6b175474e89094c44da98b954eedeac495271d0f", 18),
+                source_token("ETH", "0x0000000000000000000000000000000000000000", 18),
+            ],
+            overrides: HashMap::new(),
+            sources: vec![],
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static WETH_ADDR: &str = "0xC02aaA39b223FE8D0A0e5C4F27eAD9083C756Cc2";
+
+    fn token(symbol: &str, address: &str, decimals: i32) -> Token {
+        Token {
+            symbol: symbol.to_string(),
+            address: address.to_string(),
+            decimals,
+            aliases: vec![],
+        }
+    }
+
+    fn registry() -> Registry {
+        let mut weth = token("WETH", WETH_ADDR, 18);
+        weth.aliases.push("weth9".to_string());
+        let mut overrides = HashMap::new();
+        let mut idex = HashMap::new();
+        idex.insert("ETHW".to_string(), "WETH".to_string());
+        overrides.insert("idex".to_string(), idex);
+        Registry::build(TokenFile {
+            tokens: vec![
+                token("ETH", "0x0000000000000000000000000000000000000000", 18),
+                weth,
+            ],
+            overrides,
+            sources: vec![],
+        })
+    }
+
+    #[test]
+    fn test_lookup() {
+        let registry = registry();
+        assert_eq!(registry.len(), 2);
+        assert_eq!(registry.get("weth").unwrap().decimals, 18);
+        assert_eq!(registry.get("WETH9").unwrap().symbol, "WETH");
+        let by_addr = registry.by_addr(&WETH_ADDR.to_lowercase()).unwrap();
+        assert_eq!(by_addr.symbol, "WETH");
+        assert!(registry.get("DAI").is_none());
+    }
+
+    #[test]
+    fn test_overrides() {
+        let registry = registry();
+        assert_eq!(registry.get_on("idex", "ETHW").unwrap().symbol, "WETH");
+        assert_eq!(registry.get_on("switcheo", "WETH").unwrap().symbol, "WETH");
+        assert_eq!(registry.exchange_symbol("idex", "WETH"), "ETHW");
+        assert_eq!(registry.exchange_symbol("switcheo", "WETH"), "WETH");
+    }
+
+    #[test]
+    fn test_find_conflicts() {
+        let weth = normalize_address(WETH_ADDR);
+        let entries = vec![
+            ("registry".to_string(), token("WETH", &weth, 18)),
+            ("idex".to_string(), token("WETH", &weth, 8)),
+            ("switcheo".to_string(), token("WETH", "0x1234", 18)),
+        ];
+        let conflicts = find_conflicts(&entries);
+        assert_eq!(conflicts.len(), 2);
+        match &conflicts[0] {
+            Conflict::Decimals { sources, .. } => assert_eq!(sources.len(), 2),
+            other => panic!("unexpected {}", other),
+        }
+        match &conflicts[1] {
+            Conflict::Address { symbol, sources } => {
+                assert_eq!(symbol, "WETH");
+                assert_eq!(sources.len(), 3)
+            }
+            other => panic!("unexpected {}", other),
+        }
+    }
+
+    #[test]
+    fn test_load() {
+        let registry = Registry::load(FILENAME);
+        assert_eq!(registry.get("ETH").unwrap().decimals, 18);
+        assert_eq!(registry.get("tether").unwrap().symbol, "USDT");
+    }
+
+    #[test]
+    fn test_registry_collisions() {
+        let registry = Registry::build(TokenFile {
+            tokens: vec![token("DAI", "0x01", 18), token("DAI", "0x02", 18)],
+            overrides: HashMap::new(),
+            sources: vec![],
+        });
+        assert_eq!(registry.check().len(), 1);
+    }
+}
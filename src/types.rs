@@ -14,22 +14,78 @@ pub struct Order {
     pub fee_network: f64,
     pub quote_usd: f64,
     pub network_usd: f64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ttl_secs: Option<u64>, // the order's own max age
+}
+
+#[derive(Debug)]
+pub struct OrderError {
+    pub msg: String,
+}
+
+impl std::error::Error for OrderError {}
+
+impl fmt::Display for OrderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.msg)
+    }
 }
 
 impl Order {
-    pub fn from_file(arb_id: String) -> Order {
-        let filename = format!("{}/order", arb_id);
-        let json = std::fs::read_to_string(filename).expect("order json file bad");
-        let order: Order = serde_yaml::from_str(&json).unwrap();
-        order
+    // a .json or yaml file, or a directory holding the scanner's `order` file
+    pub fn from_file(path: &str) -> Result<Order, OrderError> {
+        let path = std::path::Path::new(path);
+        let filename = if path.is_dir() {
+            path.join("order")
+        } else {
+            path.to_path_buf()
+        };
+        let text = std::fs::read_to_string(&filename).map_err(|e| OrderError {
+            msg: format!("{} {}", filename.display(), e),
+        })?;
+        let json = filename.extension().is_some_and(|ext| ext == "json");
+        Order::parse(&text, json).map_err(|e| OrderError {
+            msg: format!("{} {}", filename.display(), e.msg),
+        })
+    }
+
+    // parse errors name the field path along with serde's line and column
+    pub fn parse(text: &str, json: bool) -> Result<Order, OrderError> {
+        if json {
+            let mut de = serde_json::Deserializer::from_str(text);
+            serde_path_to_error::deserialize(&mut de)
+                .map_err(|e| path_error(e.path(), e.inner()))
+                .and_then(|order| match de.end() {
+                    Ok(()) => Ok(order),
+                    Err(e) => Err(OrderError { msg: e.to_string() }),
+                })
+        } else {
+            let de = serde_yaml::Deserializer::from_str(text);
+            serde_path_to_error::deserialize(de).map_err(|e| path_error(e.path(), e.inner()))
+        }
+    }
+}
+
+fn path_error(path: &serde_path_to_error::Path, e: &dyn std::error::Error) -> OrderError {
+    let field = path.to_string();
+    let msg = e.to_string();
+    if field == "." {
+        OrderError { msg }
+    } else {
+        // serde_yaml already leads with the path
+        let detail = msg.strip_prefix(&format!("{}: ", field)).unwrap_or(&msg);
+        OrderError {
+            msg: format!("field {}: {}", field, detail),
+        }
     }
 }
 
+// Field0/Field1 come from the upstream scanner's serializer
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Pair {
-    #[serde(rename = "Field0")]
+    #[serde(rename = "Field0", alias = "base")]
     pub base: String, //base
-    #[serde(rename = "Field1")]
+    #[serde(rename = "Field1", alias = "quote")]
     pub quote: String, //quote
 }
 
@@ -39,7 +95,7 @@ impl fmt::Display for Pair {
     }
 }
 
-#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
 pub enum AskBid {
     #[serde(rename = "ask")]
     Ask,
@@ -82,7 +138,7 @@ impl Books {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Book {
     pub market: Market,
     pub offers: Vec<Offer>,
@@ -93,9 +149,71 @@ impl Book {
     pub fn cost_total(&self, askbid: AskBid) -> f64 {
         self.offers.iter().map(|o| o.cost(askbid)).sum()
     }
+
+    // one offer for the summed quantity at the last (worst) price
+    pub fn rollup(&self) -> Offer {
+        Offer {
+            base_qty: self.offers.iter().map(|o| o.base_qty).sum(),
+            quote: self.offers.last().map(|o| o.quote).unwrap_or(0.0),
+        }
+    }
+
+    // one offer for the summed quantity at the volume weighted price
+    pub fn vwap(&self) -> Offer {
+        let base_qty: f64 = self.offers.iter().map(|o| o.base_qty).sum();
+        let quote_qty: f64 = self.offers.iter().map(|o| o.base_qty * o.quote).sum();
+        Offer {
+            base_qty,
+            quote: if base_qty > 0.0 {
+                quote_qty / base_qty
+            } else {
+                0.0
+            },
+        }
+    }
+
+    pub fn sheet_offers(&self, strategy: SheetStrategy) -> Vec<Offer> {
+        match strategy {
+            SheetStrategy::Rollup => vec![self.rollup()],
+            SheetStrategy::Vwap => vec![self.vwap()],
+            SheetStrategy::Levels => self.offers.clone(),
+        }
+    }
+
+    // the same market re-read from the exchange. fresh offers, best first,
+    // fill the quantity this book planned for and the rest are dropped.
+    pub fn refreshed(&self, fresh: Vec<Offer>) -> Book {
+        let mut remaining: f64 = self.offers.iter().map(|o| o.base_qty).sum();
+        let mut offers = vec![];
+        for offer in fresh {
+            if remaining <= 0.0 {
+                break;
+            }
+            let base_qty = offer.base_qty.min(remaining);
+            remaining -= base_qty;
+            offers.push(Offer {
+                base_qty,
+                quote: offer.quote,
+            });
+        }
+        Book {
+            market: self.market.clone(),
+            offers,
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+// how a book's offers become order sheets, set per exchange
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SheetStrategy {
+    #[default]
+    Rollup, // one sheet at the worst price
+    Levels, // one sheet per price level
+    Vwap,   // one sheet at the volume weighted price
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Market {
     pub source: Source,
     pub base: Ticker,
@@ -121,7 +239,7 @@ impl fmt::Display for Market {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Source {
     pub name: String,
 }
@@ -145,7 +263,7 @@ impl From<&str> for Ticker {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Offer {
     pub base_qty: f64,
     pub quote: f64,
@@ -171,3 +289,121 @@ impl Offer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn book() -> Book {
+        Book {
+            market: Market {
+                source: Source {
+                    name: "idex".to_string(),
+                },
+                base: Ticker::from("DAI"),
+                base_contract: "0x".to_string(),
+                quote: Ticker::from("ETH"),
+                quote_contract: "0x".to_string(),
+                swapped: false,
+                quantity_decimals: 0.0,
+                price_decimals: 0.0,
+                min_order_size: "0".to_string(),
+            },
+            offers: vec![
+                Offer {
+                    base_qty: 30.0,
+                    quote: 0.01,
+                },
+                Offer {
+                    base_qty: 10.0,
+                    quote: 0.014,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_sheet_offers() {
+        let book = book();
+        let rollup = book.sheet_offers(SheetStrategy::Rollup);
+        assert_eq!(rollup.len(), 1);
+        assert_eq!(rollup[0].base_qty, 40.0);
+        assert_eq!(rollup[0].quote, 0.014);
+        let vwap = book.sheet_offers(SheetStrategy::Vwap);
+        assert!((vwap[0].quote - 0.011).abs() < 1e-9);
+        let levels = book.sheet_offers(SheetStrategy::Levels);
+        assert_eq!(levels.len(), 2);
+        assert_eq!(levels[0].quote, 0.01);
+    }
+
+    #[test]
+    fn test_refreshed() {
+        let book = book();
+        let fresh = book.refreshed(vec![
+            Offer {
+                base_qty: 25.0,
+                quote: 0.009,
+            },
+            Offer {
+                base_qty: 50.0,
+                quote: 0.008,
+            },
+            Offer {
+                base_qty: 5.0,
+                quote: 0.007,
+            },
+        ]);
+        assert_eq!(fresh.offers.len(), 2);
+        assert_eq!(fresh.offers[1].base_qty, 15.0);
+        assert_eq!(fresh.offers[1].quote, 0.008);
+        assert!(book.refreshed(vec![]).offers.is_empty());
+    }
+
+    fn order_json(pair: &str) -> String {
+        let mut order = serde_json::json!({
+            "id": "abc",
+            "date": "2020-01-01",
+            "ask_books": {"askbid": "ask", "books": []},
+            "bid_books": {"askbid": "bid", "books": [{
+                "market": serde_json::to_value(&book().market).unwrap(),
+                "offers": [{"base_qty": 30.0, "quote": 0.01}]
+            }]},
+            "cost": 1.0,
+            "trade_profit": 0.1,
+            "profit": 0.09,
+            "fee_network": 0.01,
+            "quote_usd": 200.0,
+            "network_usd": 200.0,
+        });
+        order["pair"] = serde_json::from_str(pair).unwrap();
+        serde_json::to_string_pretty(&order).unwrap()
+    }
+
+    #[test]
+    fn test_parse_order() {
+        let legacy = order_json(r#"{"Field0": "DAI", "Field1": "ETH"}"#);
+        let order = Order::parse(&legacy, false).unwrap(); // yaml reads json too
+        assert_eq!(order.pair.to_string(), "DAI-ETH");
+        let clean = order_json(r#"{"base": "DAI", "quote": "ETH"}"#);
+        assert_eq!(Order::parse(&clean, true).unwrap().pair.base, "DAI");
+
+        let bad = clean.replace("30.0", "\"thirty\"");
+        let err = Order::parse(&bad, true).unwrap_err();
+        assert!(
+            err.msg
+                .starts_with("field bid_books.books[0].offers[0].base_qty: invalid type"),
+            "{}",
+            err.msg
+        );
+        assert!(err.msg.contains("line "), "{}", err.msg);
+        let err = Order::parse(&bad, false).unwrap_err();
+        assert!(
+            err.msg
+                .starts_with("field bid_books.books[0].offers[0].base_qty: invalid type"),
+            "{}",
+            err.msg
+        );
+        let missing = Order::parse("id: abc\n", false).unwrap_err();
+        assert!(missing.msg.contains("missing field"), "{}", missing.msg);
+    }
+}
@@ -0,0 +1,156 @@
+use crate::types;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+pub static DEFAULT_MAX_LOSS: f64 = 0.02;
+pub static DUST_FRACTION: f64 = 0.001; // of the bought qty, left over counts as done
+
+// tried in the configured order until the bought tokens are sold
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Policy {
+    SellBack, // sell on the exchanges the asks bought from
+    NextBest, // sell into the order's bid books, best price first
+    Hold,     // keep the tokens and alert
+}
+
+impl fmt::Display for Policy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let word = match self {
+            Policy::SellBack => "sell_back",
+            Policy::NextBest => "next_best",
+            Policy::Hold => "hold",
+        };
+        write!(f, "{}", word)
+    }
+}
+
+pub fn default_policies() -> Vec<Policy> {
+    vec![Policy::Hold]
+}
+
+// lowest price accepted when selling back what was bought at ask_price
+pub fn floor_price(ask_price: f64, max_loss: f64) -> f64 {
+    ask_price * (1.0 - max_loss)
+}
+
+// one bid book per ask exchange, for the remaining qty at the floor price
+pub fn sell_back(asks: &types::Books, remaining: f64, floor: f64) -> Vec<types::Book> {
+    asks.books
+        .iter()
+        .map(|book| types::Book {
+            market: book.market.clone(),
+            offers: vec![types::Offer {
+                base_qty: remaining,
+                quote: floor,
+            }],
+        })
+        .collect()
+}
+
+// the order's bid books, best first, keeping offers at or above the floor
+pub fn next_best(bids: &types::Books, floor: f64) -> Vec<types::Book> {
+    let mut books: Vec<types::Book> = bids
+        .books
+        .iter()
+        .map(|book| types::Book {
+            market: book.market.clone(),
+            offers: book
+                .offers
+                .iter()
+                .filter(|offer| offer.quote >= floor)
+                .cloned()
+                .collect(),
+        })
+        .filter(|book| !book.offers.is_empty())
+        .collect();
+    books.sort_by(|a, b| best_quote(b).partial_cmp(&best_quote(a)).unwrap());
+    books
+}
+
+fn best_quote(book: &types::Book) -> f64 {
+    book.offers.first().map(|offer| offer.quote).unwrap_or(0.0)
+}
+
+#[derive(Debug)]
+pub struct Attempt {
+    pub policy: Policy,
+    pub exchange: String,
+    pub floor: f64,
+    pub offered: f64,
+    pub before: f64,
+    pub after: f64,
+}
+
+impl fmt::Display for Attempt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unwind {} {} floor {:0.8} offered {:0.5} remaining {:0.5} -> {:0.5}",
+            self.policy, self.exchange, self.floor, self.offered, self.before, self.after
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn book(exchange: &str, quotes: &[f64]) -> types::Book {
+        types::Book {
+            market: types::Market {
+                source: types::Source {
+                    name: exchange.to_string(),
+                },
+                base: types::Ticker::from("DAI"),
+                base_contract: "0x".to_string(),
+                quote: types::Ticker::from("ETH"),
+                quote_contract: "0x".to_string(),
+                swapped: false,
+                quantity_decimals: 0.0,
+                price_decimals: 0.0,
+                min_order_size: "0".to_string(),
+            },
+            offers: quotes
+                .iter()
+                .map(|quote| types::Offer {
+                    base_qty: 10.0,
+                    quote: *quote,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_next_best() {
+        let bids = types::Books {
+            askbid: types::AskBid::Bid,
+            books: vec![
+                book("idex", &[0.0098, 0.0090]),
+                book("switcheo", &[0.0099]),
+                book("radarrelay", &[0.0080]),
+            ],
+        };
+        let floor = floor_price(0.01, 0.02);
+        assert!((floor - 0.0098).abs() < 1e-12);
+        let books = next_best(&bids, floor - 1e-12);
+        let names: Vec<&str> = books
+            .iter()
+            .map(|b| b.market.source.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["switcheo", "idex"]);
+        assert_eq!(books[1].offers.len(), 1);
+    }
+
+    #[test]
+    fn test_sell_back() {
+        let asks = types::Books {
+            askbid: types::AskBid::Ask,
+            books: vec![book("idex", &[0.01])],
+        };
+        let books = sell_back(&asks, 5.0, 0.0098);
+        assert_eq!(books[0].market.source.name, "idex");
+        assert_eq!(books[0].offers[0].base_qty, 5.0);
+        assert_eq!(books[0].offers[0].quote, 0.0098);
+    }
+}
@@ -0,0 +1,434 @@
+use crate::config;
+use crate::exchange;
+use crate::time;
+use crate::tokens;
+use crate::types;
+
+pub static COST_TOLERANCE: f64 = 0.02; // fraction the order cost may differ from its ask books
+pub static DEFAULT_MAX_ORDER_AGE_SECS: u64 = 60;
+
+// consistency checks on an incoming order, one line per problem
+pub fn check(
+    order: &types::Order,
+    exchanges: &config::ExchangeList,
+    registry: &tokens::Registry,
+) -> Vec<String> {
+    let mut problems = vec![];
+    check_books(
+        "ask_books",
+        &order.ask_books,
+        types::AskBid::Ask,
+        exchanges,
+        registry,
+        &mut problems,
+    );
+    check_books(
+        "bid_books",
+        &order.bid_books,
+        types::AskBid::Bid,
+        exchanges,
+        registry,
+        &mut problems,
+    );
+    let ask_cost = order.ask_books.cost_total();
+    let tolerance = order.cost.abs().max(ask_cost) * COST_TOLERANCE;
+    if !order.cost.is_finite() || (order.cost - ask_cost).abs() > tolerance {
+        problems.push(format!(
+            "cost {:0.5} does not match the ask books total {:0.5}",
+            order.cost, ask_cost
+        ))
+    }
+    problems
+}
+
+fn check_books(
+    field: &str,
+    books: &types::Books,
+    askbid: types::AskBid,
+    exchanges: &config::ExchangeList,
+    registry: &tokens::Registry,
+    problems: &mut Vec<String>,
+) {
+    if books.askbid != askbid {
+        problems.push(format!(
+            "{}.askbid is {} not {}",
+            field, books.askbid, askbid
+        ))
+    }
+    for (i, book) in books.books.iter().enumerate() {
+        let at = format!("{}.books[{}]", field, i);
+        let market = &book.market;
+        let name = &market.source.name;
+        match exchanges.find_by_name(name) {
+            None => problems.push(format!("{} exchange {} is unknown", at, name)),
+            Some(exchange) if !exchange.settings.enabled => {
+                problems.push(format!("{} exchange {} is disabled", at, name))
+            }
+            Some(exchange) => check_pair(&at, market, exchange, problems),
+        }
+        check_contract(
+            &at,
+            "base",
+            name,
+            &market.base,
+            &market.base_contract,
+            registry,
+            problems,
+        );
+        check_contract(
+            &at,
+            "quote",
+            name,
+            &market.quote,
+            &market.quote_contract,
+            registry,
+            problems,
+        );
+        if book.offers.is_empty() {
+            problems.push(format!("{} has no offers", at))
+        }
+        for (j, offer) in book.offers.iter().enumerate() {
+            if !(offer.base_qty.is_finite() && offer.base_qty > 0.0) {
+                problems.push(format!(
+                    "{}.offers[{}].base_qty {} is not positive",
+                    at, j, offer.base_qty
+                ))
+            }
+            if !(offer.quote.is_finite() && offer.quote > 0.0) {
+                problems.push(format!(
+                    "{}.offers[{}].quote {} is not positive",
+                    at, j, offer.quote
+                ))
+            }
+        }
+    }
+}
+
+fn check_contract(
+    at: &str,
+    field: &str,
+    exchange_name: &str,
+    ticker: &types::Ticker,
+    contract: &str,
+    registry: &tokens::Registry,
+    problems: &mut Vec<String>,
+) {
+    match registry.get_on(exchange_name, &ticker.symbol) {
+        None => problems.push(format!(
+            "{} {} {} is not in the token registry",
+            at, field, ticker
+        )),
+        Some(token) if token.address != tokens::normalize_address(contract) => {
+            problems.push(format!(
+                "{} {}_contract {} is not the registry's {} {}",
+                at, field, contract, ticker, token.address
+            ))
+        }
+        Some(_) => (),
+    }
+}
+
+fn exchange_market(market: &types::Market) -> exchange::Market {
+    exchange::Market {
+        base: market.base.clone(),
+        base_contract: market.base_contract.clone(),
+        quote: market.quote.clone(),
+        quote_contract: market.quote_contract.clone(),
+        quantity_decimals: market.quantity_decimals,
+        price_decimals: market.price_decimals,
+        source_name: market.source.name.clone(),
+    }
+}
+
+// the swapped flag says the exchange lists the pair as quote/base
+fn check_pair(
+    at: &str,
+    market: &types::Market,
+    exchange: &config::Exchange,
+    problems: &mut Vec<String>,
+) {
+    let listed = exchange_market(market);
+    let flipped = exchange::Market {
+        base: market.quote.clone(),
+        base_contract: market.quote_contract.clone(),
+        quote: market.base.clone(),
+        quote_contract: market.base_contract.clone(),
+        quantity_decimals: market.quantity_decimals,
+        price_decimals: market.price_decimals,
+        source_name: market.source.name.clone(),
+    };
+    match (
+        exchange.api.has_market(&listed),
+        exchange.api.has_market(&flipped),
+    ) {
+        (Some(true), _) if market.swapped => problems.push(format!(
+            "{} is swapped but {} lists {}",
+            at, exchange, listed
+        )),
+        (Some(false), Some(true)) if !market.swapped => problems.push(format!(
+            "{} is not swapped but {} lists {}",
+            at, exchange, flipped
+        )),
+        (Some(false), Some(false)) => {
+            problems.push(format!("{} {} has no {} pair", at, exchange, listed))
+        }
+        _ => (),
+    }
+}
+
+// book exchanges without a pair list, so check_pair could not say whether
+// the market exists or which way round it is listed
+pub fn unchecked_pairs(order: &types::Order, exchanges: &config::ExchangeList) -> Vec<String> {
+    let mut names: Vec<String> = vec![];
+    for book in order.ask_books.books.iter().chain(&order.bid_books.books) {
+        let market = &book.market;
+        if let Some(exchange) = exchanges.find_by_name(&market.source.name)
+            && exchange.api.has_market(&exchange_market(market)).is_none()
+            && !names.contains(&exchange.settings.name)
+        {
+            names.push(exchange.settings.name.clone())
+        }
+    }
+    names
+}
+
+// the tightest age limit for books: the order's own ttl and, unless default
+// is None, each book exchange's override or the default
+pub fn max_age(
+    order: &types::Order,
+    books: &types::Books,
+    exchanges: &config::ExchangeList,
+    default: Option<u64>,
+) -> Option<u64> {
+    let exchange_limit = default.map(|default| {
+        books
+            .books
+            .iter()
+            .map(|book| {
+                exchanges
+                    .find_by_name(&book.market.source.name)
+                    .and_then(|exchange| exchange.settings.max_order_age_secs)
+                    .unwrap_or(default)
+            })
+            .min()
+            .unwrap_or(default)
+    });
+    [order.ttl_secs, exchange_limit].into_iter().flatten().min()
+}
+
+pub fn check_age(date: &str, max_age_secs: u64, now_millis: i64) -> Result<(), String> {
+    match time::parse_millis(date) {
+        Some(millis) => {
+            let age_secs = (now_millis - millis) as f64 / 1000.0;
+            if age_secs > max_age_secs as f64 {
+                Err(format!(
+                    "order is {:0.1}s old, over the {}s limit",
+                    age_secs, max_age_secs
+                ))
+            } else {
+                Ok(())
+            }
+        }
+        None => Err(format!("order date {:?} not understood", date)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    // lists this one pair, or has no pair list
+    struct Listed {
+        pair: Option<&'static str>,
+    }
+
+    impl exchange::Api for Listed {
+        fn build(
+            &self,
+            _privkey: &str,
+            _askbid: &types::AskBid,
+            _exchange: &config::ExchangeSettings,
+            _market: &exchange::Market,
+            _offer: &types::Offer,
+        ) -> Result<exchange::OrderSheet, Box<dyn std::error::Error>> {
+            Err(exchange::ExchangeError::build_box(
+                "validate stub".to_string(),
+            ))
+        }
+
+        fn submit(
+            &self,
+            _private_key: &str,
+            _exchange: &config::ExchangeSettings,
+            _sheet: exchange::OrderSheet,
+        ) -> Result<String, Box<dyn std::error::Error>> {
+            Err(exchange::ExchangeError::build_box(
+                "validate stub".to_string(),
+            ))
+        }
+
+        fn has_market(&self, market: &exchange::Market) -> Option<bool> {
+            self.pair.map(|pair| market.id("/") == pair)
+        }
+    }
+
+    fn exchanges() -> config::ExchangeList {
+        let settings = config::ExchangeSettings {
+            name: "switcheo".to_string(),
+            enabled: true,
+            has_balances: true,
+            protocol: config::ExchangeProtocol::Switcheo,
+            contract_address: None,
+            fee_recipient_address: None,
+            api_url: "".to_string(),
+            maker_fee: 0.0,
+            taker_fee: 0.0,
+            sheets: types::SheetStrategy::Rollup,
+            rate_limit_ms: None,
+            max_order_age_secs: Some(30),
+        };
+        config::ExchangeList {
+            exchanges: vec![config::Exchange {
+                settings,
+                api: Box::new(Listed {
+                    pair: Some("DAI/ETH"),
+                }),
+                rate_limit: exchange::RateLimit::new(0),
+            }],
+        }
+    }
+
+    fn registry() -> tokens::Registry {
+        let token = |symbol: &str, address: &str| tokens::Token {
+            symbol: symbol.to_string(),
+            address: address.to_string(),
+            decimals: 18,
+            aliases: vec![],
+        };
+        tokens::Registry::build(tokens::TokenFile {
+            tokens: vec![token("DAI", "0xda1"), token("ETH", "0x0")],
+            overrides: HashMap::new(),
+            sources: vec![],
+        })
+    }
+
+    fn books(askbid: types::AskBid, exchange: &str, swapped: bool) -> types::Books {
+        types::Books {
+            askbid,
+            books: vec![types::Book {
+                market: types::Market {
+                    source: types::Source {
+                        name: exchange.to_string(),
+                    },
+                    base: types::Ticker::from("DAI"),
+                    base_contract: "0xDA1".to_string(),
+                    quote: types::Ticker::from("ETH"),
+                    quote_contract: "0x0".to_string(),
+                    swapped,
+                    quantity_decimals: 0.0,
+                    price_decimals: 0.0,
+                    min_order_size: "0".to_string(),
+                },
+                offers: vec![types::Offer {
+                    base_qty: 100.0,
+                    quote: 0.01,
+                }],
+            }],
+        }
+    }
+
+    fn order() -> types::Order {
+        types::Order {
+            id: "abc".to_string(),
+            date: "".to_string(),
+            pair: types::Pair {
+                base: "DAI".to_string(),
+                quote: "ETH".to_string(),
+            },
+            ask_books: books(types::AskBid::Ask, "switcheo", false),
+            bid_books: books(types::AskBid::Bid, "switcheo", false),
+            cost: 1.0,
+            trade_profit: 0.0,
+            profit: 0.0,
+            fee_network: 0.0,
+            quote_usd: 0.0,
+            network_usd: 0.0,
+            ttl_secs: None,
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let (exchanges, registry) = (exchanges(), registry());
+        assert_eq!(check(&order(), &exchanges, &registry), Vec::<String>::new());
+
+        let mut bad = order();
+        bad.ask_books.askbid = types::AskBid::Bid;
+        bad.ask_books.books[0].market.swapped = true;
+        bad.ask_books.books[0].offers[0].base_qty = -1.0;
+        bad.bid_books = books(types::AskBid::Bid, "oasis", false);
+        bad.bid_books.books[0].market.quote_contract = "0x1".to_string();
+        bad.cost = 2.0;
+        assert_eq!(
+            check(&bad, &exchanges, &registry),
+            vec![
+                "ask_books.askbid is BID not ASK",
+                "ask_books.books[0] is swapped but switcheo lists DAI/ETH",
+                "ask_books.books[0].offers[0].base_qty -1 is not positive",
+                "bid_books.books[0] exchange oasis is unknown",
+                "bid_books.books[0] quote_contract 0x1 is not the registry's ETH 0x0",
+                "cost 2.00000 does not match the ask books total -1.00000",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unchecked_pairs() {
+        let mut exchanges = exchanges();
+        assert!(unchecked_pairs(&order(), &exchanges).is_empty());
+        let mut settings = exchanges.exchanges[0].settings.clone();
+        settings.name = "idex".to_string();
+        exchanges.exchanges.push(config::Exchange {
+            settings,
+            api: Box::new(Listed { pair: None }),
+            rate_limit: exchange::RateLimit::new(0),
+        });
+        let mut order = order();
+        order.bid_books = books(types::AskBid::Bid, "idex", false);
+        order.bid_books.books.push(order.bid_books.books[0].clone());
+        assert_eq!(unchecked_pairs(&order, &exchanges), vec!["idex"]);
+    }
+
+    #[test]
+    fn test_max_age() {
+        let exchanges = exchanges();
+        let mut order = order();
+        let asks = &order.ask_books;
+        assert_eq!(max_age(&order, asks, &exchanges, Some(60)), Some(30)); // switcheo override
+        assert_eq!(max_age(&order, asks, &exchanges, None), None); // manual without ttl
+        order.ttl_secs = Some(10);
+        assert_eq!(
+            max_age(&order, &order.bid_books, &exchanges, None),
+            Some(10)
+        );
+        let unknown = books(types::AskBid::Ask, "oasis", false);
+        assert_eq!(max_age(&order, &unknown, &exchanges, Some(60)), Some(10));
+        order.ttl_secs = None;
+        assert_eq!(max_age(&order, &unknown, &exchanges, Some(60)), Some(60));
+    }
+
+    #[test]
+    fn test_check_age() {
+        let date = "2020-01-01T00:00:00Z";
+        let millis = time::parse_millis(date).unwrap();
+        assert_eq!(check_age(date, 60, millis + 59_000), Ok(()));
+        assert_eq!(
+            check_age(date, 60, millis + 61_000),
+            Err("order is 61.0s old, over the 60s limit".to_string())
+        );
+        assert!(check_age("yesterday", 60, millis).is_err());
+        let local = time::now_string();
+        assert_eq!(check_age(&local, 1, time::now_millis() as i64), Ok(()));
+    }
+}
@@ -1,13 +1,19 @@
+use crate::limits;
 use crate::price;
+use crate::report;
 use crate::time;
 use crate::types;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::fmt;
 
+pub static FILENAME: &str = "wallet.yaml";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Wallet {
     pub coins: Vec<WalletCoin>,
+    #[serde(default)]
+    pub limits: limits::Limits,
 }
 
 impl Wallet {
@@ -15,6 +21,15 @@ impl Wallet {
         self.coins.retain(|c| c.source == "limit");
     }
 
+    // held across the wallet address and every exchange
+    pub fn held(&self, symbol: &str) -> f64 {
+        self.coins
+            .iter()
+            .filter(|c| c.source != "limit" && c.ticker_symbol.eq_ignore_ascii_case(symbol))
+            .map(|c| c.base_total())
+            .sum()
+    }
+
     pub fn coin_limit(&self, name: &str) -> f64 {
         match self.find_coin_by_symbol(name) {
             Ok(coin) => coin.amounts[0].base_qty,
@@ -39,7 +54,7 @@ impl Wallet {
         Err(WalletError {})
     }
 
-    pub fn print_with_price(&self) {
+    pub fn report(&self) -> report::BalanceReport {
         let coin_gecko = price::CoinGecko::new();
         let coin_ids = self
             .coins
@@ -48,26 +63,48 @@ impl Wallet {
             .collect::<Vec<&str>>();
         let quote_symbol = "usd";
         let prices = coin_gecko.prices(coin_ids, quote_symbol);
-        println!("[wallet {}]", time::now_string());
-        let mut subtotals: HashMap<&str, f64> = HashMap::new();
+        let mut coins = vec![];
+        let mut subtotals: BTreeMap<String, f64> = BTreeMap::new();
         for coin in &self.coins {
             if coin.source != "limit" {
                 let percoin = prices.get(&coin.ticker_symbol).unwrap();
                 let quote_total = coin.base_total() * percoin;
-                let source = coin.source.as_ref(); // rust hashmap ptr wha
-                if !subtotals.contains_key(source) {
-                    subtotals.insert(source, 0.0);
-                }
-                subtotals.insert(source, subtotals.get(source).unwrap() + quote_total);
-                println!("{} {:8.5}{}", coin, quote_total, quote_symbol);
+                *subtotals.entry(coin.source.clone()).or_insert(0.0) += quote_total;
+                coins.push(report::CoinReport {
+                    source: coin.source.clone(),
+                    symbol: coin.ticker_symbol.clone(),
+                    contract: coin.contract.clone(),
+                    qty: coin.base_total(),
+                    price: *percoin,
+                    value: quote_total,
+                });
             }
         }
-        let mut total = 0.0;
-        for (source, subtotal) in subtotals {
-            println!("{:8.8} = {:9.5}{}", source, subtotal, quote_symbol);
-            total = total + subtotal;
+        let total = subtotals.values().sum();
+        report::BalanceReport {
+            time: time::now_string(),
+            quote_symbol: quote_symbol.to_string(),
+            coins,
+            subtotals,
+            total,
+        }
+    }
+
+    pub fn print_with_price(&self) {
+        self.print_report(&self.report())
+    }
+
+    pub fn print_report(&self, report: &report::BalanceReport) {
+        let quote_symbol = &report.quote_symbol;
+        log::info!("[wallet {}]", report.time);
+        let coins = self.coins.iter().filter(|c| c.source != "limit");
+        for (coin, coin_report) in coins.zip(&report.coins) {
+            log::info!("{} {:8.5}{}", coin, coin_report.value, quote_symbol);
+        }
+        for (source, subtotal) in &report.subtotals {
+            log::info!("{:8.8} = {:9.5}{}", source, subtotal, quote_symbol);
         }
-        println!("*Total   = {:9.5}{}", total, quote_symbol);
+        log::info!("*Total   = {:9.5}{}", report.total, quote_symbol);
     }
 }
 
@@ -1,4 +1,5 @@
 use crate::eth;
+use crate::gas;
 use crate::geth;
 
 pub struct Weth {}
@@ -25,23 +26,23 @@ impl Weth {
                 ethereum_types::U256::from_dec_str(amount).unwrap(),
             ),
         };
-        let gas_price_fast = geth::ethgasstation_fast();
-        let nonce = client.nonce(&pub_addr).unwrap();
+        let gas_price_fast = geth::ethgasstation_fast()?;
+        let nonce = client.next_nonce(&pub_addr).unwrap();
         let mut token_addr_bytes = [0u8; 20];
         token_addr_bytes.copy_from_slice(&eth::dehex(Weth::CONTRACT_ADDRESS)[..]);
         let tx = ethereum_tx_sign::RawTransaction {
-            nonce: ethereum_types::U256::from(nonce),
+            nonce: ethereum_types::U256::from(nonce.value),
             to: Some(ethereum_types::H160::from(token_addr_bytes)),
             value: value,
             gas_price: ethereum_types::U256::from(gas_price_fast),
-            gas: ethereum_types::U256::from(50000),
+            gas: ethereum_types::U256::from(gas::Kind::Wrap.gas_limit()),
             data: data,
         };
         let private_key = ethereum_types::H256::from_slice(&eth::dehex(private_key));
         let rlp_bytes = tx.sign(&private_key, &eth::ETH_CHAIN_MAINNET);
         let params = (eth::hex(&rlp_bytes),);
-        let tx = client.rpc_str("eth_sendRawTransaction", geth::ParamTypes::Single(params))?;
-        println!("GOOD TX {}", tx);
+        let tx = client.send_raw(nonce, params)?;
+        crate::log::sent_tx("GOOD TX", &tx);
         Ok(true)
     }
 }